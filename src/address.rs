@@ -0,0 +1,68 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::process::Command;
+
+use client;
+use error::CliError;
+
+/// Services answering with nothing but the public address of whoever asks, by address family
+pub const LOOKUP_V4: &'static str = "https://api.ipify.org";
+pub const LOOKUP_V6: &'static str = "https://api6.ipify.org";
+
+/// The public address of the host as seen by the service at `url`
+pub fn lookup(url: &str, v6: bool) -> Result<String, CliError> {
+    let answer = try!(client::fetch(url));
+    let answer = answer.trim();
+    if valid(answer, v6) { return Ok(answer.to_owned()) }
+    Err(CliError::Network(format!("{} answered '{}' rather than an {} address", url,
+        answer.chars().take(40).collect::<String>(), family(v6))))
+}
+
+/// The first address of a network interface that's reachable from elsewhere, which link local
+/// IPv6 addresses aren't. It's read from `ip addr`, or `ifconfig` where there's no `ip`.
+pub fn of_interface(iface: &str, v6: bool) -> Result<String, CliError> {
+    let err = |e: String| CliError::Validation(format!("couldn't read the addresses of {}: {}",
+        iface, e));
+    let flag = if v6 { "-6" } else { "-4" };
+    let out = Command::new("ip").args(&["-o", flag, "addr", "show", "dev", iface]).output()
+                                .or_else(|_| Command::new("ifconfig").arg(iface).output());
+    let out = match out {
+        Ok(out) => out,
+        Err(e)  => return Err(err(e.to_string()))
+    };
+    if !out.status.success() {
+        return Err(err(String::from_utf8_lossy(&out.stderr).trim().to_owned()))
+    }
+    match addresses(&String::from_utf8_lossy(&out.stdout), v6).into_iter().next() {
+        Some(a) => Ok(a),
+        None    => Err(err(format!("it has no {} address", family(v6))))
+    }
+}
+
+// The addresses following `inet` or `inet6` in the output of `ip` or either flavor of `ifconfig`,
+// which write them as `inet 10.0.0.1/24`, `inet 10.0.0.1` or `inet addr:10.0.0.1`
+fn addresses(text: &str, v6: bool) -> Vec<String> {
+    let keyword = if v6 { "inet6" } else { "inet" };
+    let mut found = vec![];
+    let mut words = text.split_whitespace();
+    while let Some(w) = words.next() {
+        if w != keyword { continue }
+        let a = match words.next() {
+            Some("addr:") => words.next().unwrap_or(""),
+            Some(a)       => a.trim_left_matches("addr:"),
+            None          => break
+        };
+        let a = a.split(|c| c == '/' || c == '%').next().unwrap_or("");
+        if valid(a, v6) && !a.to_lowercase().starts_with("fe80:") {
+            found.push(a.to_owned());
+        }
+    }
+    found
+}
+
+fn valid(a: &str, v6: bool) -> bool {
+    if v6 { a.parse::<Ipv6Addr>().is_ok() } else { a.parse::<Ipv4Addr>().is_ok() }
+}
+
+pub fn family(v6: bool) -> &'static str {
+    if v6 { "IPv6" } else { "IPv4" }
+}
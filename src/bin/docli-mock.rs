@@ -0,0 +1,972 @@
+//! An in-memory stand-in for the parts of the DigitalOcean v2 API that docli uses.
+//!
+//! Start it with `docli-mock [--port <port>] [--token <token>]`, the first line it prints is the
+//! API root to hand to `docli --api-url`. Every change (creating droplets, renaming keys, adding
+//! DNS records...) is kept for as long as the server runs. Droplet actions are reported as
+//! "in-progress" until they've been looked up once, then "completed", or "errored" for snapshots
+//! named `fail-*`. Every other action completes immediately.
+#[macro_use]
+extern crate clap;
+extern crate hyper;
+extern crate openssl;
+extern crate rustc_serialize;
+extern crate serde;
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use std::thread;
+
+use clap::App;
+use hyper::method::Method;
+use hyper::server::{Handler, Request, Response, Server};
+use hyper::status::StatusCode;
+use hyper::uri::RequestUri;
+use openssl::crypto::hash;
+use rustc_serialize::base64::FromBase64;
+use serde::json::{self, Value};
+
+const CREATED_AT: &'static str = "2015-09-01T12:00:00Z";
+const PER_PAGE: usize = 20;
+
+macro_rules! object {
+    ($($k:expr => $v:expr),* $(,)*) => {{
+        let mut m = BTreeMap::new();
+        $(m.insert($k.to_owned(), $v);)*
+        Value::Object(m)
+    }}
+}
+
+fn s(v: &str) -> Value { Value::String(v.to_owned()) }
+fn n(v: u64) -> Value { Value::U64(v) }
+fn strings(v: &[&str]) -> Value { Value::Array(v.iter().map(|i| s(i)).collect()) }
+
+fn region(slug: &str) -> Value {
+    let name = match slug {
+        "nyc3" => "New York 3",
+        "sfo1" => "San Francisco 1",
+        "ams3" => "Amsterdam 3",
+        _      => slug
+    };
+    object! {
+        "name" => s(name),
+        "slug" => s(slug),
+        "sizes" => strings(&["512mb", "1gb", "2gb"]),
+        "features" => strings(&["private_networking", "backups", "ipv6", "metadata"]),
+        "available" => Value::Bool(true),
+    }
+}
+
+fn size(slug: &str) -> Value {
+    let (mem, cpus, disk, price) = match slug {
+        "1gb" => (1024, 1, 30, 10.0),
+        "2gb" => (2048, 2, 40, 20.0),
+        _     => (512, 1, 20, 5.0)
+    };
+    object! {
+        "slug" => s(slug),
+        "memory" => n(mem),
+        "vcpus" => n(cpus),
+        "disk" => n(disk),
+        "transfer" => Value::F64(1.0),
+        "price_monthly" => Value::F64(price),
+        "price_hourly" => Value::F64(price / 672.0),
+        "regions" => strings(&["nyc3", "sfo1", "ams3"]),
+        "available" => Value::Bool(true),
+    }
+}
+
+fn kernel() -> Value {
+    object! {
+        "id" => n(2233),
+        "name" => s("Ubuntu 14.04 x64 vmlinuz-3.13.0-37-generic"),
+        "version" => s("3.13.0-37-generic"),
+    }
+}
+
+fn image(id: u64, name: &str, slug: Option<&str>, public: bool, img_type: &str) -> Value {
+    object! {
+        "id" => n(id),
+        "name" => s(name),
+        "distribution" => s("Ubuntu"),
+        "slug" => slug.map(s).unwrap_or(Value::Null),
+        "public" => Value::Bool(public),
+        "regions" => strings(&["nyc3", "sfo1"]),
+        "created_at" => s(CREATED_AT),
+        "min_disk_size" => n(20),
+        "type" => s(img_type),
+    }
+}
+
+fn network(ip: &str, netmask: &str, gateway: &str, net_type: &str) -> Value {
+    object! {
+        "ip_address" => s(ip),
+        "netmask" => s(netmask),
+        "gateway" => s(gateway),
+        "type" => s(net_type),
+    }
+}
+
+fn dns_record(id: u64, rec_type: &str, name: &str, data: &str, priority: Option<u64>) -> Value {
+    object! {
+        "id" => n(id),
+        "type" => s(rec_type),
+        "name" => s(name),
+        "data" => s(data),
+        "priority" => priority.map(n).unwrap_or(Value::Null),
+        "port" => Value::Null,
+        "weight" => Value::Null,
+    }
+}
+
+// The MD5 fingerprint of a public key like DigitalOcean computes it. Keys made up for tests that
+// aren't valid base64 are hashed into something shaped like one.
+fn fingerprint(pub_key: &str) -> String {
+    let blob = pub_key.split_whitespace().nth(1).and_then(|k| k.from_base64().ok());
+    if let Some(blob) = blob {
+        return hash::hash(hash::Type::MD5, &blob[..]).iter().map(|b| format!("{:02x}", b))
+                                                      .collect::<Vec<_>>().join(":")
+    }
+    let mut h: u64 = 0xcbf29ce484222325;
+    let mut out = vec![];
+    for i in 0..16 {
+        for b in pub_key.bytes() {
+            h ^= b as u64 ^ i;
+            h = h.wrapping_mul(0x100000001b3);
+        }
+        out.push(format!("{:02x}", h & 0xff));
+    }
+    out.join(":")
+}
+
+fn id_of(v: &Value) -> u64 {
+    v.find("id").and_then(|i| i.as_u64()).unwrap_or(0)
+}
+
+fn str_of<'a>(v: &'a Value, key: &str) -> &'a str {
+    v.find(key).and_then(|i| i.as_string()).unwrap_or("")
+}
+
+fn set(v: &mut Value, key: &str, val: Value) {
+    if let Value::Object(ref mut m) = *v {
+        m.insert(key.to_owned(), val);
+    }
+}
+
+struct Reply {
+    status: u16,
+    body: Option<Value>,
+}
+
+fn ok(body: Value) -> Reply { Reply { status: 200, body: Some(body) } }
+fn created(body: Value) -> Reply { Reply { status: 201, body: Some(body) } }
+fn accepted(body: Value) -> Reply { Reply { status: 202, body: Some(body) } }
+fn no_content() -> Reply { Reply { status: 204, body: None } }
+
+fn error(status: u16, id: &str, msg: &str) -> Reply {
+    Reply { status: status, body: Some(object! { "id" => s(id), "message" => s(msg) }) }
+}
+
+fn not_found() -> Reply {
+    error(404, "not_found", "The resource you were accessing could not be found.")
+}
+
+fn unprocessable(msg: &str) -> Reply {
+    error(422, "unprocessable_entity", msg)
+}
+
+struct State {
+    next_id: u64,
+    account: Value,
+    droplets: Vec<Value>,
+    images: Vec<Value>,
+    actions: Vec<Value>,
+    // Droplet actions still in progress, with the status they'll end up in
+    pending: BTreeMap<u64, &'static str>,
+    keys: Vec<Value>,
+    domains: Vec<Value>,
+    records: BTreeMap<String, Vec<Value>>,
+    ratelimit_remaining: u64,
+}
+
+impl State {
+    fn new() -> State {
+        let mut st = State {
+            next_id: 10000,
+            account: object! {
+                "droplet_limit" => n(25),
+                "email" => s("sammy@digitalocean.com"),
+                "uuid" => s("b6fr89dbf6d9156cace5f3c78dc9851d957381ef"),
+                "email_verified" => Value::Bool(true),
+            },
+            droplets: vec![],
+            images: vec![
+                image(2001, "14.04 x64", Some("ubuntu-14-04-x64"), true, "snapshot"),
+                image(2002, "WordPress on 14.04", Some("wordpress"), true, "snapshot"),
+                image(2003, "web-base", None, false, "snapshot"),
+            ],
+            actions: vec![],
+            pending: BTreeMap::new(),
+            keys: vec![object! {
+                "id" => n(3001),
+                "name" => s("laptop"),
+                "public_key" => s("ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQDaptop laptop@example.com"),
+                "fingerprint" => s(&fingerprint("ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQDaptop")[..]),
+            }],
+            domains: vec![],
+            records: BTreeMap::new(),
+            ratelimit_remaining: 5000,
+        };
+        st.add_droplet(1001, "web-1", "nyc3", "512mb", 2001, "active");
+        st.add_droplet(1002, "web-2", "nyc3", "1gb", 2001, "active");
+        st.add_droplet(1003, "db-1", "sfo1", "2gb", 2001, "off");
+        set(&mut st.droplets[0], "tags", strings(&["web"]));
+        set(&mut st.droplets[1], "tags", strings(&["web"]));
+        set(&mut st.droplets[2], "tags", strings(&["db"]));
+        st.add_domain("example.com", "10.0.0.1");
+        st.action("create", 1001, "droplet", "nyc3");
+        st
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn add_droplet(&mut self, id: u64, name: &str, reg: &str, sz: &str, img: u64, status: &str) {
+        let image = self.images.iter().find(|i| id_of(i) == img).cloned()
+                        .unwrap_or(image(img, "unknown", None, false, "snapshot"));
+        let size_v = size(sz);
+        let ip = format!("104.131.{}.{}", (id / 256) % 256, id % 256);
+        self.droplets.push(object! {
+            "id" => n(id),
+            "name" => s(name),
+            "memory" => size_v.find("memory").cloned().unwrap(),
+            "vcpus" => size_v.find("vcpus").cloned().unwrap(),
+            "disk" => size_v.find("disk").cloned().unwrap(),
+            "locked" => Value::Bool(false),
+            "status" => s(status),
+            "kernel" => kernel(),
+            "created_at" => s(CREATED_AT),
+            "features" => strings(&["virtio"]),
+            "backup_ids" => Value::Array(vec![]),
+            "next_backup_window" => Value::Null,
+            "snapshot_ids" => Value::Array(vec![]),
+            "image" => image,
+            "region" => region(reg),
+            "size" => size_v,
+            "size_slug" => s(sz),
+            "tags" => Value::Array(vec![]),
+            "networks" => object! {
+                "v4" => Value::Array(vec![network(&ip[..], "255.255.240.0", "104.131.0.1", "public")]),
+                "v6" => Value::Array(vec![]),
+            },
+        });
+    }
+
+    fn add_domain(&mut self, name: &str, ip: &str) {
+        self.domains.push(object! {
+            "name" => s(name),
+            "ttl" => n(1800),
+            "zone_file" => Value::Null,
+        });
+        let a = self.next_id();
+        let cname = self.next_id();
+        let mx = self.next_id();
+        let ns = self.next_id();
+        self.records.insert(name.to_owned(), vec![
+            dns_record(ns, "NS", "@", "ns1.digitalocean.com", None),
+            dns_record(a, "A", "@", ip, None),
+            dns_record(cname, "CNAME", "www", "@", None),
+            dns_record(mx, "MX", "@", &format!("mail.{}", name)[..], Some(10)),
+        ]);
+        self.refresh_zone(name);
+    }
+
+    // Keeps the zone file of a domain in line with its records
+    fn refresh_zone(&mut self, name: &str) {
+        let mut zone = format!("$ORIGIN {}.\n$TTL 1800\n", name);
+        for r in self.records.get(name).map(|r| &r[..]).unwrap_or(&[]) {
+            let pri = r.find("priority").and_then(|p| p.as_u64())
+                       .map(|p| format!("{} ", p)).unwrap_or(String::new());
+            zone.push_str(&format!("{} IN {} {}{}\n", str_of(r, "name"), str_of(r, "type"), pri,
+                str_of(r, "data"))[..]);
+        }
+        if let Some(d) = self.domains.iter_mut().find(|d| str_of(d, "name") == name) {
+            set(d, "zone_file", s(&zone[..]));
+        }
+    }
+
+    fn action(&mut self, kind: &str, resource: u64, resource_type: &str, reg: &str) -> Value {
+        let id = self.next_id();
+        let a = object! {
+            "id" => n(id),
+            "status" => s("completed"),
+            "type" => s(kind),
+            "started_at" => s(CREATED_AT),
+            "completed_at" => s(CREATED_AT),
+            "resource_id" => n(resource),
+            "resource_type" => s(resource_type),
+            "region" => region(reg),
+            "region_slug" => s(reg),
+        };
+        self.actions.push(a.clone());
+        a
+    }
+
+    fn droplet_index(&self, id: &str) -> Option<usize> {
+        self.droplets.iter().position(|d| id_of(d).to_string() == id)
+    }
+
+    fn image_index(&self, id: &str) -> Option<usize> {
+        self.images.iter().position(|i| id_of(i).to_string() == id || str_of(i, "slug") == id)
+    }
+
+    fn key_index(&self, id: &str) -> Option<usize> {
+        self.keys.iter().position(|k| id_of(k).to_string() == id || str_of(k, "fingerprint") == id)
+    }
+
+    fn route(&mut self, method: &Method, path: &[&str], query: &BTreeMap<String, String>,
+             body: &Value, root: &str) -> Reply {
+        let seg = |i: usize| path.get(i).map(|p| *p);
+        if path.len() > 4 { return not_found() }
+        match (method, seg(0), seg(1), seg(2), seg(3)) {
+            (&Method::Get, Some("account"), None, None, None) => ok(object! { "account" => self.account.clone() }),
+            (&Method::Get, Some("actions"), None, None, None) => {
+                let all = self.actions.clone();
+                page("actions", all, query, root, "actions")
+            },
+            (&Method::Get, Some("actions"), Some(id), None, None) => {
+                match self.actions.iter().find(|a| id_of(a).to_string() == id) {
+                    Some(a) => ok(object! { "action" => a.clone() }),
+                    None    => not_found()
+                }
+            },
+            (&Method::Get, Some("regions"), None, None, None) => {
+                let all = vec![region("nyc3"), region("sfo1"), region("ams3")];
+                page("regions", all, query, root, "regions")
+            },
+            (&Method::Get, Some("sizes"), None, None, None) => {
+                let all = vec![size("512mb"), size("1gb"), size("2gb")];
+                page("sizes", all, query, root, "sizes")
+            },
+            (&Method::Get, Some("images"), None, None, None) => {
+                let all = self.images.iter().filter(|i| {
+                    let public = i.find("public").and_then(|p| p.as_boolean()).unwrap_or(false);
+                    match query.get("type").map(|t| &t[..]) {
+                        Some("distributions") => public && str_of(i, "slug") != "wordpress",
+                        Some("applications")  => public && str_of(i, "slug") == "wordpress",
+                        Some("private")       => !public,
+                        _                     => true
+                    }
+                }).cloned().collect();
+                page("images", all, query, root, "images")
+            },
+            (&Method::Get, Some("images"), Some(id), None, None) => {
+                match self.image_index(id) {
+                    Some(i) => ok(object! { "image" => self.images[i].clone() }),
+                    None    => not_found()
+                }
+            },
+            (&Method::Put, Some("images"), Some(id), None, None) => {
+                match (self.image_index(id), body.find("name").and_then(|n| n.as_string())) {
+                    (Some(i), Some(name)) => {
+                        set(&mut self.images[i], "name", s(name));
+                        ok(object! { "image" => self.images[i].clone() })
+                    },
+                    (Some(_), None)       => unprocessable("Name is required"),
+                    (None, _)             => not_found()
+                }
+            },
+            (&Method::Delete, Some("images"), Some(id), None, None) => {
+                match self.image_index(id) {
+                    Some(i) => {
+                        self.images.remove(i);
+                        no_content()
+                    },
+                    None    => not_found()
+                }
+            },
+            (&Method::Get, Some("images"), Some(id), Some("actions"), None) => {
+                match self.image_index(id) {
+                    Some(i) => {
+                        let img = id_of(&self.images[i]);
+                        let all = self.actions.iter().filter(|a| {
+                            str_of(a, "resource_type") == "image" &&
+                                a.find("resource_id").and_then(|r| r.as_u64()) == Some(img)
+                        }).cloned().collect();
+                        page("actions", all, query, root, &format!("images/{}/actions", id)[..])
+                    },
+                    None    => not_found()
+                }
+            },
+            (&Method::Post, Some("images"), Some(id), Some("actions"), None) => {
+                let i = match self.image_index(id) {
+                    Some(i) => i,
+                    None    => return not_found()
+                };
+                let img = id_of(&self.images[i]);
+                match str_of(body, "type") {
+                    "transfer" => {
+                        let reg = str_of(body, "region").to_owned();
+                        if reg.is_empty() { return unprocessable("Region is required") }
+                        if !["nyc3", "sfo1", "ams3"].contains(&&reg[..]) {
+                            return unprocessable("Region is not available")
+                        }
+                        let mut regions = self.images[i].find("regions")
+                                                        .and_then(|r| r.as_array())
+                                                        .cloned()
+                                                        .unwrap_or(vec![]);
+                        if !regions.contains(&s(&reg[..])) { regions.push(s(&reg[..])); }
+                        set(&mut self.images[i], "regions", Value::Array(regions));
+                        accepted(object! { "action" => self.action("transfer", img, "image", &reg[..]) })
+                    },
+                    "convert"  => {
+                        set(&mut self.images[i], "type", s("snapshot"));
+                        accepted(object! { "action" => self.action("convert", img, "image", "nyc3") })
+                    },
+                    _          => unprocessable("Type is not a valid image action")
+                }
+            },
+            (&Method::Get, Some("images"), Some(id), Some("actions"), Some(a_id)) => {
+                if self.image_index(id).is_none() { return not_found() }
+                match self.actions.iter().find(|a| id_of(a).to_string() == a_id) {
+                    Some(a) => ok(object! { "action" => a.clone() }),
+                    None    => not_found()
+                }
+            },
+            (&Method::Get, Some("account"), Some("keys"), None, None) => {
+                let all = self.keys.clone();
+                page("ssh_keys", all, query, root, "account/keys")
+            },
+            (&Method::Post, Some("account"), Some("keys"), None, None) => {
+                let name = str_of(body, "name").to_owned();
+                let public_key = str_of(body, "public_key").to_owned();
+                if name.is_empty() { return unprocessable("Name is required") }
+                if !public_key.starts_with("ssh-") && !public_key.starts_with("ecdsa-") {
+                    return unprocessable("Key invalid, key should be of the form 'type key [comment]'")
+                }
+                // Keys are identified by the first two fields, comments don't matter
+                let fp = fingerprint(&public_key.split_whitespace().take(2).collect::<Vec<_>>().join(" ")[..]);
+                if self.keys.iter().any(|k| str_of(k, "fingerprint") == fp) {
+                    return unprocessable("SSH Key is already in use on your account")
+                }
+                let id = self.next_id();
+                let key = object! {
+                    "id" => n(id),
+                    "name" => s(&name[..]),
+                    "public_key" => s(&public_key[..]),
+                    "fingerprint" => s(&fp[..]),
+                };
+                self.keys.push(key.clone());
+                created(object! { "ssh_key" => key })
+            },
+            (&Method::Get, Some("account"), Some("keys"), Some(id), None) => {
+                match self.key_index(id) {
+                    Some(i) => ok(object! { "ssh_key" => self.keys[i].clone() }),
+                    None    => not_found()
+                }
+            },
+            (&Method::Put, Some("account"), Some("keys"), Some(id), None) => {
+                match (self.key_index(id), body.find("name").and_then(|n| n.as_string())) {
+                    (Some(i), Some(name)) => {
+                        set(&mut self.keys[i], "name", s(name));
+                        ok(object! { "ssh_key" => self.keys[i].clone() })
+                    },
+                    (Some(_), None)       => unprocessable("Name is required"),
+                    (None, _)             => not_found()
+                }
+            },
+            (&Method::Delete, Some("account"), Some("keys"), Some(id), None) => {
+                match self.key_index(id) {
+                    Some(i) => {
+                        self.keys.remove(i);
+                        no_content()
+                    },
+                    None    => not_found()
+                }
+            },
+            (&Method::Get, Some("droplets"), None, None, None) => {
+                let all = self.droplets.clone();
+                page("droplets", all, query, root, "droplets")
+            },
+            (&Method::Post, Some("droplets"), None, None, None) => self.create_droplet(body),
+            (&Method::Get, Some("droplets"), Some(id), None, None) => {
+                match self.droplet_index(id) {
+                    Some(i) => ok(object! { "droplet" => self.droplets[i].clone() }),
+                    None    => not_found()
+                }
+            },
+            (&Method::Delete, Some("droplets"), Some(id), None, None) => {
+                match self.droplet_index(id) {
+                    Some(i) => {
+                        self.droplets.remove(i);
+                        no_content()
+                    },
+                    None    => not_found()
+                }
+            },
+            (&Method::Get, Some("droplets"), Some(id), Some(what), None) => {
+                let i = match self.droplet_index(id) {
+                    Some(i) => i,
+                    None    => return not_found()
+                };
+                let d = id_of(&self.droplets[i]);
+                let base = format!("droplets/{}/{}", id, what);
+                match what {
+                    "kernels"   => page("kernels", vec![kernel()], query, root, &base[..]),
+                    "backups"   => page("backups", vec![], query, root, &base[..]),
+                    "snapshots" => {
+                        let ids = self.droplets[i].find("snapshot_ids")
+                                                  .and_then(|s| s.as_array())
+                                                  .cloned()
+                                                  .unwrap_or(vec![]);
+                        let all = self.images.iter().filter(|img| {
+                            ids.contains(&s(&id_of(img).to_string()[..]))
+                        }).cloned().collect();
+                        page("snapshots", all, query, root, &base[..])
+                    },
+                    "actions"   => {
+                        let all = self.actions.iter().filter(|a| {
+                            str_of(a, "resource_type") == "droplet" &&
+                                a.find("resource_id").and_then(|r| r.as_u64()) == Some(d)
+                        }).cloned().collect();
+                        page("actions", all, query, root, &base[..])
+                    },
+                    "neighbors" => {
+                        let reg = self.droplets[i].find_path(&["region", "slug"]).cloned();
+                        let all = self.droplets.iter().filter(|o| {
+                            id_of(o) != d && o.find_path(&["region", "slug"]).cloned() == reg
+                        }).cloned().collect();
+                        ok(object! { "droplets" => Value::Array(all) })
+                    },
+                    _           => not_found()
+                }
+            },
+            (&Method::Post, Some("droplets"), Some(id), Some("actions"), None) => {
+                match self.droplet_index(id) {
+                    Some(i) => self.droplet_action(i, body),
+                    None    => not_found()
+                }
+            },
+            (&Method::Get, Some("droplets"), Some(id), Some("actions"), Some(a_id)) => {
+                if self.droplet_index(id).is_none() { return not_found() }
+                let i = match self.actions.iter().position(|a| id_of(a).to_string() == a_id) {
+                    Some(i) => i,
+                    None    => return not_found()
+                };
+                let reply = ok(object! { "action" => self.actions[i].clone() });
+                // Only finish once someone has seen it in progress
+                if let Some(status) = self.pending.remove(&id_of(&self.actions[i])) {
+                    set(&mut self.actions[i], "status", s(status));
+                }
+                reply
+            },
+            (&Method::Get, Some("reports"), Some("droplet_neighbors"), None, None) => {
+                let mut groups: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+                for d in self.droplets.iter() {
+                    let reg = d.find_path(&["region", "slug"]).and_then(|r| r.as_string())
+                               .unwrap_or("").to_owned();
+                    groups.entry(reg).or_insert(vec![]).push(d.clone());
+                }
+                let all = groups.into_iter()
+                                .map(|(_, g)| g)
+                                .filter(|g| g.len() > 1)
+                                .map(Value::Array)
+                                .collect();
+                ok(object! { "neighbors" => Value::Array(all) })
+            },
+            (&Method::Get, Some("droplet_upgrades"), None, None, None) => ok(Value::Array(vec![])),
+            (&Method::Get, Some("domains"), None, None, None) => {
+                let all = self.domains.clone();
+                page("domains", all, query, root, "domains")
+            },
+            (&Method::Post, Some("domains"), None, None, None) => {
+                let name = str_of(body, "name").to_owned();
+                let ip = str_of(body, "ip_address").to_owned();
+                if name.is_empty() || !name.contains('.') { return unprocessable("Name is invalid") }
+                if ip.parse::<std::net::IpAddr>().is_err() {
+                    return unprocessable("Ip address is invalid")
+                }
+                if self.domains.iter().any(|d| str_of(d, "name") == name) {
+                    return unprocessable("Name already exists")
+                }
+                self.add_domain(&name[..], &ip[..]);
+                let d = self.domains.last().cloned().unwrap();
+                created(object! { "domain" => d })
+            },
+            (&Method::Get, Some("domains"), Some(name), None, None) => {
+                match self.domains.iter().find(|d| str_of(d, "name") == name) {
+                    Some(d) => ok(object! { "domain" => d.clone() }),
+                    None    => not_found()
+                }
+            },
+            (&Method::Delete, Some("domains"), Some(name), None, None) => {
+                match self.domains.iter().position(|d| str_of(d, "name") == name) {
+                    Some(i) => {
+                        self.domains.remove(i);
+                        self.records.remove(name);
+                        no_content()
+                    },
+                    None    => not_found()
+                }
+            },
+            (&Method::Get, Some("domains"), Some(name), Some("records"), None) => {
+                match self.records.get(name).cloned() {
+                    Some(all) => page("domain_records", all, query, root,
+                                      &format!("domains/{}/records", name)[..]),
+                    None      => not_found()
+                }
+            },
+            (&Method::Post, Some("domains"), Some(name), Some("records"), None) => {
+                if !self.records.contains_key(name) { return not_found() }
+                let rec_type = str_of(body, "type").to_owned();
+                if rec_type.is_empty() { return unprocessable("Type is required") }
+                let data = str_of(body, "data").to_owned();
+                if data.is_empty() && rec_type != "NS" { return unprocessable("Data needs to be a valid value") }
+                let id = self.next_id();
+                let mut rec = dns_record(id, &rec_type[..], str_of(body, "name"), &data[..], None);
+                for k in ["priority", "port", "weight"].iter() {
+                    if let Some(v) = body.find(k) { set(&mut rec, k, v.clone()); }
+                }
+                self.records.get_mut(name).unwrap().push(rec.clone());
+                self.refresh_zone(name);
+                created(object! { "domain_record" => rec })
+            },
+            (&Method::Get, Some("domains"), Some(name), Some("records"), Some(id)) => {
+                match self.records.get(name).and_then(|r| r.iter().find(|r| id_of(r).to_string() == id)) {
+                    Some(r) => ok(object! { "domain_record" => r.clone() }),
+                    None    => not_found()
+                }
+            },
+            (&Method::Put, Some("domains"), Some(name), Some("records"), Some(id)) => {
+                let rec = {
+                    let recs = match self.records.get_mut(name) {
+                        Some(r) => r,
+                        None    => return not_found()
+                    };
+                    let rec = match recs.iter_mut().find(|r| id_of(r).to_string() == id) {
+                        Some(r) => r,
+                        None    => return not_found()
+                    };
+                    for k in ["type", "name", "data", "priority", "port", "weight"].iter() {
+                        if let Some(v) = body.find(k) { set(rec, k, v.clone()); }
+                    }
+                    rec.clone()
+                };
+                self.refresh_zone(name);
+                ok(object! { "domain_record" => rec })
+            },
+            (&Method::Delete, Some("domains"), Some(name), Some("records"), Some(id)) => {
+                let found = match self.records.get_mut(name) {
+                    Some(recs) => match recs.iter().position(|r| id_of(r).to_string() == id) {
+                        Some(i) => { recs.remove(i); true },
+                        None    => false
+                    },
+                    None       => false
+                };
+                if !found { return not_found() }
+                self.refresh_zone(name);
+                no_content()
+            },
+            _ => not_found()
+        }
+    }
+
+    fn create_droplet(&mut self, body: &Value) -> Reply {
+        for k in ["name", "region", "size", "image"].iter() {
+            let present = match body.find(k) {
+                Some(&Value::String(ref v)) => !v.is_empty(),
+                Some(&Value::Null) | None  => false,
+                Some(_)                    => true,
+            };
+            if !present {
+                return unprocessable(&format!("{}{} is required", k[..1].to_uppercase(), &k[1..])[..])
+            }
+        }
+        let name = str_of(body, "name").to_owned();
+        if !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '.') {
+            return unprocessable("Name Only valid hostname characters are allowed. (a-z, A-Z, 0-9, . and -)")
+        }
+        let reg = str_of(body, "region").to_owned();
+        if !["nyc3", "sfo1", "ams3"].contains(&&reg[..]) {
+            return unprocessable("Region is not available")
+        }
+        let sz = str_of(body, "size").to_owned();
+        if !["512mb", "1gb", "2gb"].contains(&&sz[..]) {
+            return unprocessable("Size is not available in this region.")
+        }
+        let img = match body.find("image") {
+            Some(&Value::String(ref slug)) => match self.image_index(&slug[..]) {
+                Some(i) => id_of(&self.images[i]),
+                None    => return unprocessable("You specified an invalid image for Droplet creation.")
+            },
+            Some(v) => match v.as_u64().and_then(|id| self.image_index(&id.to_string()[..])) {
+                Some(i) => id_of(&self.images[i]),
+                None    => return unprocessable("You specified an invalid image for Droplet creation.")
+            },
+            None    => unreachable!()
+        };
+        if let Some(keys) = body.find("ssh_keys").and_then(|k| k.as_array()) {
+            for k in keys.iter() {
+                let k = match *k {
+                    Value::String(ref k) => k.clone(),
+                    ref other            => json::to_string(other).unwrap_or(String::new())
+                };
+                if self.key_index(&k[..]).is_none() {
+                    return unprocessable("You specified an invalid SSH key for Droplet creation.")
+                }
+            }
+        }
+        if let Some(data) = body.find("user_data").and_then(|u| u.as_string()) {
+            if data.len() > 64 * 1024 {
+                return unprocessable("User data is too long (maximum is 64 KiB)")
+            }
+        }
+        let id = self.next_id();
+        self.add_droplet(id, &name[..], &reg[..], &sz[..], img, "new");
+        let mut d = self.droplets.pop().unwrap();
+        if body.find("ipv6").and_then(|v| v.as_boolean()) == Some(true) {
+            let v6 = network("2604:A880:0800:0010:0000:0000:0000:0001", "64",
+                             "2604:A880:0800:0010:0000:0000:0000:0001", "public");
+            if let Some(&mut Value::Object(ref mut nets)) = d.as_object_mut()
+                                                            .and_then(|m| m.get_mut("networks")) {
+                nets.insert("v6".to_owned(), Value::Array(vec![v6]));
+            }
+        }
+        if let Some(data) = body.find("user_data") { set(&mut d, "user_data", data.clone()); }
+        self.droplets.push(d.clone());
+        self.action("create", id, "droplet", &reg[..]);
+        // New droplets are ready by the time anyone asks again
+        let last = self.droplets.len() - 1;
+        set(&mut self.droplets[last], "status", s("active"));
+        accepted(object! { "droplet" => d })
+    }
+
+    fn droplet_action(&mut self, i: usize, body: &Value) -> Reply {
+        let id = id_of(&self.droplets[i]);
+        let reg = self.droplets[i].find_path(&["region", "slug"]).and_then(|r| r.as_string())
+                                  .unwrap_or("nyc3").to_owned();
+        let kind = str_of(body, "type").to_owned();
+        // Lets tests see what happens when DigitalOcean turns down some droplets of a bulk command
+        if str_of(&self.droplets[i], "name").starts_with("broken-") {
+            return unprocessable("Droplet is currently unavailable")
+        }
+        match &kind[..] {
+            "reboot" | "power_cycle" | "power_on" => set(&mut self.droplets[i], "status", s("active")),
+            "shutdown" | "power_off"              => set(&mut self.droplets[i], "status", s("off")),
+            "disable_backups" | "password_reset" | "upgrade" | "enable_private_networking" => {},
+            "enable_ipv6" => {
+                let v6 = network("2604:A880:0800:0010:0000:0000:0000:0002", "64",
+                                 "2604:A880:0800:0010:0000:0000:0000:0001", "public");
+                if let Some(&mut Value::Object(ref mut nets)) = self.droplets[i].as_object_mut()
+                                                                   .and_then(|m| m.get_mut("networks")) {
+                    nets.insert("v6".to_owned(), Value::Array(vec![v6]));
+                }
+            },
+            "rename" => {
+                let name = str_of(body, "name").to_owned();
+                if name.is_empty() { return unprocessable("Name is required") }
+                set(&mut self.droplets[i], "name", s(&name[..]));
+            },
+            "resize" => {
+                let sz = str_of(body, "size").to_owned();
+                if !["512mb", "1gb", "2gb"].contains(&&sz[..]) {
+                    return unprocessable("Size is invalid")
+                }
+                if str_of(&self.droplets[i], "status") != "off" {
+                    return unprocessable("Droplet must be powered off before resizing")
+                }
+                set(&mut self.droplets[i], "size_slug", s(&sz[..]));
+                set(&mut self.droplets[i], "size", size(&sz[..]));
+            },
+            "restore" | "rebuild" => {
+                let img = match body.find("image") {
+                    Some(&Value::String(ref slug)) => self.image_index(&slug[..]),
+                    Some(v) => v.as_u64().and_then(|id| self.image_index(&id.to_string()[..])),
+                    None    => None
+                };
+                match img {
+                    Some(img) => {
+                        let img = self.images[img].clone();
+                        set(&mut self.droplets[i], "image", img);
+                    },
+                    None      => return unprocessable("Image is invalid")
+                }
+            },
+            "change_kernel" => {
+                if body.find("kernel").and_then(|k| k.as_u64()).is_none() {
+                    return unprocessable("Kernel is invalid")
+                }
+            },
+            "snapshot" => {
+                let name = str_of(body, "name").to_owned();
+                let img_id = self.next_id();
+                self.images.push(image(img_id, &name[..], None, false, "snapshot"));
+                let mut ids = self.droplets[i].find("snapshot_ids")
+                                              .and_then(|s| s.as_array())
+                                              .cloned()
+                                              .unwrap_or(vec![]);
+                ids.push(s(&img_id.to_string()[..]));
+                set(&mut self.droplets[i], "snapshot_ids", Value::Array(ids));
+            },
+            _ => return unprocessable("Type is not a valid droplet action")
+        }
+        let mut a = self.action(&kind[..], id, "droplet", &reg[..]);
+        let failing = kind == "snapshot" && str_of(body, "name").starts_with("fail-");
+        self.pending.insert(id_of(&a), if failing { "errored" } else { "completed" });
+        set(&mut a, "status", s("in-progress"));
+        let last = self.actions.len() - 1;
+        self.actions[last] = a.clone();
+        created(object! { "action" => a })
+    }
+}
+
+// Serves one page of a collection, with DigitalOcean style `links` and `meta`
+fn page(name: &str, all: Vec<Value>, query: &BTreeMap<String, String>, root: &str, path: &str)
+        -> Reply {
+    let per_page = query.get("per_page").and_then(|p| p.parse::<usize>().ok()).unwrap_or(PER_PAGE);
+    let per_page = if per_page == 0 { PER_PAGE } else { per_page };
+    let num = query.get("page").and_then(|p| p.parse::<usize>().ok()).unwrap_or(1);
+    let num = if num == 0 { 1 } else { num };
+    let total = all.len();
+    let last = if total == 0 { 1 } else { (total + per_page - 1) / per_page };
+    let items = all.into_iter().skip((num - 1) * per_page).take(per_page).collect();
+    let link = |p: usize| {
+        let sep = if path.contains('?') { "&" } else { "?" };
+        s(&format!("{}/{}{}page={}&per_page={}", root, path, sep, p, per_page)[..])
+    };
+    let mut pages = BTreeMap::new();
+    if num > 1 {
+        pages.insert("first".to_owned(), link(1));
+        pages.insert("prev".to_owned(), link(num - 1));
+    }
+    if num < last {
+        pages.insert("next".to_owned(), link(num + 1));
+        pages.insert("last".to_owned(), link(last));
+    }
+    ok(object! {
+        name => Value::Array(items),
+        "links" => object! { "pages" => Value::Object(pages) },
+        "meta" => object! { "total" => n(total as u64) },
+    })
+}
+
+struct Mock {
+    state: Mutex<State>,
+    token: Option<String>,
+}
+
+impl Mock {
+    fn reply(&self, req: &mut Request) -> Reply {
+        let auth = req.headers.get_raw("authorization")
+                              .and_then(|v| v.get(0))
+                              .map(|v| String::from_utf8_lossy(&v[..]).into_owned())
+                              .unwrap_or(String::new());
+        let tok = auth.trim_left_matches("Bearer ").to_owned();
+        let authorized = match self.token {
+            Some(ref t) => &tok == t,
+            None        => tok.len() == 64
+        };
+        if !authorized {
+            return error(401, "unauthorized", "Unable to authenticate you.")
+        }
+        let host = req.headers.get_raw("host")
+                              .and_then(|v| v.get(0))
+                              .map(|v| String::from_utf8_lossy(&v[..]).into_owned())
+                              .unwrap_or("localhost".to_owned());
+        let root = format!("http://{}/v2", host);
+        let uri = match req.uri {
+            RequestUri::AbsolutePath(ref p) => p.clone(),
+            RequestUri::AbsoluteUri(ref u)  => u.serialize_path().unwrap_or(String::new()),
+            _                               => return not_found()
+        };
+        let mut parts = uri.splitn(2, '?');
+        let path = parts.next().unwrap_or("");
+        let query = parts.next().unwrap_or("").split('&').filter(|q| !q.is_empty()).map(|q| {
+            let mut kv = q.splitn(2, '=');
+            (kv.next().unwrap_or("").to_owned(), kv.next().unwrap_or("").to_owned())
+        }).collect::<BTreeMap<_, _>>();
+        let segments = path.split('/').filter(|p| !p.is_empty()).collect::<Vec<_>>();
+        if segments.first() != Some(&"v2") { return not_found() }
+        let mut raw = String::new();
+        if let Err(e) = req.read_to_string(&mut raw) {
+            return error(400, "bad_request", &e.to_string()[..])
+        }
+        let body = if raw.trim().is_empty() {
+            Value::Object(BTreeMap::new())
+        } else {
+            match json::from_str::<Value>(&raw[..]) {
+                Ok(v)  => v,
+                Err(_) => return error(400, "bad_request", "Unable to parse request body")
+            }
+        };
+        let method = req.method.clone();
+        let mut st = self.state.lock().unwrap();
+        st.ratelimit_remaining = st.ratelimit_remaining.saturating_sub(1);
+        st.route(&method, &segments[1..], &query, &body, &root[..])
+    }
+}
+
+// Stands in for the services telling a host its public address, answering with whatever
+// address the `as` parameter of `GET /ip?as=<address>` holds
+fn public_address(req: &Request) -> Option<String> {
+    let uri = match req.uri {
+        RequestUri::AbsolutePath(ref p) => p.clone(),
+        _                               => return None
+    };
+    let mut parts = uri.splitn(2, '?');
+    if parts.next() != Some("/ip") { return None }
+    parts.next().unwrap_or("").split('&').filter_map(|q| {
+        let mut kv = q.splitn(2, '=');
+        if kv.next() == Some("as") { kv.next().map(|v| v.to_owned()) } else { None }
+    }).next()
+}
+
+impl Handler for Mock {
+    fn handle<'a, 'k>(&'a self, mut req: Request<'a, 'k>, mut res: Response<'a>) {
+        if let Some(address) = public_address(&req) {
+            res.send(format!("{}\n", address).as_bytes()).ok();
+            return
+        }
+        let reply = self.reply(&mut req);
+        let remaining = self.state.lock().unwrap().ratelimit_remaining;
+        *res.status_mut() = StatusCode::from_u16(reply.status);
+        res.headers_mut().set_raw("ratelimit-limit", vec![b"5000".to_vec()]);
+        res.headers_mut().set_raw("ratelimit-remaining", vec![remaining.to_string().into_bytes()]);
+        res.headers_mut().set_raw("ratelimit-reset", vec![b"1444931833".to_vec()]);
+        let body = match reply.body {
+            Some(b) => {
+                res.headers_mut().set_raw("content-type", vec![b"application/json; charset=utf-8".to_vec()]);
+                json::to_string(&b).unwrap_or(String::new())
+            },
+            None    => String::new()
+        };
+        res.send(body.as_bytes()).ok();
+    }
+}
+
+fn main() {
+    let m = App::new("docli-mock")
+        .version(&format!("v{}", crate_version!()))
+        .about("Serves an in-memory fake of the DigitalOcean API for testing docli")
+        .args_from_usage("-p --port [port]   'The port to listen on (Defaults to any free port)'
+                          -t --token [token] 'Only accept this token (Defaults to accepting any \
+                                              64 character token)'")
+        .get_matches();
+    let port = value_t!(m.value_of("port"), u16).unwrap_or(0);
+    let mock = Mock {
+        state: Mutex::new(State::new()),
+        token: m.value_of("token").map(|t| t.to_owned()),
+    };
+    let listening = match Server::http(("127.0.0.1", port)).and_then(|s| s.handle(mock)) {
+        Ok(l)  => l,
+        Err(e) => {
+            writeln!(&mut io::stderr(), "Unable to start the mock server: {}", e).ok();
+            std::process::exit(1);
+        }
+    };
+    println!("http://{}/v2", listening.socket);
+    io::stdout().flush().ok();
+    loop { thread::park(); }
+}
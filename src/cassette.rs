@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+
+use hyper::method::Method;
+
+use serde::json::{self, Value};
+
+use doapi::response::HeaderOnly;
+
+use error::CliError;
+
+/// A single request and the response DigitalOcean gave to it. Only the path below the API root
+/// is kept so a cassette can be replayed against any `--api-url`, and the Authorization header is
+/// never written down.
+#[derive(Clone, Debug)]
+pub struct Interaction {
+    pub method: String,
+    pub path: String,
+    pub body: Option<String>,
+    pub status: String,
+    pub content_type: String,
+    pub ratelimit_limit: f64,
+    pub ratelimit_remaining: f64,
+    pub ratelimit_reset: f64,
+    pub response: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// Every exchange made by a run, either being written to a file (`--record`) or served back from
+/// one instead of the network (`--replay`)
+#[derive(Debug)]
+pub struct Cassette {
+    path: String,
+    mode: Mode,
+    // Replayed interactions are removed as they're used, so the same request made twice gets
+    // whatever was answered the first and second time, in order. Bulk commands send requests
+    // from several threads.
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl Cassette {
+    /// Starts a new, empty, cassette at `path`, replacing any previous one
+    pub fn record(path: &str) -> Result<Cassette, CliError> {
+        let c = Cassette {
+            path: path.to_owned(),
+            mode: Mode::Record,
+            interactions: Mutex::new(vec![]),
+        };
+        try!(c.save(&[]));
+        Ok(c)
+    }
+
+    /// Loads a cassette previously written with `--record`
+    pub fn replay(path: &str) -> Result<Cassette, CliError> {
+        let err = |e: String| CliError::Validation(format!("{}: {}", path, e));
+        let mut s = String::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut s)) {
+            return Err(err(e.to_string()))
+        }
+        let v = match json::from_str::<Value>(&s[..]) {
+            Ok(v)  => v,
+            Err(e) => return Err(err(e.to_string()))
+        };
+        let list = match v.find("interactions").and_then(|i| i.as_array()) {
+            Some(l) => l,
+            None    => return Err(err("not a docli cassette, \"interactions\" is missing".to_owned()))
+        };
+        let mut interactions = vec![];
+        for (n, i) in list.iter().enumerate() {
+            match Interaction::from_json(i) {
+                Some(i) => interactions.push(i),
+                None    => return Err(err(format!("interaction {} is incomplete", n + 1)))
+            }
+        }
+        Ok(Cassette {
+            path: path.to_owned(),
+            mode: Mode::Replay,
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    pub fn replaying(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    /// Answers a request with the first unused interaction matching its method, path and body
+    pub fn play(&self, method: &Method, path: &str, body: Option<&String>)
+                -> Result<(HeaderOnly, String), CliError> {
+        let mut interactions = self.interactions.lock().unwrap();
+        let method = method.to_string();
+        let found = interactions.iter().position(|i| {
+            i.method == method && i.path == path && same_body(i.body.as_ref(), body)
+        });
+        match found {
+            Some(n) => {
+                let i = interactions.remove(n);
+                Ok((i.header(), i.response))
+            },
+            None    => Err(CliError::Network(format!("{} {} was never recorded in {}",
+                                                      method, path, self.path)))
+        }
+    }
+
+    /// Adds an exchange to the cassette. The file is rewritten each time so that it's complete
+    /// even when the run ends with an error.
+    pub fn add(&self, i: Interaction) -> Result<(), CliError> {
+        let mut interactions = self.interactions.lock().unwrap();
+        interactions.push(i);
+        self.save(&interactions[..])
+    }
+
+    fn save(&self, interactions: &[Interaction]) -> Result<(), CliError> {
+        let list = interactions.iter().map(|i| i.to_json()).collect();
+        let mut map = BTreeMap::new();
+        map.insert("interactions".to_owned(), Value::Array(list));
+        let mut s = json::to_string_pretty(&Value::Object(map)).unwrap_or(String::new());
+        s.push('\n');
+        File::create(&self.path[..]).and_then(|mut f| f.write_all(s.as_bytes())).map_err(|e| {
+            CliError::Validation(format!("{}: {}", self.path, e))
+        })
+    }
+}
+
+// JSON bodies match regardless of their formatting, so cassettes can be edited by hand
+fn same_body(a: Option<&String>, b: Option<&String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            match (json::from_str::<Value>(a), json::from_str::<Value>(b)) {
+                (Ok(a), Ok(b)) => a == b,
+                _              => a == b
+            }
+        },
+        (None, None)       => true,
+        _                  => false
+    }
+}
+
+impl Interaction {
+    pub fn new(method: &Method, path: &str, body: Option<String>, header: &HeaderOnly,
+               response: &str) -> Interaction {
+        Interaction {
+            method: method.to_string(),
+            path: path.to_owned(),
+            body: body,
+            status: header.status.clone(),
+            content_type: header.content_type.clone(),
+            ratelimit_limit: header.ratelimit_limit,
+            ratelimit_remaining: header.ratelimit_remaining,
+            ratelimit_reset: header.ratelimit_reset,
+            response: response.to_owned(),
+        }
+    }
+
+    fn header(&self) -> HeaderOnly {
+        HeaderOnly {
+            content_type: self.content_type.clone(),
+            status: self.status.clone(),
+            ratelimit_limit: self.ratelimit_limit,
+            ratelimit_remaining: self.ratelimit_remaining,
+            ratelimit_reset: self.ratelimit_reset,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut req = BTreeMap::new();
+        req.insert("method".to_owned(), Value::String(self.method.clone()));
+        req.insert("path".to_owned(), Value::String(self.path.clone()));
+        req.insert("body".to_owned(),
+                   self.body.clone().map(Value::String).unwrap_or(Value::Null));
+        let mut res = BTreeMap::new();
+        res.insert("status".to_owned(), Value::String(self.status.clone()));
+        res.insert("content-type".to_owned(), Value::String(self.content_type.clone()));
+        res.insert("ratelimit-limit".to_owned(), Value::F64(self.ratelimit_limit));
+        res.insert("ratelimit-remaining".to_owned(), Value::F64(self.ratelimit_remaining));
+        res.insert("ratelimit-reset".to_owned(), Value::F64(self.ratelimit_reset));
+        res.insert("body".to_owned(), Value::String(self.response.clone()));
+        let mut map = BTreeMap::new();
+        map.insert("request".to_owned(), Value::Object(req));
+        map.insert("response".to_owned(), Value::Object(res));
+        Value::Object(map)
+    }
+
+    fn from_json(v: &Value) -> Option<Interaction> {
+        let text = |path: &[&str]| v.find_path(path).and_then(|s| s.as_string()).map(|s| s.to_owned());
+        let num = |path: &[&str]| v.find_path(path).and_then(|n| n.as_f64()).unwrap_or(0.0);
+        Some(Interaction {
+            method: match text(&["request", "method"]) { Some(m) => m, None => return None },
+            path: match text(&["request", "path"]) { Some(p) => p, None => return None },
+            body: text(&["request", "body"]),
+            status: match text(&["response", "status"]) { Some(s) => s, None => return None },
+            content_type: text(&["response", "content-type"]).unwrap_or(String::new()),
+            ratelimit_limit: num(&["response", "ratelimit-limit"]),
+            ratelimit_remaining: num(&["response", "ratelimit-remaining"]),
+            ratelimit_reset: num(&["response", "ratelimit-reset"]),
+            response: text(&["response", "body"]).unwrap_or(String::new()),
+        })
+    }
+}
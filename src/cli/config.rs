@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use clap::ArgMatches;
+use serde::json::Value;
+
+use config::{ConfigFile, Profile};
+use error::{CliError, CliResult};
+use message::CliMessage;
+use output::{self, OutputFormat};
+use token;
+use cli;
+
+// Profiles are local, so this never needs a token and never sends anything
+pub fn run(m: &ArgMatches, mut file: ConfigFile) -> CliResult {
+    let out = value_t!(m.value_of("output"), OutputFormat).unwrap_or(OutputFormat::Text);
+    match m.subcommand() {
+        ("add", Some(m))    => {
+            let name = m.value_of("name").unwrap();
+            let mut p = file.profiles.get(name).cloned().unwrap_or(Profile {
+                name: name.to_owned(),
+                ..Profile::default()
+            });
+            if let Some(t) = m.value_of("tok") {
+                // Reading it from stdin keeps the token out of the shell history
+                p.token = Some(if t == "-" {
+                    match token::from_file(t) {
+                        Ok(t)  => t,
+                        Err(e) => return finish(Err(e), out)
+                    }
+                } else {
+                    t.to_owned()
+                });
+            }
+            if let Some(f) = m.value_of("file") { p.token_file = Some(f.to_owned()); }
+            if let Some(c) = m.value_of("cmd") { p.token_command = Some(c.to_owned()); }
+            if let Some(r) = m.value_of("region") { p.region = Some(r.to_owned()); }
+            if let Some(s) = m.value_of("size") { p.size = Some(s.to_owned()); }
+            if let Some(k) = m.values_of("keys") {
+                p.ssh_keys = k.iter().map(|&k| k.to_owned()).collect();
+            }
+            if let Ok(o) = value_t!(m.value_of("format"), OutputFormat) { p.output = Some(o); }
+            if let Some(u) = m.value_of("url") { p.api_url = Some(u.to_owned()); }
+            if let Some(u) = m.value_of("ssh-user") { p.ssh_user = Some(u.to_owned()); }
+            if let Some(i) = m.value_of("identity") { p.ssh_identity = Some(i.to_owned()); }
+            file.profiles.insert(name.to_owned(), p);
+            // The first profile becomes the default
+            if file.current.is_none() { file.current = Some(name.to_owned()); }
+            if !out.is_structured() { CliMessage::AddProfile(name).display(); }
+            finish(file.save(), out)
+        },
+        ("switch", Some(m)) => {
+            let name = m.value_of("name").unwrap();
+            if !out.is_structured() { CliMessage::SwitchProfile(name).display(); }
+            if !file.profiles.contains_key(name) {
+                return finish(Err(no_profile(name)), out)
+            }
+            file.current = Some(name.to_owned());
+            finish(file.save(), out)
+        },
+        ("remove", Some(m)) => {
+            let name = m.value_of("name").unwrap();
+            if !file.profiles.contains_key(name) {
+                if !out.is_structured() { CliMessage::RemoveProfile(name).display(); }
+                return finish(Err(no_profile(name)), out)
+            }
+            if !m.is_present("noconfirm") {
+                try!(cli::confirm_on(out));
+            }
+            if !out.is_structured() { CliMessage::RemoveProfile(name).display(); }
+            file.profiles.remove(name);
+            if file.current.as_ref().map(|c| &c[..] == name).unwrap_or(false) {
+                file.current = None;
+            }
+            finish(file.save(), out)
+        },
+        _                   => {
+            // "list" or no subcommand
+            if out.is_structured() {
+                print!("{}", output::render(out, &profiles_value(&file)));
+                return Ok(())
+            }
+            CliMessage::Profiles.display();
+            for p in file.profiles.values() {
+                let current = file.current.as_ref().map(|c| c == &p.name).unwrap_or(false);
+                println!("\t{}{}\n", if current { "* " } else { "  " },
+                         &describe(p).replace("\n", "\n\t    "));
+            }
+            if file.profiles.is_empty() { println!("\tNo profiles to display"); }
+            Ok(())
+        }
+    }
+}
+
+fn no_profile(name: &str) -> CliError {
+    CliError::NotFound(format!("no profile named '{}' in {}", name, ConfigFile::path().display()))
+}
+
+// Prints the outcome of a change to the configuration file. With --output nothing but errors is
+// printed, to stderr.
+fn finish(res: CliResult, out: OutputFormat) -> CliResult {
+    match res {
+        Ok(()) => {
+            if !out.is_structured() { CliMessage::Success.display(); }
+            Ok(())
+        },
+        Err(e) => {
+            if out.is_structured() {
+                writeln!(&mut io::stderr(), "{}", e).ok();
+            } else {
+                CliMessage::Failure.display();
+                println!("\n\t{}\n", e);
+            }
+            Err(e)
+        }
+    }
+}
+
+// Tokens are never displayed, only where they come from
+fn token_state(p: &Profile) -> String {
+    if let Some(ref c) = p.token_command {
+        format!("from `{}`", c)
+    } else if let Some(ref f) = p.token_file {
+        format!("from {}", f)
+    } else if let Some(ref t) = p.token {
+        token::redact(&t[..])
+    } else {
+        "None".to_owned()
+    }
+}
+
+fn describe(p: &Profile) -> String {
+    let opt = |o: &Option<String>| o.clone().unwrap_or("None".to_owned());
+    format!("{}\ntoken: {}\nregion: {}\nsize: {}\nssh keys: {}\noutput: {}\napi url: {}\n\
+        ssh user: {}\nssh identity: {}",
+        p.name,
+        token_state(p),
+        opt(&p.region),
+        opt(&p.size),
+        if p.ssh_keys.is_empty() { "None".to_owned() } else { p.ssh_keys.join(", ") },
+        p.output.map(|o| o.to_string()).unwrap_or("None".to_owned()),
+        opt(&p.api_url),
+        opt(&p.ssh_user),
+        opt(&p.ssh_identity))
+}
+
+fn profiles_value(file: &ConfigFile) -> Value {
+    let opt = |o: &Option<String>| o.clone().map(Value::String).unwrap_or(Value::Null);
+    Value::Array(file.profiles.values().map(|p| {
+        let mut map = BTreeMap::new();
+        map.insert("name".to_owned(), Value::String(p.name.clone()));
+        map.insert("current".to_owned(),
+                   Value::Bool(file.current.as_ref().map(|c| c == &p.name).unwrap_or(false)));
+        map.insert("token".to_owned(), Value::String(token_state(p)));
+        map.insert("region".to_owned(), opt(&p.region));
+        map.insert("size".to_owned(), opt(&p.size));
+        map.insert("ssh_keys".to_owned(),
+                   Value::Array(p.ssh_keys.iter().map(|k| Value::String(k.clone())).collect()));
+        map.insert("output".to_owned(), opt(&p.output.map(|o| o.to_string())));
+        map.insert("api_url".to_owned(), opt(&p.api_url));
+        map.insert("ssh_user".to_owned(), opt(&p.ssh_user));
+        map.insert("ssh_identity".to_owned(), opt(&p.ssh_identity));
+        Value::Object(map)
+    }).collect())
+}
@@ -1,224 +1,533 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::thread;
+
 use clap::ArgMatches;
 
-use doapi::{DoManager, DoRequest};
-use doapi::request::DnsRecord;
+use doapi::DoManager;
+use doapi::request::{DnsRecType, DnsRecord};
+use serde::json::Value;
+use time;
 
+use address;
+use client::{self, Endpoint};
 use config::Config;
+use error::{CliError, CliResult};
+use infra::{self, Op};
 use message::CliMessage;
+use output::{self, OutputFormat};
+use zone::{self, RecordChange};
 use cli;
 
-fn dns_record_from_matches(m: &ArgMatches) -> DnsRecord {
-    let pri = value_t!(m.value_of("priority"), u64);
-    let port = value_t!(m.value_of("port"), u64);
-    let weight = value_t!(m.value_of("weight"), u64);
+// The record described by the flags, checked against its type so that mistakes are caught before
+// DigitalOcean sees them. A new record needs everything its type does, an update only what it
+// changes.
+fn dns_record_from_matches(m: &ArgMatches, creating: bool) -> Result<DnsRecord, CliError> {
+    let mut errors = vec![];
+    let (priority, port, weight) = {
+        let mut number = |flag: &str| m.value_of(flag).and_then(|v| match v.parse::<u64>() {
+            Ok(n) if n <= 65535 => Some(n),
+            _                   => {
+                errors.push(format!("--{} must be a number from 0 to 65535, not '{}'", flag, v));
+                None
+            }
+        });
+        (number("priority"), number("port"), number("weight"))
+    };
     let data = m.value_of("data");
-    let name = m.value_of("name");
-    let t = m.value_of("type");
-    DnsRecord {
-        rec_type: if t.is_none() {None} else {Some(t.unwrap().to_owned())},
-        name: if name.is_none() {None} else {Some(name.unwrap().to_owned())},
-        data: if data.is_none() {None} else {Some(data.unwrap().to_owned())},
-        priority: if pri.is_err() {None} else {Some(pri.unwrap())},
-        port: if port.is_err() {None} else {Some(port.unwrap())},
-        weight: if weight.is_err() {None} else {Some(weight.unwrap())},
+    if data == Some("") { errors.push("--data can't be empty".to_owned()); }
+    if let Ok(t) = value_t!(m.value_of("type"), DnsRecType) {
+        let expected = match (&t, data.unwrap_or("")) {
+            (_, "")                                                  => None,
+            (&DnsRecType::A, d) if d.parse::<Ipv4Addr>().is_err()    => Some("an IPv4 address"),
+            (&DnsRecType::AAAA, d) if d.parse::<Ipv6Addr>().is_err() => Some("an IPv6 address"),
+            (&DnsRecType::CNAME, d) | (&DnsRecType::MX, d) | (&DnsRecType::NS, d)
+            | (&DnsRecType::SRV, d) if !zone::is_host(d)             => {
+                Some("a host name, or @ for the domain itself")
+            },
+            _                                                        => None
+        };
+        if let Some(what) = expected {
+            errors.push(format!("--data must be {} for {} records, not '{}'", what, t,
+                data.unwrap_or("")));
+        }
+        if let (&DnsRecType::TXT, Some(d)) = (&t, data) {
+            let len = d.chars().count();
+            if len > zone::TXT_MAX {
+                errors.push(format!("--data can't be longer than {} characters for TXT records, \
+                    not {}", zone::TXT_MAX, len));
+            }
+        }
+        if creating && data.is_none() {
+            errors.push(format!("--data is needed for {} records", t));
+        }
+        let (uses_priority, srv) = match t {
+            DnsRecType::MX  => (true, false),
+            DnsRecType::SRV => (true, true),
+            _               => (false, false)
+        };
+        let mut needs = |flag: &str, used: bool, applies_to: &str| {
+            if !used && m.is_present(flag) {
+                errors.push(format!("--{} only applies to {} records, not {}", flag, applies_to,
+                    t));
+            } else if used && creating && !m.is_present(flag) {
+                errors.push(format!("--{} is needed for {} records", flag, t));
+            }
+        };
+        needs("priority", uses_priority, "MX and SRV");
+        needs("port", srv, "SRV");
+        needs("weight", srv, "SRV");
     }
+    if !errors.is_empty() {
+        return Err(CliError::Validation(if errors.len() == 1 {
+            errors.remove(0)
+        } else {
+            format!("the record has {} problems:\n\t{}", errors.len(), errors.join("\n\t"))
+        }))
+    }
+    Ok(DnsRecord {
+        rec_type: m.value_of("type").map(|t| t.to_owned()),
+        name: m.value_of("name").map(|n| n.to_owned()),
+        data: data.map(|d| d.to_owned()),
+        priority: priority,
+        port: port,
+        weight: weight,
+    })
 }
 
-
-pub fn run(pm: &ArgMatches, cfg: &mut Config) {
+pub fn run(pm: &ArgMatches, cfg: &mut Config) -> CliResult {
     if pm.is_present("verbose") { cfg.verbose = true; }
     if pm.is_present("nosend") { cfg.no_send = true; }
+    if let Ok(o) = value_t!(pm.value_of("output"), OutputFormat) { cfg.output = o; }
     let domgr = DoManager::with_token(&cfg.auth[..]);
     let domain = pm.value_of("domain").unwrap();
     match pm.subcommand() {
         ("create-record", Some(m)) => {
-            let rec = dns_record_from_matches(&m);
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.domain(domain)
-                          .dns_records()
-                          .create(&rec)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domain(domain).dns_records().create(&rec).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::CreateDns(&rec).display();
-            match domgr.domain(domain).dns_records().create(&rec).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n","\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            let rec = match dns_record_from_matches(&m, true) {
+                Ok(rec) => rec,
+                Err(e)  => return cli::fail(cfg, e)
+            };
+            cli::execute(m, cfg, domgr.domain(domain).dns_records().create(&rec),
+                CliMessage::CreateDns(&rec), cli::show)
         },
         ("records", Some(m))        => {
-            if cfg.verbose {
-                CliMessage::Request(
-                    &domgr.domain(domain)
-                         .dns_records()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domain(domain).dns_records().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DnsRecords.display();
-            match domgr.domain(domain).dns_records().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for act in v.iter() {
-                        CliMessage::DnsRecord.display();
-                        println!("\t{}\n", &act.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo DNS records to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.domain(domain).dns_records(), CliMessage::DnsRecords,
+                |r| cli::show_list(r, CliMessage::DnsRecord, "DNS records"))
         },
         ("update-record", Some(m)) => {
+            let rec = match dns_record_from_matches(&m, false) {
+                Ok(rec) => rec,
+                Err(e)  => return cli::fail(cfg, e)
+            };
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
+                try!(cli::confirm(cfg));
             }
-            let rec = dns_record_from_matches(&m);
             let id = m.value_of("id").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.domain(domain)
-                          .dns_record(id)
-                          .update(&rec)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domain(domain).dns_record(id).update(&rec).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::UpdateDns(id, &rec).display();
-            match domgr.domain(domain).dns_record(id).update(&rec).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.domain(domain).dns_record(id).update(&rec),
+                CliMessage::UpdateDns(id, &rec), cli::show)
         },
         ("record", Some(m))   => {
             let id = m.value_of("id").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.domain(domain)
-                          .dns_record(id)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domain(domain).dns_record(id).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::ShowDns(id).display();
-            match domgr.domain(domain).dns_record(id).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.domain(domain).dns_record(id), CliMessage::ShowDns(id),
+                cli::show)
         },
         ("delete-record", Some(m)) => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
+                try!(cli::confirm(cfg));
             }
             let id = m.value_of("id").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.domain(domain)
-                          .dns_record(id)
-                          .delete()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domain(domain).dns_record(id).delete().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
+            cli::execute(m, cfg, domgr.domain(domain).dns_record(id).delete(),
+                CliMessage::DeleteDns(id), cli::show)
+        },
+        ("export", Some(m))        => export(m, cfg, domain),
+        ("import", Some(m))        => import(m, cfg, domain),
+        ("sync", Some(m))          => sync(m, cfg, domain),
+        ("ddns", Some(m))          => ddns(m, cfg, domain),
+        _                          => unreachable!()
+    }
+}
+
+// The records of the domain as a zone file, printed alone so it can be redirected
+fn export(m: &ArgMatches, cfg: &Config, domain: &str) -> CliResult {
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    if cfg.no_send || m.is_present("nosend") {
+        return cli::execute(m, cfg, domgr.domain(domain).dns_records(), CliMessage::DnsRecords,
+            |_| ())
+    }
+    let res = client::send(&domgr.domain(domain), &cfg.api).and_then(|d| {
+        client::send(&domgr.domain(domain).dns_records(), &cfg.api).map(|r| (d.data, r.data))
+    });
+    match res {
+        Ok((d, records)) => {
+            let ttl = d.find("ttl").and_then(|t| t.as_u64()).unwrap_or(1800);
+            print!("{}", zone::export(domain, ttl, records.as_array().map(|a| &a[..])
+                                                              .unwrap_or(&[])));
+            Ok(())
+        },
+        Err(e)           => cli::fail(cfg, e)
+    }
+}
+
+// Creates the records of a zone file that the domain doesn't have already, one after the other
+fn import(m: &ArgMatches, cfg: &Config, domain: &str) -> CliResult {
+    let path = m.value_of("file").unwrap();
+    let mut s = String::new();
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut s)) {
+        return cli::fail(cfg, CliError::Validation(format!("{}: {}", path, e)))
+    }
+    let records = match zone::parse(&s[..], domain) {
+        Ok(r)       => r,
+        Err(errors) => return cli::fail(cfg, CliError::Validation(format!("{}: the zone has {} \
+            problem(s):\n\t{}", path, errors.len(), errors.join("\n\t"))))
+    };
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    if cfg.no_send || m.is_present("nosend") {
+        return cli::execute(m, cfg, domgr.domain(domain).dns_records(), CliMessage::DnsRecords,
+            |_| ())
+    }
+    let existing: Vec<DnsRecord> = match client::send(&domgr.domain(domain).dns_records(),
+                                                      &cfg.api) {
+        Ok(r)  => r.data.as_array().map(|a| a.iter().map(zone::record_of).collect())
+                                   .unwrap_or(vec![]),
+        Err(e) => return cli::fail(cfg, e)
+    };
+    let (present, missing): (Vec<&DnsRecord>, Vec<&DnsRecord>) = records.iter().partition(|r| {
+        existing.iter().any(|e| zone::same(e, r))
+    });
+
+    // What's about to be created goes to stderr with --output, stdout is only for the results
+    let text = !cfg.output.is_structured();
+    if text {
+        CliMessage::ImportZone(path, domain).display();
+        preview(&missing, &present, &mut io::stdout());
+    } else {
+        preview(&missing, &present, &mut io::stderr());
+    }
+    if m.is_present("dry-run") {
+        if !text {
+            let list = missing.iter().map(|r| zone::to_json(r)).collect();
+            print!("{}", output::render(cfg.output, &Value::Array(list)));
+        }
+        return Ok(())
+    }
+    if missing.is_empty() { return Ok(()) }
+    if !m.is_present("noconfirm") { try!(cli::confirm(cfg)); }
+    let total = missing.len();
+    let mut failed = vec![];
+    for r in missing {
+        let res = cli::execute(m, cfg, domgr.domain(domain).dns_records().create(r),
+            CliMessage::CreateDns(r), cli::show);
+        if let Err(e) = res { failed.push(e); }
+    }
+    match failed.len() {
+        0               => Ok(()),
+        n if n == total => Err(failed.remove(0)),
+        n               => cli::fail(cfg, CliError::Partial(n, total))
+    }
+}
+
+fn preview(missing: &[&DnsRecord], present: &[&DnsRecord], out: &mut Write) {
+    for r in missing.iter() {
+        writeln!(out, "\t+ {}", zone::line(r)).ok();
+    }
+    for r in present.iter() {
+        writeln!(out, "\t= {}", zone::line(r)).ok();
+    }
+    writeln!(out, "\n\t{} to create, {} already there", missing.len(), present.len()).ok();
+}
+
+// Makes the records of the domain match a file. When a change fails, those already made are
+// undone in reverse, so the zone ends up either matching the file or the way it was.
+fn sync(m: &ArgMatches, cfg: &Config, domain: &str) -> CliResult {
+    let path = m.value_of("file").unwrap();
+    let wanted = match infra::load_records(path) {
+        Ok(r)  => r,
+        Err(e) => return cli::fail(cfg, e)
+    };
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    if cfg.no_send || m.is_present("nosend") {
+        return cli::execute(m, cfg, domgr.domain(domain).dns_records(), CliMessage::DnsRecords,
+            |_| ())
+    }
+    let (changes, unchanged) = match client::send(&domgr.domain(domain).dns_records(), &cfg.api) {
+        Ok(r)  => zone::diff(&wanted, r.data.as_array().map(|a| &a[..]).unwrap_or(&[])),
+        Err(e) => return cli::fail(cfg, e)
+    };
+    let text = !cfg.output.is_structured();
+    if text {
+        CliMessage::SyncZone(domain, path).display();
+        show_changes(&changes, unchanged, &mut io::stdout());
+    } else {
+        show_changes(&changes, unchanged, &mut io::stderr());
+    }
+    if m.is_present("dry-run") || changes.is_empty() {
+        if !text {
+            let list = changes.iter().map(|c| c.to_json()).collect();
+            print!("{}", output::render(cfg.output, &Value::Array(list)));
+        }
+        return Ok(())
+    }
+    if !m.is_present("noconfirm") { try!(cli::confirm(cfg)); }
+
+    if text { CliMessage::ApplySync(changes.len()).display(); }
+    let mut done = vec![];
+    let mut failure = None;
+    for c in changes.iter() {
+        if text {
+            print!("\t{} {} ", c.op.symbol(), zone::line(&c.record));
+            io::stdout().flush().ok();
+        }
+        match change(&domgr, &cfg.api, domain, c) {
+            Ok(id) => {
+                if text { CliMessage::Success.display(); }
+                done.push((c, id));
+            },
+            Err(e) => {
+                if text {
+                    CliMessage::Failure.display();
+                    println!("\t\t{}", e);
                 }
+                failure = Some(e);
+                break
+            }
+        }
+    }
+    let e = match failure {
+        Some(e) => e,
+        None    => {
+            if !text {
+                let list = changes.iter().map(|c| c.to_json()).collect();
+                print!("{}", output::render(cfg.output, &Value::Array(list)));
             }
-            CliMessage::DeleteDns(id).display();
-            match domgr.domain(domain).dns_record(id).delete().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
+            return Ok(())
+        }
+    };
+
+    if text { CliMessage::Rollback(done.len()).display(); }
+    let mut restored = true;
+    for &(c, ref id) in done.iter().rev() {
+        if text {
+            print!("\t{} {} ", undo_symbol(c.op), zone::line(c.old.as_ref().unwrap_or(&c.record)));
+            io::stdout().flush().ok();
+        }
+        match undo(&domgr, &cfg.api, domain, c, &id[..]) {
+            Ok(_)      => if text { CliMessage::Success.display(); },
+            Err(undo_e) => {
+                if text {
                     CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
+                    println!("\t\t{}", undo_e);
+                } else {
+                    writeln!(&mut io::stderr(), "{}", undo_e).ok();
                 }
+                restored = false;
+            }
+        }
+    }
+    if !restored {
+        let msg = format!("Some changes couldn't be undone, compare the records of {} with {}",
+            domain, path);
+        if text { println!("\n\t{}", msg); } else { writeln!(&mut io::stderr(), "{}", msg).ok(); }
+    }
+    cli::fail(cfg, e)
+}
+
+fn show_changes(changes: &[RecordChange], unchanged: usize, out: &mut Write) {
+    for c in changes.iter() {
+        let id = c.id.as_ref().map(|id| format!(" (ID {})", id)).unwrap_or(String::new());
+        writeln!(out, "\t{} {}{}", c.op.symbol(), zone::line(&c.record), id).ok();
+        if let Some(ref old) = c.old {
+            for d in zone::differences(old, &c.record) {
+                writeln!(out, "\t\t{}", d).ok();
             }
+        }
+    }
+    if changes.is_empty() {
+        writeln!(out, "\tNo changes, the records match").ok();
+    } else {
+        let count = |op| changes.iter().filter(|c| c.op == op).count();
+        writeln!(out, "\n\t{} to create, {} to update, {} to delete, {} unchanged",
+            count(Op::Create), count(Op::Update), count(Op::Delete), unchanged).ok();
+    }
+}
+
+// Makes a change, returning the ID of the record it was made to
+fn change(domgr: &DoManager, api: &Endpoint, domain: &str, c: &RecordChange)
+          -> Result<String, CliError> {
+    let id = c.id.clone().unwrap_or(String::new());
+    let res = match c.op {
+        Op::Create => {
+            return client::send(&domgr.domain(domain).dns_records().create(&c.record), api)
+                          .map(|r| infra::id_of("record", &r.data))
         },
-        _                          => unreachable!()
+        Op::Update => {
+            client::send(&domgr.domain(domain).dns_record(&id[..]).update(&c.record), api)
+                  .map(|_| ())
+        },
+        _          => client::send(&domgr.domain(domain).dns_record(&id[..]).delete(), api)
+                            .map(|_| ())
+    };
+    res.map(|_| id)
+}
+
+// Takes back a change made to the record `id`. A deleted record comes back with a new ID.
+fn undo(domgr: &DoManager, api: &Endpoint, domain: &str, c: &RecordChange, id: &str)
+        -> CliResult {
+    match c.op {
+        Op::Create => {
+            client::send(&domgr.domain(domain).dns_record(id).delete(), api).map(|_| ())
+        },
+        Op::Update => {
+            let old = c.old.as_ref().unwrap_or(&c.record);
+            client::send(&domgr.domain(domain).dns_record(id).update(old), api).map(|_| ())
+        },
+        _          => {
+            client::send(&domgr.domain(domain).dns_records().create(&c.record), api).map(|_| ())
+        }
+    }
+}
+
+fn undo_symbol(op: Op) -> &'static str {
+    match op {
+        Op::Create => "-",
+        Op::Delete => "+",
+        _          => "~"
+    }
+}
+
+// The outcome of making sure a record holds the current address of the host
+struct Check {
+    status: &'static str,
+    address: String,
+    previous: Option<String>,
+    id: String,
+}
+
+impl Check {
+    fn to_json(&self, name: &str, rec_type: &str) -> Value {
+        let mut map = BTreeMap::new();
+        map.insert("time".to_owned(), Value::String(now()));
+        map.insert("status".to_owned(), Value::String(self.status.to_owned()));
+        map.insert("name".to_owned(), Value::String(name.to_owned()));
+        map.insert("type".to_owned(), Value::String(rec_type.to_owned()));
+        map.insert("address".to_owned(), Value::String(self.address.clone()));
+        map.insert("previous".to_owned(),
+                   self.previous.clone().map(Value::String).unwrap_or(Value::Null));
+        map.insert("id".to_owned(), Value::String(self.id.clone()));
+        Value::Object(map)
+    }
+}
+
+// Points a record at the address of the host, once or, with --daemon, every --interval seconds.
+// The daemon logs each check and carries on through errors that may go away by themselves, like
+// an interface without an address for a while.
+fn ddns(m: &ArgMatches, cfg: &Config, domain: &str) -> CliResult {
+    let name = m.value_of("name").unwrap();
+    let rec_type = m.value_of("type").unwrap_or("A");
+    let interval = match m.value_of("interval").map(|i| i.parse::<u32>()) {
+        Some(Ok(i)) if i > 0 && i <= 86400 => i,
+        Some(_)                            => {
+            return cli::fail(cfg, CliError::Validation("--interval must be a number of seconds \
+                from 1 to 86400".to_owned()))
+        },
+        None                               => 300
+    };
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    if cfg.no_send || m.is_present("nosend") {
+        return cli::execute(m, cfg, domgr.domain(domain).dns_records(), CliMessage::DnsRecords,
+            |_| ())
+    }
+    let host = if name == "@" { domain.to_owned() } else { format!("{}.{}", name, domain) };
+    let text = !cfg.output.is_structured();
+    if !m.is_present("daemon") {
+        if text { CliMessage::Ddns(rec_type, &host[..]).display(); }
+        return match check(m, cfg, &domgr, domain) {
+            Ok(c)  => {
+                log(cfg, &c, name, rec_type);
+                Ok(())
+            },
+            Err(e) => cli::fail(cfg, e)
+        }
+    }
+    if text { CliMessage::DdnsDaemon(rec_type, &host[..], interval).display(); }
+    loop {
+        match check(m, cfg, &domgr, domain) {
+            Ok(c)                                                => log(cfg, &c, name, rec_type),
+            // Waiting won't bring back the token or the domain
+            Err(e @ CliError::Auth(_)) | Err(e @ CliError::NotFound(_)) => return cli::fail(cfg, e),
+            Err(e)                                               => {
+                writeln!(&mut io::stderr(), "\t{} error {}", now(), e).ok();
+            }
+        }
+        thread::sleep_ms(interval * 1000);
+    }
+}
+
+// Finds the address of the host and the record named --name, then creates or updates the record
+// when it doesn't hold that address yet
+fn check(m: &ArgMatches, cfg: &Config, domgr: &DoManager, domain: &str)
+         -> Result<Check, CliError> {
+    let name = m.value_of("name").unwrap();
+    let rec_type = m.value_of("type").unwrap_or("A");
+    let v6 = rec_type == "AAAA";
+    let address = try!(match m.value_of("interface") {
+        Some(iface) => address::of_interface(iface, v6),
+        None        => {
+            let default = if v6 { address::LOOKUP_V6 } else { address::LOOKUP_V4 };
+            address::lookup(m.value_of("lookup-url").unwrap_or(default), v6)
+        }
+    });
+    let records = try!(client::send(&domgr.domain(domain).dns_records(), &cfg.api)).data;
+    let found: Vec<&Value> = records.as_array().map(|a| a.iter().filter(|r| {
+        let rec = zone::record_of(r);
+        rec.rec_type.as_ref().map(|t| &t[..]) == Some(rec_type)
+            && rec.name.as_ref().map(|n| &n[..]) == Some(name)
+    }).collect()).unwrap_or(vec![]);
+    if found.len() > 1 {
+        return Err(CliError::Validation(format!("{} has {} {} records named '{}', keep only one \
+            for its address to be kept up to date", domain, found.len(), rec_type, name)))
     }
+    let rec = DnsRecord {
+        rec_type: Some(rec_type.to_owned()),
+        name: Some(name.to_owned()),
+        data: Some(address.clone()),
+        priority: None,
+        port: None,
+        weight: None,
+    };
+    let existing = match found.first() {
+        Some(r) => r,
+        None    => {
+            let res = try!(client::send(&domgr.domain(domain).dns_records().create(&rec),
+                                        &cfg.api));
+            return Ok(Check {
+                status: "created",
+                address: address,
+                previous: None,
+                id: infra::id_of("record", &res.data),
+            })
+        }
+    };
+    let id = infra::id_of("record", existing);
+    let previous = zone::record_of(existing).data.unwrap_or(String::new());
+    if previous == address {
+        return Ok(Check { status: "unchanged", address: address, previous: None, id: id })
+    }
+    try!(client::send(&domgr.domain(domain).dns_record(&id[..]).update(&rec), &cfg.api));
+    Ok(Check { status: "updated", address: address, previous: Some(previous), id: id })
+}
+
+fn log(cfg: &Config, c: &Check, name: &str, rec_type: &str) {
+    if cfg.output.is_structured() {
+        print!("{}", output::render(cfg.output, &c.to_json(name, rec_type)));
+    } else {
+        let was = c.previous.as_ref().map(|p| format!(" (was {})", p)).unwrap_or(String::new());
+        println!("\t{} {} {}{}", now(), c.status, c.address, was);
+    }
+    io::stdout().flush().ok();
+}
+
+fn now() -> String {
+    time::strftime("%Y-%m-%d %H:%M:%S", &time::now()).unwrap_or(String::new())
 }
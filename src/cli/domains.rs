@@ -1,162 +1,41 @@
-
 use clap::ArgMatches;
 
-use doapi::{DoManager, DoRequest};
+use doapi::DoManager;
 
 use config::Config;
+use error::CliResult;
 use message::CliMessage;
+use output::OutputFormat;
 use cli;
 
-pub fn run(m: &ArgMatches, cfg: &mut Config) {
+pub fn run(m: &ArgMatches, cfg: &mut Config) -> CliResult {
     if m.is_present("verbose") { cfg.verbose = true; }
     if m.is_present("nosend") { cfg.no_send = true; }
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
     let domgr = DoManager::with_token(&cfg.auth[..]);
     match m.subcommand() {
         ("create", Some(m))      => {
             let name = m.value_of("name").unwrap();
             // TODO: Validate IP
             let ip   = m.value_of("ip").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.domains()
-                          .create(name, ip)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domains().create(name, ip).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::CreateDomain(name, ip).display();
-            match domgr.domains().create(name, ip).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", s);
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.domains().create(name, ip),
+                CliMessage::CreateDomain(name, ip), cli::show)
         },
         ("show-domain", Some(m)) => {
             let name = m.value_of("name").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.domain(name)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domain(name).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Domain(name).display();
-            match domgr.domain(name).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.domain(name), CliMessage::Domain(name), cli::show)
         },
         ("delete", Some(m))      => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
+                try!(cli::confirm(cfg));
             }
             let name = m.value_of("name").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.domain(name)
-                          .delete()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domain(name).delete().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DeleteDomain(name).display();
-            match domgr.domain(name).delete().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", s);
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.domain(name).delete(), CliMessage::DeleteDomain(name),
+                cli::show)
         },
         _                        => {
-            if cfg.verbose {
-                CliMessage::Request(
-                    &domgr.domains()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send { return }
-            if cfg.verbose {
-                CliMessage::JsonResponse.display();
-                match domgr.domains().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Domains.display();
-            match domgr.domains().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for d in v.iter() {
-                        CliMessage::Domains.display();
-                        println!("\t{}\n", &d.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo domains to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.domains(), CliMessage::Domains,
+                |r| cli::show_list(r, CliMessage::Domains, "domains"))
         }
     }
 }
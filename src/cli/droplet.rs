@@ -1,878 +1,147 @@
 use clap::ArgMatches;
 
-use doapi::{DoManager, DoRequest};
+use doapi::DoManager;
 
 use config::Config;
+use error::CliResult;
 use message::CliMessage;
+use output::OutputFormat;
+use resolve;
 use cli;
 
-pub fn run(m: &ArgMatches, cfg: &mut Config) {
+pub fn run(m: &ArgMatches, cfg: &mut Config) -> CliResult {
     if m.is_present("verbose") { cfg.verbose = true; }
     if m.is_present("nosend") { cfg.no_send = true; }
-    let id = m.value_of("id").unwrap();
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
+    let id = try!(resolve::droplet(m, cfg, m.value_of("id").unwrap()));
+    let id = &id[..];
     let domgr = DoManager::with_token(&cfg.auth[..]);
     match m.subcommand() {
-        ("kernels", Some(m))              => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                         .kernels()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).kernels().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DropletKernels(id).display();
-            match domgr.droplet(id).kernels().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for act in v.iter() {
-                        CliMessage::Kernel.display();
-                        println!("\t{}", &act.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo kernels to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+        ("kernels", Some(m))                   => {
+            cli::execute(m, cfg, domgr.droplet(id).kernels(), CliMessage::DropletKernels(id),
+                |r| cli::show_list(r, CliMessage::Kernel, "kernels"))
         },
-        ("snapshots", Some(m))            => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                         .snapshots()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).snapshots().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DropletSnapshots(id).display();
-            match domgr.droplet(id).snapshots().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for act in v.iter() {
-                        CliMessage::Snapshot.display();
-                        println!("\t{}", &act.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo snapshots to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+        ("snapshots", Some(m))                 => {
+            cli::execute(m, cfg, domgr.droplet(id).snapshots(), CliMessage::DropletSnapshots(id),
+                |r| cli::show_list(r, CliMessage::Snapshot, "snapshots"))
         },
-        ("backups", Some(m))              => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                         .backups()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).backups().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DropletBackups(id).display();
-            match domgr.droplet(id).backups().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for act in v.iter() {
-                        CliMessage::Backup.display();
-                        println!("\t{}", &act.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo backups to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+        ("backups", Some(m))                   => {
+            cli::execute(m, cfg, domgr.droplet(id).backups(), CliMessage::DropletBackups(id),
+                |r| cli::show_list(r, CliMessage::Backup, "backups"))
         },
-        ("actions", Some(m))              => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                         .actions()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).actions().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DropletActions(id).display();
-            match domgr.droplet(id).actions().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for act in v.iter() {
-                        CliMessage::Action.display();
-                        println!("\t{}\n", &act.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo actions to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+        ("actions", Some(m))                   => {
+            cli::execute(m, cfg, domgr.droplet(id).actions(), CliMessage::DropletActions(id),
+                |r| cli::show_list(r, CliMessage::Action, "actions"))
         },
         ("delete", Some(m))                    => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
-            }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .delete()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).delete().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DeleteDroplet(id).display();
-            match domgr.droplet(id).delete().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
+                try!(cli::confirm(cfg));
             }
+            cli::execute(m, cfg, domgr.droplet(id).delete(), CliMessage::DeleteDroplet(id),
+                cli::show)
         },
-        ("neighbors", Some(m))            => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                         .neighbors()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).neighbors().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DropletNeighbors(id).display();
-            match domgr.droplet(id).neighbors().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for act in v.iter() {
-                        CliMessage::Neighbor.display();
-                        println!("\t{}", &act.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo neighbors to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+        ("neighbors", Some(m))                 => {
+            cli::execute(m, cfg, domgr.droplet(id).neighbors(), CliMessage::DropletNeighbors(id),
+                |r| cli::show_list(r, CliMessage::Neighbor, "neighbors"))
         },
         ("disable-backups", Some(m))           => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .disable_backups()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).disable_backups().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DisableBackups(id).display();
-            match domgr.droplet(id).disable_backups().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).disable_backups(),
+                CliMessage::DisableBackups(id))
         },
         ("reboot", Some(m))                    => {
-            println!("Rebooting droplet with id: {}", id);
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .reboot()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).reboot().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::RebootDroplet(id).display();
-            match domgr.droplet(id).reboot().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).reboot(),
+                CliMessage::RebootDroplet(id))
         },
         ("power-cycle", Some(m))               => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .power_cycle()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).power_cycle().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::PowerCycleDroplet(id).display();
-            match domgr.droplet(id).power_cycle().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).power_cycle(),
+                CliMessage::PowerCycleDroplet(id))
         },
         ("shutdown", Some(m))                  => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .shutdown()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).shutdown().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::ShutdownDroplet(id).display();
-            match domgr.droplet(id).shutdown().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).shutdown(),
+                CliMessage::ShutdownDroplet(id))
         },
         ("power-off", Some(m))                 => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .power_off()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).power_off().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::PowerOffDroplet(id).display();
-            match domgr.droplet(id).power_off().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).power_off(),
+                CliMessage::PowerOffDroplet(id))
         },
         ("power-on", Some(m))                  => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .power_on()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).power_on().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::PowerOnDroplet(id).display();
-            match domgr.droplet(id).power_on().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).power_on(),
+                CliMessage::PowerOnDroplet(id))
         },
         ("restore", Some(m))                   => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
-            }
-            let img = m.value_of("image").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .restore(img)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).restore(img).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::RestoreDroplet(id, img).display();
-            match domgr.droplet(id).restore(img).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
+                try!(cli::confirm(cfg));
             }
+            let img = try!(resolve::image(m, cfg, m.value_of("image").unwrap()));
+            let img = &img[..];
+            cli::execute_action(m, cfg, id, domgr.droplet(id).restore(img),
+                CliMessage::RestoreDroplet(id, img))
         },
         ("reset-password", Some(m))            => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .reset_password()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).reset_password().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::ResetPassword(id).display();
-            match domgr.droplet(id).reset_password().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).reset_password(),
+                CliMessage::ResetPassword(id))
         },
         ("resize", Some(m))                    => {
             let disk = m.is_present("disk");
             let size = m.value_of("size").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .resize(size, disk)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).resize(size, disk).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::ResizeDroplet(id, size, disk).display();
-            match domgr.droplet(id).resize(size, disk).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).resize(size, disk),
+                CliMessage::ResizeDroplet(id, size, disk))
         },
         ("rebuild", Some(m))                   => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
-            }
-            let img = m.value_of("image").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .rebuild(img)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).rebuild(img).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::RebuildDroplet(id, img).display();
-            match domgr.droplet(id).rebuild(img).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
+                try!(cli::confirm(cfg));
             }
+            let img = try!(resolve::image(m, cfg, m.value_of("image").unwrap()));
+            let img = &img[..];
+            cli::execute_action(m, cfg, id, domgr.droplet(id).rebuild(img),
+                CliMessage::RebuildDroplet(id, img))
         },
         ("rename", Some(m))                    => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
+                try!(cli::confirm(cfg));
             }
             let name = m.value_of("name").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .rename(name)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).rename(name).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::RenameDroplet(id, name).display();
-            match domgr.droplet(id).rename(name).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).rename(name),
+                CliMessage::RenameDroplet(id, name))
         },
         ("change-kernel", Some(m))             => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
+                try!(cli::confirm(cfg));
             }
             let kernel = m.value_of("kernel_id").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .change_kernel(kernel)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).change_kernel(kernel).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::ChangeKernel(id, kernel).display();
-            match domgr.droplet(id).change_kernel(kernel).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).change_kernel(kernel),
+                CliMessage::ChangeKernel(id, kernel))
         },
         ("enable-ipv6", Some(m))               => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .enable_ipv6()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).enable_ipv6().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::EnableIpv6(id).display();
-            match domgr.droplet(id).enable_ipv6().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).enable_ipv6(),
+                CliMessage::EnableIpv6(id))
         },
         ("enable-private-networking", Some(m)) => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .enable_private_networking()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).enable_private_networking().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::EnablePrivateNetworking(id).display();
-            match domgr.droplet(id).enable_private_networking().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).enable_private_networking(),
+                CliMessage::EnablePrivateNetworking(id))
         },
         ("snapshot", Some(m))                  => {
             let name = m.value_of("name").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .snapshot(name)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).snapshot(name).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::SnapshotDroplet(id, name).display();
-            match domgr.droplet(id).snapshot(name).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).snapshot(name),
+                CliMessage::SnapshotDroplet(id, name))
+        },
+        ("wait", Some(m))                      => {
+            cli::wait_for_action(m, cfg, id, m.value_of("action_id").unwrap())
         },
-        ("action", Some(m))               => {
+        ("action", Some(m))                    => {
             let a_id = m.value_of("action_id").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .action(a_id)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).action(a_id).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DropletAction(id, a_id).display();
-            match domgr.droplet(id).action(a_id).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.droplet(id).action(a_id),
+                CliMessage::DropletAction(id, a_id), cli::show)
         },
-        ("upgrade", Some(m))                    => {
+        ("upgrade", Some(m))                   => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
-            }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .upgrade()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).upgrade().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::UpgradeDroplet(id).display();
-            match domgr.droplet(id).upgrade().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
+                try!(cli::confirm(cfg));
             }
+            cli::execute_action(m, cfg, id, domgr.droplet(id).upgrade(),
+                CliMessage::UpgradeDroplet(id))
         },
         _                                      => {
-            if cfg.verbose {
-                CliMessage::Request(
-                    &domgr.droplet(id)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send { return }
-            if cfg.verbose {
-                CliMessage::JsonResponse.display();
-                match domgr.droplet(id).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Droplet(id).display();
-            match domgr.droplet(id).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.droplet(id), CliMessage::Droplet(id), cli::show)
         }
     }
 }
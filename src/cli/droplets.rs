@@ -1,188 +1,371 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
 use clap::ArgMatches;
 
-use doapi::{DoManager, DoRequest};
+use doapi::DoManager;
 use doapi::request::Droplet;
+use serde::json::Value;
 
-use config::Config;
+use client::{self, Endpoint};
+use config::{Config, Profile};
+use error::{CliError, CliResult};
 use message::CliMessage;
+use output::{self, OutputFormat};
+use resolve;
+use spec;
+use userdata;
+use cli;
 
-fn droplet_from_matches(m: &ArgMatches) -> Droplet {
-    Droplet {
-        name: m.value_of("name").unwrap().to_owned(),
-        region: m.value_of("region").unwrap().to_owned(),
-        size: m.value_of("size").unwrap().to_owned(),
-        image: m.value_of("image").unwrap().to_owned(),
+// Region, size and ssh keys fall back to the defaults of the current profile. User data is read
+// from --user-data-file and rendered with --template.
+fn droplet_from_matches(m: &ArgMatches, profile: &Profile) -> Result<Droplet, CliError> {
+    let or_profile = |arg: &str, default: &Option<String>| {
+        match m.value_of(arg).map(|v| v.to_owned()).or(default.clone()) {
+            Some(v) => Ok(v),
+            None    => Err(CliError::Validation(format!("no {} given, use --{} or set one in \
+                the profile with `docli config add`", arg, arg)))
+        }
+    };
+    let name = match m.value_of("name") {
+        Some(n) => n,
+        None    => return Err(CliError::Validation("no name given, name the droplet or describe \
+            it in a file with --file".to_owned()))
+    };
+    let image = match m.value_of("image") {
+        Some(i) => i,
+        None    => return Err(CliError::Validation("no image given, use --image".to_owned()))
+    };
+    let user_data = match (m.value_of("data"), m.value_of("user-data-file")) {
+        (Some(_), Some(_)) => return Err(CliError::Validation("use either --user-data or \
+            --user-data-file, not both".to_owned())),
+        (Some(d), None)    => Some(d.to_owned()),
+        (None, Some(f))    => Some(try!(userdata::read(f))),
+        (None, None)       => None
+    };
+    let mut droplet = Droplet {
+        name: name.to_owned(),
+        region: try!(or_profile("region", &profile.region)),
+        size: try!(or_profile("size", &profile.size)),
+        image: image.to_owned(),
         ssh_keys: if let Some(v) = m.values_of("keys") {
             Some(v.iter().map(|&k| k.to_owned()).collect::<Vec<_>>())
+        } else if !profile.ssh_keys.is_empty() {
+            Some(profile.ssh_keys.clone())
         } else {
             None
         },
         backups: m.is_present("backups"),
         ipv6: m.is_present("ipv6"),
         private_networking: m.is_present("private-networking"),
-        user_data: if let Some(d) = m.value_of("data") {
-            Some(d.to_owned())
+        user_data: None,
+    };
+    if let Some(data) = user_data {
+        let data = if m.is_present("template") {
+            try!(userdata::render(&data[..], &userdata::vars(&droplet, profile)))
         } else {
-            None
+            data
+        };
+        try!(userdata::check(&data[..]));
+        droplet.user_data = Some(data);
+    }
+    Ok(droplet)
+}
+
+// Droplets described in a spec file, where --region, --size and --ssh-keys take the place of the
+// profile defaults
+fn droplets_from_file(m: &ArgMatches, path: &str, profile: &Profile)
+                      -> Result<Vec<Droplet>, CliError> {
+    if m.is_present("name") || m.is_present("image") {
+        return Err(CliError::Validation("the name and image of droplets created with --file \
+            belong in the file".to_owned()))
+    }
+    let mut defaults = profile.clone();
+    if let Some(r) = m.value_of("region") { defaults.region = Some(r.to_owned()); }
+    if let Some(s) = m.value_of("size") { defaults.size = Some(s.to_owned()); }
+    if let Some(k) = m.values_of("keys") {
+        defaults.ssh_keys = k.iter().map(|&k| k.to_owned()).collect();
+    }
+    let v = try!(spec::load(path));
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    spec::droplets(&v, &defaults, dir).map_err(|e| match e {
+        CliError::Validation(s) => CliError::Validation(format!("{}: {}", path, s)),
+        e                       => e
+    })
+}
+
+/// Creates the droplets one after the other. Image and key names are only looked up once, and a
+/// droplet that can't be created doesn't keep the others from being created.
+fn create(m: &ArgMatches, cfg: &Config, droplets: Vec<Droplet>) -> CliResult {
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    let total = droplets.len();
+    let mut known = BTreeMap::new();
+    let mut failed = vec![];
+    for mut d in droplets {
+        let res = resolve_names(m, cfg, &mut d, &mut known).and_then(|_| {
+            cli::execute(m, cfg, domgr.droplets().create(&d), CliMessage::CreateDroplet(&d),
+                cli::show)
+        });
+        if let Err(e) = res { failed.push(e); }
+    }
+    match failed.len() {
+        0               => Ok(()),
+        n if n == total => Err(failed.remove(0)),
+        n               => cli::fail(cfg, CliError::Partial(n, total))
+    }
+}
+
+pub fn resolve_names(m: &ArgMatches, cfg: &Config, d: &mut Droplet,
+                     known: &mut BTreeMap<String, String>) -> CliResult {
+    let image = d.image.clone();
+    d.image = try!(cached(known, format!("image {}", image), || {
+        resolve::image(m, cfg, &image[..])
+    }));
+    if let Some(keys) = d.ssh_keys.take() {
+        let mut ids = vec![];
+        for k in keys {
+            ids.push(try!(cached(known, format!("key {}", k), || {
+                resolve::ssh_key(m, cfg, &k[..])
+            })));
         }
+        d.ssh_keys = Some(ids);
     }
+    Ok(())
 }
 
-pub fn run(m: &ArgMatches, cfg: &mut Config) {
+fn cached<F>(known: &mut BTreeMap<String, String>, key: String, lookup: F)
+             -> Result<String, CliError>
+             where F: FnOnce() -> Result<String, CliError> {
+    if let Some(id) = known.get(&key) { return Ok(id.clone()) }
+    let id = try!(lookup());
+    known.insert(key, id.clone());
+    Ok(id)
+}
+
+pub fn run(m: &ArgMatches, cfg: &mut Config) -> CliResult {
     if m.is_present("verbose") { cfg.verbose = true; }
     if m.is_present("nosend") { cfg.no_send = true; }
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
     let domgr = DoManager::with_token(&cfg.auth[..]);
     match m.subcommand() {
         ("neighbors", Some(m)) => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplets()
-                         .neighbors()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplets().neighbors().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::AllDropletNeighbors.display();
-            match domgr.droplets().neighbors().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for nv in v.iter() {
+            cli::execute(m, cfg, domgr.droplets().neighbors(), CliMessage::AllDropletNeighbors,
+                |r| {
+                    for nv in r.value.iter() {
                         for dr in nv.iter() {
                             CliMessage::Neighbor.display();
                             println!("\t{}\n", &dr.to_string()[..].replace("\n", "\n\t"));
                         }
                     }
-                    if v.is_empty() { println!("\tNo neighbors to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+                    if r.value.is_empty() { println!("\tNo neighbors to display"); }
+                })
         },
         ("upgrades", Some(m))  => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplets()
-                         .upgrades()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplets().upgrades().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::AllDropletUpgrades.display();
-            match domgr.droplets().upgrades().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for act in v.iter() {
-                        CliMessage::NamelessDroplet.display();
-                        println!("\t{}", act);
-                    }
-                    if v.is_empty() { println!("\tNo upgrades to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.droplets().upgrades(), CliMessage::AllDropletUpgrades,
+                |r| cli::show_list(r, CliMessage::NamelessDroplet, "upgrades"))
         },
-        ("create", Some(m))         => {
-            let droplet_cfg = droplet_from_matches(&m);
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplets()
-                          .create(&droplet_cfg)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
+        ("create", Some(m))    => {
+            if cfg.token_from_stdin && m.value_of("user-data-file") == Some("-") {
+                return cli::fail(cfg, CliError::Validation("the token was read from stdin, which \
+                    leaves no user data to read there, give --user-data-file a file".to_owned()))
             }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplets().create(&droplet_cfg).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::CreateDroplet(&droplet_cfg).display();
-            match domgr.droplets().create(&droplet_cfg).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", s);
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
+            let droplets = match m.value_of("file") {
+                Some(f) => droplets_from_file(m, f, &cfg.profile),
+                None    => droplet_from_matches(&m, &cfg.profile).map(|d| vec![d])
+            };
+            match droplets {
+                Ok(d)  => create(m, cfg, d),
+                Err(e) => cli::fail(cfg, e)
             }
         },
-        _                           => {
-            if cfg.verbose {
-                CliMessage::Request(
-                    &domgr.droplets()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
+        ("reboot", Some(m))    => bulk(m, cfg, "reboot", "Rebooting"),
+        ("power-off", Some(m)) => bulk(m, cfg, "power-off", "Powering off"),
+        ("power-on", Some(m))  => bulk(m, cfg, "power-on", "Powering on"),
+        ("shutdown", Some(m))  => bulk(m, cfg, "shutdown", "Shutting down"),
+        ("snapshot", Some(m))  => bulk(m, cfg, "snapshot", "Snapshotting"),
+        ("delete", Some(m))    => bulk(m, cfg, "delete", "Deleting"),
+        _                      => {
+            cli::execute(m, cfg, domgr.droplets(), CliMessage::Droplets,
+                |r| cli::show_list(r, CliMessage::AnonDroplet, "droplets"))
+        }
+    }
+}
+
+// How many droplets a bulk command works on at once unless `--parallel` says otherwise
+const PARALLEL: usize = 4;
+
+/// Runs `op` on every droplet picked by `--match`, `--tag`, `--region` and `--size` once the
+/// selection has been confirmed. Droplets are worked on by a few threads at a time and the outcome
+/// for each is displayed as it comes in; when only some of them fail the result is
+/// `CliError::Partial`.
+fn bulk(m: &ArgMatches, cfg: &Config, op: &str, doing: &str) -> CliResult {
+    let text = !cfg.output.is_structured();
+    let parallel = match m.value_of("parallel").map(|p| p.parse::<usize>()) {
+        Some(Ok(p)) if p > 0 => p,
+        Some(_)              => return cli::fail(cfg, CliError::Validation(format!("--parallel \
+            expects a number of droplets, not '{}'", m.value_of("parallel").unwrap()))),
+        None                 => PARALLEL
+    };
+    if !["match", "tag", "region", "size"].iter().any(|a| m.is_present(a)) {
+        return cli::fail(cfg, CliError::Validation("select the droplets with --match, --tag, \
+            --region or --size".to_owned()))
+    }
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    // Droplets have to be listed before anything can be displayed
+    if cfg.no_send || m.is_present("nosend") {
+        return cli::execute(m, cfg, domgr.droplets(), CliMessage::Droplets, |_| ())
+    }
+    let all = match client::send(&domgr.droplets(), &cfg.api) {
+        Ok(r)  => r.data,
+        Err(e) => return cli::fail(cfg, e)
+    };
+    let targets: Vec<(String, String)> = all.as_array().map(|a| &a[..]).unwrap_or(&[]).iter()
+        .filter(|d| selects(m, d))
+        .map(|d| (d.find("id").and_then(|i| i.as_u64()).unwrap_or(0).to_string(),
+                  text_of(d, &["name"]).to_owned()))
+        .collect();
+    if targets.is_empty() {
+        return cli::fail(cfg, CliError::NotFound("no droplets match the selection".to_owned()))
+    }
+
+    // The selection goes to stderr with --output, stdout is only for the results
+    if text {
+        CliMessage::SelectedDroplets(&op.replace("-", " ")[..], targets.len()).display();
+    }
+    for &(ref id, ref name) in targets.iter() {
+        if text {
+            println!("\t{} (ID {})", name, id);
+        } else {
+            writeln!(&mut io::stderr(), "{} (ID {})", name, id).ok();
+        }
+    }
+    if !m.is_present("noconfirm") {
+        try!(cli::confirm(cfg));
+    }
+    if text { CliMessage::BulkDroplets(doing, targets.len()).display(); }
+    let verbose = cfg.verbose || m.is_present("verbose");
+
+    // Workers take droplets from the back of the queue, so it's reversed to keep the order
+    let queue = Arc::new(Mutex::new(targets.iter().rev().cloned().collect::<Vec<_>>()));
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..cmp::min(parallel, targets.len()) {
+        let (queue, tx) = (queue.clone(), tx.clone());
+        let (op, auth, api) = (op.to_owned(), cfg.auth.clone(), cfg.api.clone());
+        let snapshot = m.value_of("name").unwrap_or("").to_owned();
+        thread::spawn(move || {
+            loop {
+                let next = queue.lock().unwrap().pop();
+                let (id, name) = match next {
+                    Some(t) => t,
+                    None    => break
+                };
+                let snapshot = snapshot.replace("{name}", &name[..]).replace("{id}", &id[..]);
+                let (req, res) = bulk_one(&op[..], &id[..], &snapshot[..], &auth[..], &api);
+                if tx.send((id, name, req, res)).is_err() { break }
             }
-            if cfg.no_send { return }
-            if cfg.verbose {
-                CliMessage::JsonResponse.display();
-                match domgr.droplets().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
+        });
+    }
+    drop(tx);
+
+    let mut results = vec![];
+    let mut failed = 0;
+    let mut first_err = None;
+    for (id, name, req, res) in rx.iter() {
+        if verbose {
+            if text {
+                CliMessage::Request(&req[..]).display();
+            } else {
+                writeln!(&mut io::stderr(), "{}", req).ok();
             }
-            CliMessage::Droplets.display();
-            match domgr.droplets().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for d in v.iter() {
-                        CliMessage::AnonDroplet.display();
-                        println!("\t{}", &d.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo droplets to dipslay"); }
-                },
-                Err(e) => {
+        }
+        let mut row = BTreeMap::new();
+        row.insert("id".to_owned(), Value::U64(id.parse().unwrap_or(0)));
+        row.insert("name".to_owned(), Value::String(name.clone()));
+        row.insert("success".to_owned(), Value::Bool(res.is_ok()));
+        if text { print!("\t{} (ID {}): ", name, id); }
+        match res {
+            Ok(action) => {
+                if text { CliMessage::Success.display(); }
+                row.insert("action_id".to_owned(), action.map(Value::U64).unwrap_or(Value::Null));
+                row.insert("error".to_owned(), Value::Null);
+            },
+            Err(e)     => {
+                if text {
                     CliMessage::Failure.display();
-                    println!("{}\n", e);
+                    println!("\t\t{}", e);
                 }
+                row.insert("action_id".to_owned(), Value::Null);
+                row.insert("error".to_owned(), Value::String(e.to_string()));
+                failed += 1;
+                if first_err.is_none() { first_err = Some(e); }
             }
         }
+        results.push(Value::Object(row));
     }
+    let total = results.len();
+    if !text { print!("{}", output::render(cfg.output, &Value::Array(results))); }
+
+    match first_err {
+        None    => Ok(()),
+        // When nothing worked there's likely a single cause, worth its own exit code
+        Some(e) => {
+            cli::fail(cfg, if failed == total { e } else { CliError::Partial(failed, total) })
+        }
+    }
+}
+
+// Sends a single request of a bulk command, handing back the request as --verbose displays it and
+// the ID of the action it started if any
+fn bulk_one(op: &str, id: &str, snapshot: &str, auth: &str, api: &Endpoint)
+            -> (String, Result<Option<u64>, CliError>) {
+    let domgr = DoManager::with_token(auth);
+    let (req, res) = match op {
+        "reboot"    => {
+            let r = domgr.droplet(id).reboot();
+            (cli::shown_request(&r, auth, api), client::send(&r, api))
+        },
+        "power-off" => {
+            let r = domgr.droplet(id).power_off();
+            (cli::shown_request(&r, auth, api), client::send(&r, api))
+        },
+        "power-on"  => {
+            let r = domgr.droplet(id).power_on();
+            (cli::shown_request(&r, auth, api), client::send(&r, api))
+        },
+        "shutdown"  => {
+            let r = domgr.droplet(id).shutdown();
+            (cli::shown_request(&r, auth, api), client::send(&r, api))
+        },
+        "snapshot"  => {
+            let r = domgr.droplet(id).snapshot(snapshot);
+            (cli::shown_request(&r, auth, api), client::send(&r, api))
+        },
+        _           => {
+            let r = domgr.droplet(id).delete();
+            return (cli::shown_request(&r, auth, api), client::send(&r, api).map(|_| None))
+        }
+    };
+    (req, res.map(|r| r.data.find("id").and_then(|i| i.as_u64())))
+}
+
+/// Whether a droplet is picked by `--match`, `--tag`, `--region` and `--size`, all of them being
+/// optional
+pub fn selects(m: &ArgMatches, d: &Value) -> bool {
+    let tagged = |t: &str| {
+        d.find("tags").and_then(|tags| tags.as_array())
+                      .map(|tags| tags.iter().any(|tag| tag.as_string() == Some(t)))
+                      .unwrap_or(false)
+    };
+    m.value_of("match").map(|p| resolve::glob(p, text_of(d, &["name"]))).unwrap_or(true)
+        && m.value_of("tag").map(|t| tagged(t)).unwrap_or(true)
+        && m.value_of("region").map(|r| text_of(d, &["region", "slug"]) == r).unwrap_or(true)
+        && m.value_of("size").map(|s| text_of(d, &["size_slug"]) == s).unwrap_or(true)
+}
+
+fn text_of<'a>(v: &'a Value, path: &[&str]) -> &'a str {
+    v.find_path(path).and_then(|s| s.as_string()).unwrap_or("")
 }
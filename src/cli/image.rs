@@ -1,270 +1,58 @@
-
 use clap::ArgMatches;
 
-use doapi::{DoManager, DoRequest};
+use doapi::DoManager;
 
 use config::Config;
+use error::CliResult;
 use message::CliMessage;
+use output::OutputFormat;
+use resolve;
 use cli;
 
-pub fn run(m: &ArgMatches, cfg: &mut Config) {
+pub fn run(m: &ArgMatches, cfg: &mut Config) -> CliResult {
     if m.is_present("verbose") { cfg.verbose = true; }
     if m.is_present("nosend") { cfg.no_send = true; }
-    let id = m.value_of("id").unwrap();
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
+    let id = try!(resolve::image(m, cfg, m.value_of("id").unwrap()));
+    let id = &id[..];
     let domgr = DoManager::with_token(&cfg.auth[..]);
     match m.subcommand() {
         ("actions", Some(m))      => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.image(id)
-                         .actions()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.image(id).actions().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::ImageActions(id).display();
-            match domgr.image(id).actions().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    for act in s.iter() {
-                        CliMessage::Action.display();
-                        println!("\t{}\n", &act.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if s.is_empty() { println!("\tNo actions to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.image(id).actions(), CliMessage::ImageActions(id),
+                |r| cli::show_list(r, CliMessage::Action, "actions"))
         },
-        ("rename", Some(m))            => {
+        ("rename", Some(m))       => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
+                try!(cli::confirm(cfg));
             }
             let name = m.value_of("name").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.image(id)
-                          .update(name)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.image(id).update(name).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::UpdateImage(id, name).display();
-            match domgr.image(id).update(name).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.image(id).update(name), CliMessage::UpdateImage(id, name),
+                cli::show)
         },
-        ("delete", Some(m))            => {
+        ("delete", Some(m))       => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
-            }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.image(id)
-                          .delete()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.image(id).delete().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DeleteImage(id).display();
-            match domgr.image(id).delete().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
+                try!(cli::confirm(cfg));
             }
+            cli::execute(m, cfg, domgr.image(id).delete(), CliMessage::DeleteImage(id), cli::show)
         },
-        ("transfer", Some(m))          => {
+        ("transfer", Some(m))     => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
+                try!(cli::confirm(cfg));
             }
             let reg = m.value_of("region").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.image(id)
-                         .transfer(reg)
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.image(id).transfer(reg).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::TransferImage(id, reg).display();
-            match domgr.image(id).transfer(reg).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.image(id).transfer(reg), CliMessage::TransferImage(id, reg),
+                cli::show)
         },
-        ("convert", Some(m))           => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.image(id)
-                          .convert()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.image(id).convert().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::ConvertImage(id).display();
-            match domgr.image(id).convert().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+        ("convert", Some(m))      => {
+            cli::execute(m, cfg, domgr.image(id).convert(), CliMessage::ConvertImage(id), cli::show)
         },
         ("action", Some(m))       => {
             let a_id = m.value_of("action_id").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.image(id)
-                         .action(a_id)
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.image(id).action(a_id).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::ImageAction(id, a_id).display();
-            match domgr.image(id).action(a_id).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.image(id).action(a_id), CliMessage::ImageAction(id, a_id),
+                cli::show)
         },
-        _                              => {
-            if cfg.verbose {
-                CliMessage::Request(
-                    &domgr.image(id)
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send { return }
-            if cfg.verbose {
-                CliMessage::JsonResponse.display();
-                match domgr.image(id).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Image(id).display();
-            match domgr.image(id).retrieve() {
-                Ok(img) => {
-                    CliMessage::Success.display();
-                    println!("\t{}\n", &img.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+        _                         => {
+            cli::execute(m, cfg, domgr.image(id), CliMessage::Image(id), cli::show)
         }
     }
 }
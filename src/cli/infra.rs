@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use doapi::DoManager;
+use doapi::request::Droplet;
+use serde::json::Value;
+
+use client::{self, Endpoint};
+use config::Config;
+use error::{CliError, CliResult};
+use infra::{self, Change, DriftKind, Live, Op, Plan, Resource, State};
+use message::CliMessage;
+use output::{self, OutputFormat};
+use cli;
+use cli::droplets;
+
+/// `docli plan`, displays what `apply` would change without changing anything
+pub fn plan(m: &ArgMatches, cfg: &mut Config) -> CliResult {
+    settings(m, cfg);
+    let file = m.value_of("file").unwrap();
+    let (_, _, plan) = match try!(prepare(m, cfg)) {
+        Some(p) => p,
+        None    => return Ok(())
+    };
+    if cfg.output.is_structured() {
+        let changes = plan.changes.iter().map(|c| c.to_json()).collect();
+        print!("{}", output::render(cfg.output, &Value::Array(changes)));
+    } else {
+        CliMessage::Plan(file).display();
+        show_plan(&plan, file, &mut io::stdout());
+    }
+    Ok(())
+}
+
+/// `docli apply`, carries out the plan once confirmed. The state file is saved after every step,
+/// so a run that stops half way picks up where it left off the next time.
+pub fn apply(m: &ArgMatches, cfg: &mut Config) -> CliResult {
+    settings(m, cfg);
+    let file = m.value_of("file").unwrap();
+    let text = !cfg.output.is_structured();
+    let (resources, mut state, plan) = match try!(prepare(m, cfg)) {
+        Some(p) => p,
+        None    => return Ok(())
+    };
+    if text {
+        CliMessage::Plan(file).display();
+        show_plan(&plan, file, &mut io::stdout());
+    } else {
+        show_plan(&plan, file, &mut io::stderr());
+    }
+    if !plan.changes.is_empty() && !m.is_present("noconfirm") { try!(cli::confirm(cfg)); }
+    // Whatever matches already is adopted, so that it's found by its ID from now on
+    for &(ref address, ref id) in plan.unchanged.iter() {
+        state.ids.insert(address.clone(), id.clone());
+    }
+    for address in plan.gone.iter() {
+        state.ids.remove(address);
+    }
+    if let Err(e) = state.save() { return cli::fail(cfg, e) }
+    if plan.changes.is_empty() {
+        if !text { print!("{}", output::render(cfg.output, &Value::Array(vec![]))); }
+        return Ok(())
+    }
+
+    if text { CliMessage::Apply(file).display(); }
+    let mut known = BTreeMap::new();
+    let mut results = vec![];
+    let mut failure = None;
+    for c in plan.changes.iter() {
+        if text {
+            print!("\t{} {} ", c.op.symbol(), c.address);
+            io::stdout().flush().ok();
+        }
+        let res = carry_out(m, cfg, c, &resources, &mut known, &mut state);
+        let mut row = match c.to_json() {
+            Value::Object(map) => map,
+            _                  => BTreeMap::new()
+        };
+        row.insert("success".to_owned(), Value::Bool(res.is_ok()));
+        match res {
+            Ok(id) => {
+                if text { CliMessage::Success.display(); }
+                row.insert("id".to_owned(), id.clone().map(Value::String).unwrap_or(Value::Null));
+                match id {
+                    Some(id) => { state.ids.insert(c.address.clone(), id); },
+                    None     => { state.ids.remove(&c.address); }
+                }
+            },
+            Err(e) => {
+                if text { CliMessage::Failure.display(); }
+                row.insert("error".to_owned(), Value::String(e.to_string()));
+                failure = Some(e);
+            }
+        }
+        results.push(Value::Object(row));
+        if let Err(e) = state.save() { return cli::fail(cfg, e) }
+        if failure.is_some() { break }
+    }
+    let done = results.len() - if failure.is_some() { 1 } else { 0 };
+    if !text { print!("{}", output::render(cfg.output, &Value::Array(results))); }
+    match failure {
+        Some(e) => {
+            if text {
+                println!("\n\tStopped after {} of {} change(s), what was done is in the state \
+                    file", done, plan.changes.len());
+            }
+            cli::fail(cfg, e)
+        },
+        None    => Ok(())
+    }
+}
+
+/// `docli drift`, only reads the account and tells how it differs from the manifest. Any
+/// difference makes it fail, so that it can be run as a regular check.
+pub fn drift(m: &ArgMatches, cfg: &mut Config) -> CliResult {
+    settings(m, cfg);
+    let file = m.value_of("file").unwrap();
+    let resources = match infra::load(file, &cfg.profile) {
+        Ok(r)  => r,
+        Err(e) => return cli::fail(cfg, e)
+    };
+    if cfg.no_send {
+        let domgr = DoManager::with_token(&cfg.auth[..]);
+        return cli::execute(m, cfg, domgr.ssh_keys(), CliMessage::SshKeys, |_| ())
+    }
+    let live = match live(cfg, infra::domains(&resources, None)) {
+        Ok(l)  => l,
+        Err(e) => return cli::fail(cfg, e)
+    };
+    let drift = infra::drift(&resources, &live);
+    if cfg.output.is_structured() {
+        let found = drift.iter().map(|d| d.to_json()).collect();
+        print!("{}", output::render(cfg.output, &Value::Array(found)));
+    } else {
+        CliMessage::Drift(file).display();
+        for d in drift.iter() {
+            let id = d.id.as_ref().map(|id| format!(" (ID {})", id)).unwrap_or(String::new());
+            println!("\t{:<10} {}{}", d.kind.name(), d.address, id);
+            for detail in d.details.iter() {
+                println!("\t\t{}", detail);
+            }
+        }
+        if drift.is_empty() {
+            println!("\tNo drift, the account matches {}", file);
+        } else {
+            let count = |k| drift.iter().filter(|d| d.kind == k).count();
+            println!("\n\t{} missing, {} unexpected, {} different", count(DriftKind::Missing),
+                count(DriftKind::Unexpected), count(DriftKind::Differs));
+        }
+    }
+    if drift.is_empty() { Ok(()) } else { cli::fail(cfg, CliError::Drift(drift.len())) }
+}
+
+fn settings(m: &ArgMatches, cfg: &mut Config) {
+    if m.is_present("verbose") { cfg.verbose = true; }
+    if m.is_present("nosend") { cfg.no_send = true; }
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
+}
+
+// The state file sits next to the manifest unless --state says otherwise
+fn state_path(m: &ArgMatches) -> String {
+    match m.value_of("state") {
+        Some(s) => s.to_owned(),
+        None    => {
+            Path::new(m.value_of("file").unwrap()).with_extension("state.json")
+                                                  .to_string_lossy().into_owned()
+        }
+    }
+}
+
+// Reads the manifest and the state, then compares them with the account. With --nosend only the
+// first listing is displayed and nothing is compared.
+fn prepare(m: &ArgMatches, cfg: &Config) -> Result<Option<(Vec<Resource>, State, Plan)>, CliError> {
+    let loaded = infra::load(m.value_of("file").unwrap(), &cfg.profile).and_then(|resources| {
+        State::load(&state_path(m)[..]).map(|state| (resources, state))
+    });
+    let (resources, state) = match loaded {
+        Ok(l)  => l,
+        Err(e) => return cli::fail(cfg, e).map(|_| None)
+    };
+    if cfg.no_send {
+        let domgr = DoManager::with_token(&cfg.auth[..]);
+        return cli::execute(m, cfg, domgr.ssh_keys(), CliMessage::SshKeys, |_| ()).map(|_| None)
+    }
+    let live = match live(cfg, infra::domains(&resources, Some(&state))) {
+        Ok(l)  => l,
+        Err(e) => return cli::fail(cfg, e).map(|_| None)
+    };
+    let plan = infra::plan(&resources, &live, &state);
+    Ok(Some((resources, state, plan)))
+}
+
+// Lists the account, along with the records of `domains` that exist
+fn live(cfg: &Config, domains: BTreeSet<String>) -> Result<Live, CliError> {
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    let list = |v: Value| match v {
+        Value::Array(a) => a,
+        _               => vec![]
+    };
+    let mut live = Live {
+        ssh_keys: list(try!(client::send(&domgr.ssh_keys(), &cfg.api)).data),
+        domains: list(try!(client::send(&domgr.domains(), &cfg.api)).data),
+        records: BTreeMap::new(),
+        droplets: list(try!(client::send(&domgr.droplets(), &cfg.api)).data),
+    };
+    for d in domains {
+        if live.domains.iter().any(|v| infra::id_of("domain", v) == d) {
+            let records = try!(client::send(&domgr.domain(&d[..]).dns_records(), &cfg.api));
+            live.records.insert(d, list(records.data));
+        }
+    }
+    Ok(live)
+}
+
+fn show_plan(plan: &Plan, file: &str, out: &mut Write) {
+    for c in plan.changes.iter() {
+        let id = c.id.as_ref().map(|id| format!(" (ID {})", id)).unwrap_or(String::new());
+        writeln!(out, "\t{} {}{}", c.op.symbol(), c.address, id).ok();
+        for d in c.details.iter() {
+            writeln!(out, "\t\t{}", d).ok();
+        }
+    }
+    if plan.changes.is_empty() {
+        writeln!(out, "\tNo changes, the account matches {}", file).ok();
+    } else {
+        writeln!(out, "\n\t{} to create, {} to update, {} to replace, {} to delete, {} unchanged",
+            plan.count(Op::Create), plan.count(Op::Update), plan.count(Op::Replace),
+            plan.count(Op::Delete), plan.unchanged.len()).ok();
+    }
+}
+
+// Makes a single change, returning the ID of the resource afterwards, or None once it's deleted.
+// Replacing creates the new resource before deleting the old one, so nothing is lost when creating
+// it fails. Once the new one exists it's in `state`, even if the old one can't be deleted.
+fn carry_out(m: &ArgMatches, cfg: &Config, c: &Change, resources: &[Resource],
+             known: &mut BTreeMap<String, String>, state: &mut State)
+             -> Result<Option<String>, CliError> {
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    let id = c.id.clone().unwrap_or(String::new());
+    if c.op == Op::Delete {
+        try!(delete(&domgr, &cfg.api, &c.address[..], &id[..]));
+        return Ok(None)
+    }
+    let made = try!(make(m, cfg, c, &resources[c.resource.unwrap()], known));
+    if c.op == Op::Replace {
+        if let Some(ref new) = made { state.ids.insert(c.address.clone(), new.clone()); }
+        try!(delete(&domgr, &cfg.api, &c.address[..], &id[..]));
+    }
+    Ok(made)
+}
+
+// Creates the resource of `c`, or updates it in place
+fn make(m: &ArgMatches, cfg: &Config, c: &Change, r: &Resource,
+        known: &mut BTreeMap<String, String>) -> Result<Option<String>, CliError> {
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    let api = &cfg.api;
+    let id = c.id.clone().unwrap_or(String::new());
+    let created = |v: Value| Some(infra::id_of(r.kind(), &v));
+    match *r {
+        Resource::SshKey { ref name, ref public_key } => {
+            client::send(&domgr.ssh_keys().create(name, public_key), api).map(|r| created(r.data))
+        },
+        Resource::Domain { ref name, ref ip } => {
+            client::send(&domgr.domains().create(name, ip), api).map(|r| created(r.data))
+        },
+        Resource::Record { ref domain, ref record } => {
+            if c.op == Op::Update {
+                return client::send(&domgr.domain(domain).dns_record(&id[..]).update(record), api)
+                              .map(|r| created(r.data))
+            }
+            // A new domain comes with records of its own, which are adopted rather than doubled
+            let existing = try!(client::send(&domgr.domain(domain).dns_records(), api)).data;
+            if let Some(v) = existing.as_array().and_then(|a| a.iter().find(|v| r.is(v))) {
+                return Ok(created(v.clone()))
+            }
+            client::send(&domgr.domain(domain).dns_records().create(record), api)
+                  .map(|r| created(r.data))
+        },
+        Resource::Droplet(ref d) => {
+            if c.op == Op::Update {
+                try!(resize(m, cfg, &domgr, &id[..], &d.size[..]));
+                return Ok(Some(id.clone()))
+            }
+            let mut d = copy(d);
+            try!(droplets::resolve_names(m, cfg, &mut d, known));
+            client::send(&domgr.droplets().create(&d), api).map(|r| created(r.data))
+        }
+    }
+}
+
+// DigitalOcean only resizes droplets that are off, so a running droplet is powered off first and
+// back on afterwards, each action being waited for before the next one
+fn resize(m: &ArgMatches, cfg: &Config, domgr: &DoManager, id: &str, size: &str) -> CliResult {
+    let api = &cfg.api;
+    let d = try!(client::send(&domgr.droplet(id), api)).data;
+    let running = d.find("status").and_then(|s| s.as_string()) != Some("off");
+    if running {
+        let r = try!(client::send(&domgr.droplet(id).power_off(), api));
+        try!(cli::await_action(m, cfg, id, &try!(cli::action_id(&r))[..]));
+    }
+    let r = try!(client::send(&domgr.droplet(id).resize(size, false), api));
+    try!(cli::await_action(m, cfg, id, &try!(cli::action_id(&r))[..]));
+    if running {
+        let r = try!(client::send(&domgr.droplet(id).power_on(), api));
+        try!(cli::await_action(m, cfg, id, &try!(cli::action_id(&r))[..]));
+    }
+    Ok(())
+}
+
+fn delete(domgr: &DoManager, api: &Endpoint, address: &str, id: &str) -> CliResult {
+    let res = match infra::split(address) {
+        ("ssh_key", _)     => client::send(&domgr.ssh_key(id).destroy(), api),
+        ("domain", _)      => client::send(&domgr.domain(id).delete(), api),
+        ("record", domain) => client::send(&domgr.domain(domain).dns_record(id).delete(), api),
+        _                  => client::send(&domgr.droplet(id).delete(), api),
+    };
+    res.map(|_| ())
+}
+
+// Request droplets can't be cloned, and creating one needs its image and keys turned into IDs
+fn copy(d: &Droplet) -> Droplet {
+    Droplet {
+        name: d.name.clone(),
+        region: d.region.clone(),
+        size: d.size.clone(),
+        image: d.image.clone(),
+        ssh_keys: d.ssh_keys.clone(),
+        backups: d.backups,
+        ipv6: d.ipv6,
+        private_networking: d.private_networking,
+        user_data: d.user_data.clone(),
+    }
+}
@@ -0,0 +1,62 @@
+use std::io::{self, Write};
+
+use clap::ArgMatches;
+
+use doapi::DoManager;
+
+use client;
+use config::Config;
+use error::{CliError, CliResult};
+use inventory::{self, Host, Options};
+use message::CliMessage;
+use output::OutputFormat;
+use token;
+use cli::{self, droplets, ssh};
+
+// The port of the Prometheus node exporter
+const PORT: u16 = 9100;
+
+/// Writes the droplets of the account, or those picked by `--match`, `--tag`, `--region` and
+/// `--size`, as an inventory for other tools. It's printed alone so it can be redirected.
+pub fn run(m: &ArgMatches, cfg: &mut Config) -> CliResult {
+    if m.is_present("verbose") { cfg.verbose = true; }
+    if m.is_present("nosend") { cfg.no_send = true; }
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
+    let port = match m.value_of("port").map(|p| p.parse::<u16>()) {
+        Some(Ok(p)) => p,
+        Some(_)     => return cli::fail(cfg, CliError::Validation(format!("--port expects a port \
+            number, not '{}'", m.value_of("port").unwrap()))),
+        None        => PORT
+    };
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    if cfg.no_send {
+        return cli::execute(m, cfg, domgr.droplets(), CliMessage::Droplets, |_| ())
+    }
+    let all = match client::send(&domgr.droplets(), &cfg.api) {
+        Ok(r)  => r.data,
+        Err(e) => return cli::fail(cfg, e)
+    };
+    let (private, ipv6) = (m.is_present("private"), m.is_present("ipv6"));
+    let mut hosts = vec![];
+    let listed = all.as_array().map(|a| &a[..]).unwrap_or(&[]);
+    for d in listed.iter().filter(|d| droplets::selects(m, d)) {
+        match ssh::address(d, private, ipv6) {
+            Some(a) => hosts.push(Host::of(d, a)),
+            None    => {
+                let name = d.find("name").and_then(|n| n.as_string()).unwrap_or("");
+                writeln!(&mut io::stderr(), "{} has no {} address, it's left out", name,
+                    ssh::network(private, ipv6)).ok();
+            }
+        }
+    }
+    hosts.sort_by(|a, b| a.name.cmp(&b.name));
+    let opts = Options {
+        groupings: m.values_of("group-by").unwrap_or(inventory::GROUPINGS.to_vec()),
+        user: cfg.profile.ssh_user.clone(),
+        identity: cfg.profile.ssh_identity.as_ref()
+                     .map(|i| token::expand_home(&i[..]).to_string_lossy().into_owned()),
+        port: port,
+    };
+    print!("{}", inventory::render(m.value_of("format").unwrap(), &hosts[..], &opts));
+    Ok(())
+}
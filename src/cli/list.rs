@@ -1,89 +1,26 @@
-
 use clap::ArgMatches;
 
-use doapi::{DoManager, DoRequest};
+use doapi::DoManager;
 
 use config::Config;
+use error::CliResult;
 use message::CliMessage;
+use output::OutputFormat;
+use cli;
 
-pub fn run(m: &ArgMatches, cfg: &mut Config) {
+pub fn run(m: &ArgMatches, cfg: &mut Config) -> CliResult {
     if m.is_present("verbose") { cfg.verbose = true; }
     if m.is_present("nosend") { cfg.no_send = true; }
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
     let domgr = DoManager::with_token(&cfg.auth[..]);
     match m.subcommand() {
         ("regions", Some(m))         => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.regions()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.regions().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Regions.display();
-            match domgr.regions().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for reg in v.iter() {
-                        CliMessage::Region.display();
-                        println!("\t{}\n", &reg.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo regions to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.regions(), CliMessage::Regions,
+                |r| cli::show_list(r, CliMessage::Region, "regions"))
         },
         ("sizes", Some(m))           => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.sizes()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.sizes().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Sizes.display();
-            match domgr.sizes().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for siz in v.iter() {
-                        CliMessage::Size.display();
-                        println!("\t{}\n", &siz.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo sizes to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.sizes(), CliMessage::Sizes,
+                |r| cli::show_list(r, CliMessage::Size, "sizes"))
         },
         ("images", Some(m))          => {
             let request = if m.is_present("applications") {
@@ -97,190 +34,24 @@ pub fn run(m: &ArgMatches, cfg: &mut Config) {
             } else {
                 domgr.images()
             };
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &request.to_string()
-                        .replace("\n", "\n\t")[..]
-                ).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match request.retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Images.display();
-            match request.retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for img in v.iter() {
-                        CliMessage::ImageList.display();
-                        println!("\t{}\n", &img.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo images to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, request, CliMessage::Images,
+                |r| cli::show_list(r, CliMessage::ImageList, "images"))
         },
         ("ssh-keys", Some(m))        => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.ssh_keys()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.ssh_keys().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::SshKeys.display();
-            match domgr.ssh_keys().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for k in v.iter() {
-                        CliMessage::AnonSshKey.display();
-                        println!("\t{}\n", &k.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo SSH keys to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.ssh_keys(), CliMessage::SshKeys,
+                |r| cli::show_list(r, CliMessage::AnonSshKey, "SSH keys"))
         },
         ("droplets", Some(m))        => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.droplets()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.droplets().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Droplets.display();
-            match domgr.droplets().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for d in v.iter() {
-                        CliMessage::AnonDroplet.display();
-                        println!("\t{}", &d.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo droplets to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.droplets(), CliMessage::Droplets,
+                |r| cli::show_list(r, CliMessage::AnonDroplet, "droplets"))
         },
         ("domains", Some(m))         => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.domains()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.domains().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Domains.display();
-            match domgr.domains().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for d in v.iter() {
-                        CliMessage::Domains.display();
-                        println!("\t{}\n", &d.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo domains to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.domains(), CliMessage::Domains,
+                |r| cli::show_list(r, CliMessage::Domains, "domains"))
         },
         ("account-actions", Some(m)) => {
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.account()
-                         .actions()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.account().actions().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::Actions.display();
-            match domgr.account().actions().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for act in v.iter() {
-                        CliMessage::Action.display();
-                        println!("\t{}\n", &act.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo account actions to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.account().actions(), CliMessage::Actions,
+                |r| cli::show_list(r, CliMessage::Action, "account actions"))
         },
         _                      => unreachable!()
     }
@@ -6,16 +6,285 @@ pub mod droplets;
 pub mod dns;
 pub mod domains;
 pub mod account;
+pub mod config;
+pub mod infra;
+pub mod inventory;
+pub mod ssh;
 
-use std::io;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::thread;
+
+use clap::ArgMatches;
+use doapi::{DoManager, DoRequest};
+use doapi::response::{Action, NamedResponse};
+use serde::de::Deserialize;
+
+use client::{self, Endpoint, Reply};
+use config::Config;
+use error::{CliError, CliResult};
 use message::CliMessage;
+use output::{self, OutputFormat};
+use token;
 
-pub fn confirm() -> bool {
-    CliMessage::Confirm.display();
+// How long `--wait` waits for an action by default, in seconds
+pub const WAIT_TIMEOUT: u64 = 300;
+// Seconds between two looks at an action being waited for
+pub const WAIT_INTERVAL: u64 = 2;
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Asks before changing anything, which is `Declined` unless the answer is yes. The prompt goes to
+/// stderr when stdout is for the data. A token read from stdin leaves no answer to read there, so
+/// --noconfirm is required then.
+pub fn confirm(cfg: &Config) -> CliResult {
+    if cfg.token_from_stdin {
+        return fail(cfg, CliError::Validation("the token was read from stdin, which leaves no \
+            answer to read there, use --noconfirm".to_owned()))
+    }
+    confirm_on(cfg.output)
+}
+
+/// `confirm` for commands that run without a `Config`
+pub fn confirm_on(output: OutputFormat) -> CliResult {
+    if output.is_structured() {
+        write!(&mut io::stderr(), "Warning: the action you are about to perform modifies existing \
+            data...\n\tAre you sure you want to continue? [Y/n]: ").ok();
+    } else {
+        CliMessage::Confirm.display();
+    }
     let mut s = String::new();
     io::stdin().read_line(&mut s).ok();
     match s[..].trim() {
-        "Y" | "y" => return true,
-        _         => return false
+        "Y" | "y" => Ok(()),
+        _         => Err(CliError::Declined)
+    }
+}
+
+/// Runs a single request the same way for every command: shows the request with `--verbose`,
+/// stops there with `--nosend`, otherwise sends it once and displays the raw JSON (`--verbose`),
+/// the structured output (`--output`) or `msg` followed by whatever `render` prints. A failed
+/// request is displayed and then returned so it can set the exit code.
+pub fn execute<T, R, F>(m: &ArgMatches, cfg: &Config, req: R, msg: CliMessage, render: F)
+                        -> CliResult
+                        where T: Deserialize + NamedResponse,
+                              R: DoRequest<T> + Display,
+                              F: FnOnce(&Reply<T>) {
+    send_and_show(m, cfg, req, msg, render, true).map(|_| ())
+}
+
+/// Runs a droplet action like `execute`, then with `--wait` keeps looking at it until it's done.
+/// With `--output` only the final state of the action is printed.
+pub fn execute_action<R>(m: &ArgMatches, cfg: &Config, droplet: &str, req: R, msg: CliMessage)
+                         -> CliResult
+                         where R: DoRequest<Action> + Display {
+    let wait = m.is_present("wait");
+    match try!(send_and_show(m, cfg, req, msg, show, !wait)) {
+        Some(ref r) if wait => {
+            match action_id(r) {
+                Ok(id) => wait_for_action(m, cfg, droplet, &id[..]),
+                Err(e) => fail(cfg, e)
+            }
+        },
+        _                   => Ok(())
+    }
+}
+
+/// Polls a droplet action until it's no longer in progress, or `--timeout` seconds have passed.
+/// Whatever state it ends up in is displayed and decides the result: an action that errored or
+/// was still going when time ran out is a failure.
+pub fn wait_for_action(m: &ArgMatches, cfg: &Config, droplet: &str, action: &str) -> CliResult {
+    let timeout = try!(timeout_of(m));
+    if cfg.no_send || m.is_present("nosend") { return Ok(()) }
+    let text = !cfg.output.is_structured();
+    if text { CliMessage::WaitAction(action).display(); }
+    let res = poll(cfg, droplet, action, timeout, text);
+    if let Err(ref e) = res {
+        if text {
+            CliMessage::Failure.display();
+            println!("\n\t{}\n", e);
+        } else {
+            display_structured(&res, cfg);
+        }
+    }
+    let status = action_status(&res);
+    let r = try!(res);
+    let err = outcome(&status[..], droplet, action, timeout);
+    if text {
+        match err {
+            Some(ref e) => {
+                CliMessage::Failure.display();
+                println!("\n\t{}", e);
+            },
+            None        => CliMessage::Success.display()
+        }
+        show(&r);
+    } else {
+        print!("{}", output::render(cfg.output, &r.data));
+        if let Some(ref e) = err { writeln!(&mut io::stderr(), "{}", e).ok(); }
+    }
+    match err {
+        Some(e) => Err(e),
+        None    => Ok(())
+    }
+}
+
+/// Waits for a droplet action like `wait_for_action` without displaying anything, for commands
+/// that chain several actions and report on them as a whole
+pub fn await_action(m: &ArgMatches, cfg: &Config, droplet: &str, action: &str) -> CliResult {
+    let timeout = try!(timeout_of(m));
+    if cfg.no_send || m.is_present("nosend") { return Ok(()) }
+    let res = poll(cfg, droplet, action, timeout, false);
+    let status = action_status(&res);
+    try!(res);
+    match outcome(&status[..], droplet, action, timeout) {
+        Some(e) => Err(e),
+        None    => Ok(())
+    }
+}
+
+/// The ID of the action a request started
+pub fn action_id(r: &Reply<Action>) -> Result<String, CliError> {
+    r.data.find("id").and_then(|i| i.as_u64()).map(|i| i.to_string())
+          .ok_or(CliError::Api("the reply holds no action id".to_owned()))
+}
+
+fn timeout_of(m: &ArgMatches) -> Result<u64, CliError> {
+    match m.value_of("timeout") {
+        Some(t) => t.parse::<u64>().map_err(|_| CliError::Validation(format!("--timeout expects \
+            a number of seconds, not '{}'", t))),
+        None    => Ok(WAIT_TIMEOUT)
+    }
+}
+
+// Looks at the action until it's no longer in progress or `timeout` seconds have passed, with a
+// spinner when `spin`
+fn poll(cfg: &Config, droplet: &str, action: &str, timeout: u64, spin: bool)
+        -> Result<Reply<Action>, CliError> {
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    // In tenths of a second, not counting the time spent on requests
+    let mut waited = 0;
+    let mut res;
+    loop {
+        res = client::send(&domgr.droplet(droplet).action(action), &cfg.api);
+        if action_status(&res) != "in-progress" || waited >= timeout * 10 { break }
+        for _ in 0..WAIT_INTERVAL * 10 {
+            if spin {
+                print!(" {}\x08\x08", SPINNER[(waited % 4) as usize]);
+                io::stdout().flush().ok();
+            }
+            thread::sleep_ms(100);
+            waited += 1;
+        }
+    }
+    if spin && waited > 0 { print!("  \x08\x08"); }
+    res
+}
+
+// What an action that ended up as `status` amounts to, an error unless it completed
+fn outcome(status: &str, droplet: &str, action: &str, timeout: u64) -> Option<CliError> {
+    match status {
+        "completed"   => None,
+        "in-progress" => Some(CliError::Timeout(format!("action {} of droplet {} was still in \
+            progress after {}s", action, droplet, timeout))),
+        _             => Some(CliError::ActionFailed(format!("action {} of droplet {} finished \
+            as {}", action, droplet, status)))
+    }
+}
+
+fn action_status(res: &Result<Reply<Action>, CliError>) -> String {
+    match *res {
+        Ok(ref r) => r.data.find("status").and_then(|s| s.as_string()).unwrap_or("").to_owned(),
+        Err(_)    => String::new()
+    }
+}
+
+// `execute`, handing back the reply unless nothing was sent. Without `print_structured`, nothing
+// is printed on success when `--output` is used, because the caller prints something else.
+fn send_and_show<T, R, F>(m: &ArgMatches, cfg: &Config, req: R, msg: CliMessage, render: F,
+                          print_structured: bool) -> Result<Option<Reply<T>>, CliError>
+                          where T: Deserialize + NamedResponse,
+                                R: DoRequest<T> + Display,
+                                F: FnOnce(&Reply<T>) {
+    let verbose = cfg.verbose || m.is_present("verbose");
+    if verbose {
+        CliMessage::Request(&shown_request(&req, &cfg.auth[..], &cfg.api)[..]).display();
+    }
+    if cfg.no_send || m.is_present("nosend") { return Ok(None) }
+    let res = client::send(&req, &cfg.api);
+    if cfg.output.is_structured() {
+        if print_structured || res.is_err() { display_structured(&res, cfg); }
+        return res.map(Some)
+    }
+    if verbose {
+        CliMessage::JsonResponse.display();
+        match res {
+            Ok(ref r) => {
+                CliMessage::Success.display();
+                println!("\n\t{}\n", r.json);
+            },
+            Err(ref e) => {
+                CliMessage::Failure.display();
+                println!("\n\t{}\n", e);
+            }
+        }
+    }
+    msg.display();
+    match res {
+        Ok(r)  => {
+            CliMessage::Success.display();
+            render(&r);
+            Ok(Some(r))
+        },
+        Err(e) => {
+            CliMessage::Failure.display();
+            println!("\n\t{}\n", e);
+            Err(e)
+        }
+    }
+}
+
+/// A request the way `--verbose` displays it, sent to `api` and without the token, since it
+/// includes the Authorization header
+pub fn shown_request(req: &Display, auth: &str, api: &Endpoint) -> String {
+    req.to_string().replace(auth, &token::redact(auth)[..])
+                   .replace(client::DO_API, &api.base[..])
+                   .replace("\n", "\n\t")
+}
+
+/// Displays an error that ends a command like that of a failed request, then returns it
+pub fn fail(cfg: &Config, e: CliError) -> CliResult {
+    if cfg.output.is_structured() {
+        writeln!(&mut io::stderr(), "{}", e).ok();
+    } else {
+        println!("\n\t{}\n", e);
+    }
+    Err(e)
+}
+
+/// Renders a single response object
+pub fn show<T: Display>(r: &Reply<T>) {
+    println!("\n\t{}\n", &r.value.to_string()[..].replace("\n", "\n\t"));
+}
+
+/// Renders the status and rate limit information of a response
+pub fn show_header<T>(r: &Reply<T>) {
+    println!("\t{}\n", &r.header.to_string()[..].replace("\n", "\n\t"));
+}
+
+/// Renders each object of a collection preceded by `item`, or a note when there are none
+pub fn show_list<T: Display>(r: &Reply<Vec<T>>, item: CliMessage, what: &str) {
+    for i in r.value.iter() {
+        item.display();
+        println!("\t{}\n", &i.to_string()[..].replace("\n", "\n\t"));
+    }
+    if r.value.is_empty() { println!("\tNo {} to display", what); }
+}
+
+/// Prints only the response object in the format chosen with `--output`. Errors go to stderr so
+/// they don't end up in whatever is parsing stdout.
+pub fn display_structured<T>(res: &Result<Reply<T>, CliError>, cfg: &Config) {
+    match *res {
+        Ok(ref r)  => print!("{}", output::render(cfg.output, &r.data)),
+        Err(ref e) => { writeln!(&mut io::stderr(), "{}", e).ok(); }
     }
 }
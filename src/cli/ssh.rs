@@ -0,0 +1,165 @@
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::process::{self, Command};
+use std::thread;
+
+use clap::ArgMatches;
+
+use doapi::DoManager;
+use serde::json::Value;
+
+use client;
+use config::Config;
+use error::{CliError, CliResult};
+use message::CliMessage;
+use output::OutputFormat;
+use resolve;
+use token;
+use cli::{self, WAIT_INTERVAL, WAIT_TIMEOUT};
+
+/// Opens an SSH session on a droplet, or runs a command there, with the system `ssh`. Whatever
+/// `ssh` exits with, docli does too.
+pub fn run(m: &ArgMatches, cfg: &mut Config) -> CliResult {
+    if m.is_present("verbose") { cfg.verbose = true; }
+    if m.is_present("nosend") { cfg.no_send = true; }
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
+    let timeout = match m.value_of("timeout") {
+        Some(t) => match t.parse::<u64>() {
+            Ok(t)  => t,
+            Err(_) => return cli::fail(cfg, CliError::Validation(format!("--timeout expects a \
+                number of seconds, not '{}'", t)))
+        },
+        None    => WAIT_TIMEOUT
+    };
+    let id = try!(resolve::droplet(m, cfg, m.value_of("droplet").unwrap()));
+    if cfg.no_send {
+        let domgr = DoManager::with_token(&cfg.auth[..]);
+        return cli::execute(m, cfg, domgr.droplet(&id[..]), CliMessage::Droplet(&id[..]),
+            cli::show)
+    }
+    let (name, address) = match target(m, cfg, &id[..], timeout) {
+        Ok(t)  => t,
+        Err(e) => return cli::fail(cfg, e)
+    };
+
+    let user = m.value_of("user").map(|u| u.to_owned())
+                .or(cfg.profile.ssh_user.clone())
+                .unwrap_or("root".to_owned());
+    let mut args = vec![];
+    let identity = m.value_of("identity").map(|i| i.to_owned())
+                    .or(cfg.profile.ssh_identity.clone());
+    if let Some(i) = identity {
+        args.push("-i".to_owned());
+        args.push(token::expand_home(&i[..]).to_string_lossy().into_owned());
+    }
+    args.push(format!("{}@{}", user, address));
+    let cmd = m.values_of("cmd").unwrap_or(vec![]);
+    args.extend(cmd.iter().map(|c| c.to_string()));
+    if m.is_present("print") {
+        println!("ssh {}", args.iter().map(|a| quote(a)).collect::<Vec<_>>().join(" "));
+        return Ok(())
+    }
+
+    if cmd.is_empty() && !cfg.output.is_structured() {
+        CliMessage::Ssh(&name[..], &format!("{}@{}", user, address)[..]).display();
+    }
+    match Command::new("ssh").args(&args[..]).status() {
+        Ok(s) if s.success() => Ok(()),
+        // ssh itself fails with 255, anything else comes from the command run
+        Ok(s)                => process::exit(s.code().unwrap_or(255)),
+        Err(e)               => {
+            cli::fail(cfg, CliError::Validation(format!("couldn't run ssh, is it installed? {}",
+                e)))
+        }
+    }
+}
+
+// The name of the droplet and the address to connect to. With --wait, once the droplet is active
+// and port 22 answers, counting only the time spent waiting between two looks.
+fn target(m: &ArgMatches, cfg: &Config, id: &str, timeout: u64)
+          -> Result<(String, String), CliError> {
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    let mut d = try!(client::send(&domgr.droplet(id), &cfg.api)).data;
+    let name = d.find("name").and_then(|n| n.as_string()).unwrap_or(id).to_owned();
+    if !m.is_present("wait") {
+        let address = try!(address_of(m, &d, &name[..]));
+        return Ok((name, address))
+    }
+    let text = !cfg.output.is_structured();
+    if text {
+        CliMessage::WaitSsh(&name[..]).display();
+        io::stdout().flush().ok();
+    }
+    let mut waited = 0;
+    let mut status = status_of(&d);
+    while status != "active" {
+        if waited >= timeout {
+            if text { CliMessage::Failure.display(); }
+            return Err(CliError::Timeout(format!("droplet {} was still {} after {}s", name,
+                status, timeout)))
+        }
+        thread::sleep_ms((WAIT_INTERVAL * 1000) as u32);
+        waited += WAIT_INTERVAL;
+        d = try!(client::send(&domgr.droplet(id), &cfg.api)).data;
+        status = status_of(&d);
+    }
+    let address = try!(address_of(m, &d, &name[..]));
+    while TcpStream::connect((&address[..], 22)).is_err() {
+        if waited >= timeout {
+            if text { CliMessage::Failure.display(); }
+            return Err(CliError::Timeout(format!("port 22 of droplet {} didn't answer within {}s",
+                name, timeout)))
+        }
+        thread::sleep_ms((WAIT_INTERVAL * 1000) as u32);
+        waited += WAIT_INTERVAL;
+    }
+    if text { CliMessage::Success.display(); }
+    Ok((name, address))
+}
+
+fn status_of(d: &Value) -> String {
+    d.find("status").and_then(|s| s.as_string()).unwrap_or("").to_owned()
+}
+
+/// The address of a droplet in one of its networks, the public IPv4 one unless `private` or `ipv6`
+/// ask for another
+pub fn address(d: &Value, private: bool, ipv6: bool) -> Option<String> {
+    let (version, kind) = if ipv6 { ("v6", "public") } else if private { ("v4", "private") }
+                          else { ("v4", "public") };
+    d.find_path(&["networks", version])
+     .and_then(|n| n.as_array())
+     .and_then(|nets| {
+         nets.iter().find(|n| n.find("type").and_then(|t| t.as_string()) == Some(kind))
+     })
+     .and_then(|n| n.find("ip_address"))
+     .and_then(|a| a.as_string())
+     .map(|a| a.to_owned())
+}
+
+/// The network `address` looks in, as in "has no public IPv4 address"
+pub fn network(private: bool, ipv6: bool) -> &'static str {
+    if ipv6 { "IPv6" } else if private { "private" } else { "public IPv4" }
+}
+
+fn address_of(m: &ArgMatches, d: &Value, name: &str) -> Result<String, CliError> {
+    let (private, ipv6) = (m.is_present("private"), m.is_present("ipv6"));
+    address(d, private, ipv6).ok_or_else(|| {
+        let how = if ipv6 {
+            " (see droplet enable-ipv6)"
+        } else if private {
+            " (see droplet enable-private-networking)"
+        } else {
+            ""
+        };
+        CliError::Validation(format!("droplet {} has no {} address{}", name,
+            network(private, ipv6), how))
+    })
+}
+
+// An argument the way a shell reads it back
+fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty() && arg.chars().all(|c| {
+        c.is_alphanumeric() || "@%+=:,./_-".contains(c)
+    });
+    if plain { arg.to_owned() } else { format!("'{}'", arg.replace("'", "'\\''")) }
+}
@@ -1,200 +1,389 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
 
 use clap::ArgMatches;
 
-use doapi::{DoManager, DoRequest};
+use doapi::DoManager;
+use serde::json::Value;
 
+use client;
 use config::Config;
+use error::{CliError, CliResult};
 use message::CliMessage;
+use infra::Op;
+use output::{self, OutputFormat};
+use pubkey::{self, PublicKey};
+use resolve;
+use token;
 use cli;
 
-pub fn run(m: &ArgMatches, cfg: &mut Config) {
+pub fn run(m: &ArgMatches, cfg: &mut Config) -> CliResult {
     if m.is_present("verbose") { cfg.verbose = true; }
     if m.is_present("nosend") { cfg.no_send = true; }
+    if let Ok(o) = value_t!(m.value_of("output"), OutputFormat) { cfg.output = o; }
     let domgr = DoManager::with_token(&cfg.auth[..]);
     match m.subcommand() {
         ("create", Some(m))   => {
             let name = m.value_of("name").unwrap();
             let pub_key = m.value_of("public_key").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.ssh_keys()
-                          .create(name, pub_key)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.ssh_keys().create(name, pub_key).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::CreateSshKey(name, pub_key).display();
-            match domgr.ssh_keys().create(name, pub_key).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", s);
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.ssh_keys().create(name, pub_key),
+                CliMessage::CreateSshKey(name, pub_key), cli::show)
         },
+        ("import", Some(m))   => import(m, cfg),
+        ("sync", Some(m))     => sync(m, cfg),
+        ("generate", Some(m)) => generate(m, cfg),
         ("key", Some(m)) => {
-            let id = m.value_of("id").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.ssh_key(id)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.ssh_key(id).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::SshKey(id).display();
-            match domgr.ssh_key(id).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            let id = try!(resolve::ssh_key(m, cfg, m.value_of("id").unwrap()));
+            let id = &id[..];
+            cli::execute(m, cfg, domgr.ssh_key(id), CliMessage::SshKey(id), cli::show)
         },
         ("rename", Some(m))   => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
+                try!(cli::confirm(cfg));
             }
-            let id = m.value_of("id").unwrap();
+            let id = try!(resolve::ssh_key(m, cfg, m.value_of("id").unwrap()));
+            let id = &id[..];
             let name = m.value_of("name").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.ssh_key(id)
-                          .update(name)
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.ssh_key(id).update(name).retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::UpdateSshKey(name, id).display();
-            match domgr.ssh_key(id).update(name).retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
-            }
+            cli::execute(m, cfg, domgr.ssh_key(id).update(name),
+                CliMessage::UpdateSshKey(name, id), cli::show)
         },
         ("destroy", Some(m))  => {
             if !m.is_present("noconfirm") {
-                if !cli::confirm() { return }
-            }
-            let id = m.value_of("id").unwrap();
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::Request(
-                    &domgr.ssh_key(id)
-                          .destroy()
-                          .to_string()
-                          .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send || m.is_present("nosend") { return }
-            if cfg.verbose || m.is_present("verbose") {
-                CliMessage::JsonResponse.display();
-                match domgr.ssh_key(id).destroy().retrieve_json() {
-                    Ok(s) => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::DestroySshKey(id).display();
-            match domgr.ssh_key(id).destroy().retrieve() {
-                Ok(s) => {
-                    CliMessage::Success.display();
-                    println!("\n\t{}\n", &s.to_string()[..].replace("\n", "\n\t"));
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("\n\t{}\n", e);
-                }
+                try!(cli::confirm(cfg));
             }
+            let id = try!(resolve::ssh_key(m, cfg, m.value_of("id").unwrap()));
+            let id = &id[..];
+            cli::execute(m, cfg, domgr.ssh_key(id).destroy(), CliMessage::DestroySshKey(id),
+                cli::show)
         },
         _                     => {
-            if cfg.verbose {
-                CliMessage::Request(
-                    &domgr.ssh_keys()
-                         .to_string()
-                         .replace("\n", "\n\t")[..]).display();
-            }
-            if cfg.no_send { return }
-            if cfg.verbose {
-                CliMessage::JsonResponse.display();
-                match domgr.ssh_keys().retrieve_json() {
-                    Ok(s)  => {
-                        CliMessage::Success.display();
-                        println!("\n\t{}\n", s);
-                    },
-                    Err(e) => {
-                        CliMessage::Failure.display();
-                        println!("\n\t{}\n", e);
-                    }
-                }
-            }
-            CliMessage::SshKeys.display();
-            match domgr.ssh_keys().retrieve() {
-                Ok(v) => {
-                    CliMessage::Success.display();
-                    for k in v.iter() {
-                        CliMessage::AnonSshKey.display();
-                        println!("\t{}\n", &k.to_string()[..].replace("\n", "\n\t"));
-                    }
-                    if v.is_empty() { println!("\tNo SSH keys to dipslay"); }
-                },
-                Err(e) => {
-                    CliMessage::Failure.display();
-                    println!("{}\n", e);
-                }
+            cli::execute(m, cfg, domgr.ssh_keys(), CliMessage::SshKeys,
+                |r| cli::show_list(r, CliMessage::AnonSshKey, "SSH keys"))
+        }
+    }
+}
+
+// Uploads the keys of files, directories and ssh-agent that the account doesn't have yet, which
+// is told by their fingerprints
+fn import(m: &ArgMatches, cfg: &Config) -> CliResult {
+    let paths = m.values_of("paths").unwrap_or(vec![]);
+    let agent = m.is_present("agent");
+    if paths.is_empty() && !agent {
+        return cli::fail(cfg, CliError::Validation("nothing to import, give public key files or \
+            directories, or --agent".to_owned()))
+    }
+    let mut keys: Vec<PublicKey> = vec![];
+    let mut errors = vec![];
+    let read = paths.iter().map(|p| pubkey::read(p));
+    for res in read.chain(if agent { Some(pubkey::from_agent()) } else { None }.into_iter()) {
+        match res {
+            Ok(k)                         => keys.extend(k.into_iter()),
+            Err(CliError::Validation(e))  => errors.push(e),
+            Err(e)                        => return cli::fail(cfg, e)
+        }
+    }
+    if !errors.is_empty() {
+        return cli::fail(cfg, CliError::Validation(errors.join("\n\t")))
+    }
+    if keys.is_empty() {
+        return cli::fail(cfg, CliError::Validation("ssh-agent holds no keys".to_owned()))
+    }
+    // The same key may well be both in a file and in ssh-agent
+    pubkey::dedup(&mut keys);
+    let name = m.value_of("name");
+    if name.is_some() && keys.len() > 1 {
+        return cli::fail(cfg, CliError::Validation(format!("--name only applies to a single key, \
+            not to {}", keys.len())))
+    }
+
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    if cfg.no_send || m.is_present("nosend") {
+        return cli::execute(m, cfg, domgr.ssh_keys(), CliMessage::SshKeys, |_| ())
+    }
+    let existing = match account_keys(&domgr, cfg) {
+        Ok(k)  => k,
+        Err(e) => return cli::fail(cfg, e)
+    };
+    let mut missing = vec![];
+    let mut present = vec![];
+    for k in keys.iter() {
+        match in_account(&existing, k) {
+            Some(e) => present.push((k, e)),
+            None    => missing.push((k, name.map(|n| n.to_owned()).unwrap_or(k.default_name())))
+        }
+    }
+
+    // What's about to be uploaded goes to stderr with --output, stdout is only for the results
+    let text = !cfg.output.is_structured();
+    if text {
+        CliMessage::ImportKeys.display();
+        preview(&missing, &present, &mut io::stdout());
+    } else {
+        preview(&missing, &present, &mut io::stderr());
+    }
+    if m.is_present("dry-run") {
+        if !text {
+            let list = missing.iter().map(|&(k, ref name)| to_json(k, name)).collect();
+            print!("{}", output::render(cfg.output, &Value::Array(list)));
+        }
+        return Ok(())
+    }
+    if missing.is_empty() { return Ok(()) }
+    if !m.is_present("noconfirm") { try!(cli::confirm(cfg)); }
+    let total = missing.len();
+    let mut failed = vec![];
+    for &(k, ref name) in missing.iter() {
+        let line = k.line();
+        let res = cli::execute(m, cfg, domgr.ssh_keys().create(&name[..], &line[..]),
+            CliMessage::CreateSshKey(&name[..], &line[..]), cli::show);
+        if let Err(e) = res { failed.push(e); }
+    }
+    match failed.len() {
+        0               => Ok(()),
+        n if n == total => Err(failed.remove(0)),
+        n               => cli::fail(cfg, CliError::Partial(n, total))
+    }
+}
+
+fn preview(missing: &[(&PublicKey, String)], present: &[(&PublicKey, &Value)], out: &mut Write) {
+    for &(k, ref name) in missing.iter() {
+        writeln!(out, "\t+ {} ({}) from {}", name, k.fingerprint(), k.source).ok();
+    }
+    for &(k, e) in present.iter() {
+        writeln!(out, "\t= {} ({}), already there as {} (ID {})", k.source, k.fingerprint(),
+            field(e, "name"), id_of(e)).ok();
+    }
+    writeln!(out, "\n\t{} to upload, {} already there", missing.len(), present.len()).ok();
+}
+
+fn to_json(k: &PublicKey, name: &str) -> Value {
+    let mut map = BTreeMap::new();
+    map.insert("name".to_owned(), Value::String(name.to_owned()));
+    map.insert("fingerprint".to_owned(), Value::String(k.fingerprint()));
+    map.insert("public_key".to_owned(), Value::String(k.line()));
+    map.insert("source".to_owned(), Value::String(k.source.clone()));
+    Value::Object(map)
+}
+
+// Makes a new ed25519 key pair, in ~/.ssh/<name> unless --file says otherwise, and uploads its
+// public half. The key pair is left in place when the upload fails.
+fn generate(m: &ArgMatches, cfg: &Config) -> CliResult {
+    let name = m.value_of("name").unwrap();
+    let path = match m.value_of("file") {
+        Some(f) => token::expand_home(f),
+        None    => {
+            let file: String = name.chars().map(|c| {
+                if c.is_alphanumeric() || "-_.@".contains(c) { c } else { '_' }
+            }).collect();
+            token::expand_home("~/.ssh").join(file)
+        }
+    };
+    let public = pubkey::public_of(&path);
+    for p in [&path, &public].iter() {
+        if p.exists() {
+            return cli::fail(cfg, CliError::Validation(format!("{} already exists, give another \
+                --file", p.display())))
+        }
+    }
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    if cfg.no_send || m.is_present("nosend") {
+        // Nothing is written either, the request is shown with a stand-in for the key
+        let line = format!("<the public key to be written to {}>", public.display());
+        return cli::execute(m, cfg, domgr.ssh_keys().create(name, &line[..]),
+            CliMessage::CreateSshKey(name, &line[..]), cli::show)
+    }
+
+    let text = !cfg.output.is_structured();
+    if text { CliMessage::GenerateKey(&path.to_string_lossy()[..]).display(); }
+    let key = match pubkey::generate(&path, name, m.is_present("passphrase")) {
+        Ok(k)  => k,
+        Err(e) => return cli::fail(cfg, e)
+    };
+    if text { println!("\t{}\n", key.fingerprint()); }
+    let line = key.line();
+    let res = cli::execute(m, cfg, domgr.ssh_keys().create(name, &line[..]),
+        CliMessage::CreateSshKey(name, &line[..]), cli::show);
+    if res.is_err() {
+        let hint = format!("The key pair is kept in {}, upload it with docli ssh-keys import {}",
+            path.display(), public.display());
+        if text { println!("\t{}", hint); } else { writeln!(&mut io::stderr(), "{}", hint).ok(); }
+    }
+    res
+}
+
+// Makes the keys of the account match a directory of .pub files or a file of keys: missing keys
+// are uploaded and those named otherwise renamed, after the comment of the key or its file. With
+// --prune the keys found nowhere in the source are destroyed too.
+fn sync(m: &ArgMatches, cfg: &Config) -> CliResult {
+    let source = m.value_of("source").unwrap();
+    let mut keys = match pubkey::read(source) {
+        Ok(k)  => k,
+        Err(e) => return cli::fail(cfg, e)
+    };
+    pubkey::dedup(&mut keys);
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    if cfg.no_send || m.is_present("nosend") {
+        return cli::execute(m, cfg, domgr.ssh_keys(), CliMessage::SshKeys, |_| ())
+    }
+    let existing = match account_keys(&domgr, cfg) {
+        Ok(k)  => k,
+        Err(e) => return cli::fail(cfg, e)
+    };
+
+    let mut changes = vec![];
+    let mut unchanged = 0;
+    for k in keys.iter() {
+        let name = k.default_name();
+        match in_account(&existing, k) {
+            None                               => changes.push(KeyChange {
+                op: Op::Create,
+                name: name,
+                fingerprint: k.fingerprint(),
+                id: None,
+                previous: None,
+                public_key: Some(k.line()),
+            }),
+            Some(e) if field(e, "name") != name => changes.push(KeyChange {
+                op: Op::Update,
+                name: name,
+                fingerprint: k.fingerprint(),
+                id: Some(id_of(e).to_string()),
+                previous: Some(field(e, "name").to_owned()),
+                public_key: None,
+            }),
+            Some(_)                            => unchanged += 1
+        }
+    }
+    let fingerprints: Vec<String> = keys.iter().map(|k| k.fingerprint()).collect();
+    let extra: Vec<&Value> = existing.iter().filter(|e| {
+        !fingerprints.iter().any(|fp| &fp[..] == field(e, "fingerprint"))
+    }).collect();
+    let prune = m.is_present("prune");
+    if prune {
+        changes.extend(extra.iter().map(|e| KeyChange {
+            op: Op::Delete,
+            name: field(e, "name").to_owned(),
+            fingerprint: field(e, "fingerprint").to_owned(),
+            id: Some(id_of(e).to_string()),
+            previous: None,
+            public_key: None,
+        }));
+    }
+
+    let text = !cfg.output.is_structured();
+    let kept = if prune { 0 } else { extra.len() };
+    if text {
+        CliMessage::SyncKeys(source).display();
+        show_changes(&changes, unchanged, kept, &mut io::stdout());
+    } else {
+        show_changes(&changes, unchanged, kept, &mut io::stderr());
+    }
+    if m.is_present("dry-run") || changes.is_empty() {
+        if !text {
+            let list = changes.iter().map(|c| c.to_json()).collect();
+            print!("{}", output::render(cfg.output, &Value::Array(list)));
+        }
+        return Ok(())
+    }
+    if !m.is_present("noconfirm") { try!(cli::confirm(cfg)); }
+
+    // Keys are independent of each other, so a failure doesn't stop the others from being synced
+    let total = changes.len();
+    let mut failed = vec![];
+    for c in changes.iter() {
+        let name = &c.name[..];
+        let id = c.id.as_ref().map(|i| &i[..]).unwrap_or("");
+        let res = match c.op {
+            Op::Create => {
+                let line = c.public_key.as_ref().map(|k| &k[..]).unwrap_or("");
+                cli::execute(m, cfg, domgr.ssh_keys().create(name, line),
+                    CliMessage::CreateSshKey(name, line), cli::show)
+            },
+            Op::Update => {
+                cli::execute(m, cfg, domgr.ssh_key(id).update(name),
+                    CliMessage::UpdateSshKey(name, id), cli::show)
+            },
+            _          => {
+                cli::execute(m, cfg, domgr.ssh_key(id).destroy(), CliMessage::DestroySshKey(id),
+                    cli::show)
             }
+        };
+        if let Err(e) = res { failed.push(e); }
+    }
+    match failed.len() {
+        0               => Ok(()),
+        n if n == total => Err(failed.remove(0)),
+        n               => cli::fail(cfg, CliError::Partial(n, total))
+    }
+}
+
+// A change `sync` makes to the keys of the account
+struct KeyChange {
+    op: Op,
+    /// The name the key ends up with, or had when it's destroyed
+    name: String,
+    fingerprint: String,
+    /// The ID of a key renamed or destroyed
+    id: Option<String>,
+    /// The name a renamed key had
+    previous: Option<String>,
+    /// The key to upload, with its comment
+    public_key: Option<String>,
+}
+
+impl KeyChange {
+    fn line(&self) -> String {
+        match self.op {
+            Op::Create => format!("{} ({})", self.name, self.fingerprint),
+            Op::Update => format!("{} to {} ({})", self.previous.as_ref().unwrap_or(&self.name),
+                self.name, self.fingerprint),
+            _          => format!("{} ({}), ID {}", self.name, self.fingerprint,
+                self.id.as_ref().unwrap_or(&String::new())),
         }
     }
+
+    fn to_json(&self) -> Value {
+        let text = |s: &Option<String>| s.clone().map(Value::String).unwrap_or(Value::Null);
+        let mut map = BTreeMap::new();
+        map.insert("op".to_owned(), Value::String(self.op.name().to_owned()));
+        map.insert("name".to_owned(), Value::String(self.name.clone()));
+        map.insert("fingerprint".to_owned(), Value::String(self.fingerprint.clone()));
+        map.insert("id".to_owned(), text(&self.id));
+        map.insert("previous".to_owned(), text(&self.previous));
+        map.insert("public_key".to_owned(), text(&self.public_key));
+        Value::Object(map)
+    }
+}
+
+fn show_changes(changes: &[KeyChange], unchanged: usize, kept: usize, out: &mut Write) {
+    for c in changes.iter() {
+        writeln!(out, "\t{} {}", c.op.symbol(), c.line()).ok();
+    }
+    let count = |op: Op| changes.iter().filter(|c| c.op == op).count();
+    writeln!(out, "\n\t{} to upload, {} to rename, {} to destroy, {} unchanged", count(Op::Create),
+        count(Op::Update), count(Op::Delete), unchanged).ok();
+    if kept > 0 {
+        writeln!(out, "\t{} key(s) of the account aren't in the source and are kept, see --prune",
+            kept).ok();
+    }
+}
+
+// The keys the account has, as listed by the API
+fn account_keys(domgr: &DoManager, cfg: &Config) -> Result<Vec<Value>, CliError> {
+    let r = try!(client::send(&domgr.ssh_keys(), &cfg.api));
+    Ok(r.data.as_array().map(|a| a.clone()).unwrap_or(vec![]))
+}
+
+// The key of the account with the fingerprint of `k`
+fn in_account<'a>(existing: &'a [Value], k: &PublicKey) -> Option<&'a Value> {
+    let fp = k.fingerprint();
+    existing.iter().find(|e| field(e, "fingerprint") == fp)
+}
+
+fn field<'a>(e: &'a Value, name: &str) -> &'a str {
+    e.find(name).and_then(|f| f.as_string()).unwrap_or("")
+}
+
+fn id_of(e: &Value) -> u64 {
+    e.find("id").and_then(|i| i.as_u64()).unwrap_or(0)
 }
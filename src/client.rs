@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use hyper::{self, Url};
+use hyper::client::{self, Response};
+use hyper::header::{ContentType, Authorization};
+use hyper::method::Method;
+use hyper::net::Fresh;
+
+use serde::de::Deserialize;
+use serde::json::{self, Value};
+
+use doapi::DoRequest;
+use doapi::request::BaseRequest;
+use doapi::response::{HeaderOnly, NamedResponse};
+
+use cassette::{Cassette, Interaction};
+use error::CliError;
+use proxy::{Proxy, Tunnel};
+
+/// The API root doapi builds every request URL against
+pub const DO_API: &'static str = "https://api.digitalocean.com/v2";
+
+/// Where requests are actually sent, DigitalOcean unless `--api-url` (or the profile) points
+/// somewhere else such as a local mock server, or nowhere at all when replaying a cassette
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    pub base: String,
+    /// Records (`--record`) or replays (`--replay`) every exchange
+    pub cassette: Option<Arc<Cassette>>,
+}
+
+impl Endpoint {
+    pub fn new(base: &str) -> Endpoint {
+        Endpoint {
+            base: base.trim_right_matches('/').to_owned(),
+            cassette: None,
+        }
+    }
+
+    /// The part of a URL below the API root, which is how cassettes identify requests
+    pub fn path(&self, url: &str) -> String {
+        if url.starts_with(&self.base[..]) {
+            url[self.base.len()..].to_owned()
+        } else {
+            url.to_owned()
+        }
+    }
+
+    /// Points a URL built for DigitalOcean at this endpoint, anything else is left alone
+    pub fn rebase(&self, url: &str) -> String {
+        if url.starts_with(DO_API) {
+            format!("{}{}", self.base, &url[DO_API.len()..])
+        } else {
+            url.to_owned()
+        }
+    }
+}
+
+impl Default for Endpoint {
+    fn default() -> Endpoint {
+        Endpoint::new(DO_API)
+    }
+}
+
+/// The result of sending a request to DigitalOcean exactly once
+pub struct Reply<T> {
+    /// The raw JSON body(s) exactly as received, one per page
+    pub json: String,
+    /// The status and rate limiting information from the (last) response
+    pub header: HeaderOnly,
+    /// The named object pulled out of the body (i.e. the contents of "droplets")
+    pub data: Value,
+    /// `data` deserialized into the type the request asks for
+    pub value: T,
+}
+
+/// Sends the request a single time to `api`, following pagination links for collections, and
+/// returns both the raw JSON and the typed response built from that same JSON
+pub fn send<T, R>(req: &R, api: &Endpoint) -> Result<Reply<T>, CliError>
+                  where T: Deserialize + NamedResponse,
+                        R: DoRequest<T> {
+    let name = <T as NamedResponse>::name().into_owned();
+    let url = api.rebase(req.url());
+    let (mut header, mut body) = try!(perform(api, req.method(), &url[..], req.auth(), req.body()));
+    try!(check_status(&header, &body[..]));
+    let mut json = body.clone();
+    let data = if name == "header" {
+        header_value(&header)
+    } else {
+        let mut data = try!(extract(&body[..], &name[..]));
+        // Collections are paged, keep asking for the next page until there isn't one
+        while let Some(next) = next_page(&body[..]) {
+            let (h, b) = try!(perform(api, Method::Get, &api.rebase(&next[..])[..], req.auth(), None));
+            try!(check_status(&h, &b[..]));
+            let page = try!(extract(&b[..], &name[..]));
+            if let (&mut Value::Array(ref mut all), Value::Array(more)) = (&mut data, page) {
+                all.extend(more.into_iter());
+            }
+            json.push_str("\n");
+            json.push_str(&b[..]);
+            header = h;
+            body = b;
+        }
+        data
+    };
+    let value = match json::from_value::<T>(data.clone()) {
+        Ok(v)  => v,
+        Err(e) => return Err(CliError::Api(e.to_string()))
+    };
+    Ok(Reply {
+        json: json,
+        header: header,
+        data: data,
+        value: value,
+    })
+}
+
+// Answers from the cassette when replaying, otherwise goes over the network and records the
+// exchange when asked to
+fn perform(api: &Endpoint, method: Method, url: &str, auth: &str, body: Option<String>)
+           -> Result<(HeaderOnly, String), CliError> {
+    let cassette = match api.cassette {
+        Some(ref c) => c,
+        None        => return exchange(method, url, auth, body)
+    };
+    let path = api.path(url);
+    if cassette.replaying() {
+        return cassette.play(&method, &path[..], body.as_ref())
+    }
+    let (header, res) = try!(exchange(method.clone(), url, auth, body.clone()));
+    try!(cassette.add(Interaction::new(&method, &path[..], body, &header, &res[..])));
+    Ok((header, res))
+}
+
+/// Fetches a page that has nothing to do with DigitalOcean, such as a service telling the
+/// public address of the host. The token is never sent along.
+pub fn fetch(url: &str) -> Result<String, CliError> {
+    let fresh_req = try!(request(Method::Get, url));
+    let mut response = match fresh_req.start().and_then(|r| r.send()) {
+        Ok(resp) => resp,
+        Err(e)   => return Err(CliError::Network(format!("{}: {}", url, e)))
+    };
+    let header = header_from(&response);
+    let mut s = String::new();
+    if let Err(e) = response.read_to_string(&mut s) {
+        return Err(CliError::Network(format!("{}: {}", url, e)))
+    }
+    if !header.status.starts_with('2') {
+        return Err(CliError::Network(format!("{} answered {}", url, header.status)))
+    }
+    Ok(s)
+}
+
+// A request for `url`, through a proxy when the environment sets one
+fn request(method: Method, url: &str) -> Result<client::Request<Fresh>, CliError> {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(e)  => return Err(CliError::Validation(hyper::Error::Uri(e).to_string()))
+    };
+    let proxy = Proxy::from_env(&url.scheme[..], url.domain().unwrap_or(""));
+    let fresh_req = match proxy {
+        Some(p) => client::Request::with_connector(method, url, &Tunnel(p)),
+        None    => client::Request::new(method, url)
+    };
+    fresh_req.map_err(|e| CliError::Network(e.to_string()))
+}
+
+// Performs one HTTP exchange, honoring the request method and body, through a proxy when the
+// environment sets one
+fn exchange(method: Method, url: &str, auth: &str, body: Option<String>)
+            -> Result<(HeaderOnly, String), CliError> {
+    let mut fresh_req = try!(request(method, url));
+    fresh_req.headers_mut().set(ContentType("application/json".parse().unwrap()));
+    fresh_req.headers_mut().set(Authorization(format!("Bearer {}", auth)));
+    let mut streaming_req = match fresh_req.start() {
+        Ok(req) => req,
+        Err(e)  => return Err(CliError::Network(e.to_string()))
+    };
+    if let Some(ref b) = body {
+        if let Err(e) = streaming_req.write_all(b.as_bytes()) {
+            return Err(CliError::Network(e.to_string()))
+        }
+    }
+    let mut response = match streaming_req.send() {
+        Ok(resp) => resp,
+        Err(e)   => return Err(CliError::Network(e.to_string()))
+    };
+    let header = header_from(&response);
+    let mut s = String::new();
+    if let Err(e) = response.read_to_string(&mut s) {
+        return Err(CliError::Network(e.to_string()))
+    }
+    Ok((header, s))
+}
+
+fn header_from(r: &Response) -> HeaderOnly {
+    let raw_num = |name: &str| -> f64 {
+        r.headers.get_raw(name)
+                 .and_then(|v| v.get(0))
+                 .and_then(|v| String::from_utf8_lossy(&v[..]).parse::<f64>().ok())
+                 .unwrap_or(0.0)
+    };
+    let raw_status = r.status_raw();
+    HeaderOnly {
+        content_type: r.headers.get::<ContentType>()
+                               .map(|c| c.to_string())
+                               .unwrap_or(String::new()),
+        status: format!("{} {}", raw_status.0, raw_status.1),
+        ratelimit_limit: raw_num("ratelimit-limit"),
+        ratelimit_remaining: raw_num("ratelimit-remaining"),
+        ratelimit_reset: raw_num("ratelimit-reset"),
+    }
+}
+
+// The same fields `HeaderOnly` deserializes from, so requests that only return a header (such as
+// deletes) can be handled like any other response
+fn header_value(h: &HeaderOnly) -> Value {
+    let mut map = BTreeMap::new();
+    map.insert("content-type".to_owned(), Value::String(h.content_type.clone()));
+    map.insert("status".to_owned(), Value::String(h.status.clone()));
+    map.insert("ratelimit-limit".to_owned(), Value::F64(h.ratelimit_limit));
+    map.insert("ratelimit-remaining".to_owned(), Value::F64(h.ratelimit_remaining));
+    map.insert("ratelimit-reset".to_owned(), Value::F64(h.ratelimit_reset));
+    Value::Object(map)
+}
+
+// Turns any non 2xx response into the matching error, using the message DigitalOcean sent
+fn check_status(header: &HeaderOnly, body: &str) -> Result<(), CliError> {
+    let code = header.status.split(' ').next().and_then(|c| c.parse::<u16>().ok()).unwrap_or(0);
+    if code >= 200 && code < 300 { return Ok(()) }
+    let msg = json::from_str::<Value>(body).ok()
+        .and_then(|v| v.find("message").and_then(|m| m.as_string()).map(|m| m.to_owned()))
+        .unwrap_or(header.status.clone());
+    Err(CliError::from_status(code, format!("DigitalOcean Error Response: {}", msg)))
+}
+
+// Pulls the object named `name` (i.e. "droplets" or "ssh_key") out of a raw DigitalOcean
+// response body
+fn extract(body: &str, name: &str) -> Result<Value, CliError> {
+    if body.trim().is_empty() { return Err(CliError::Api("No response body".to_owned())) }
+    let v = match json::from_str::<Value>(body) {
+        Ok(v)  => v,
+        Err(e) => return Err(CliError::Api(e.to_string()))
+    };
+    match v.find(name) {
+        Some(obj) => Ok(obj.clone()),
+        None      => Ok(v)
+    }
+}
+
+fn next_page(body: &str) -> Option<String> {
+    json::from_str::<Value>(body).ok()
+        .and_then(|v| v.find_path(&["links", "pages", "next"])
+                       .and_then(|n| n.as_string())
+                       .map(|n| n.to_owned()))
+}
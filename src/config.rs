@@ -1,5 +1,248 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use toml::{self, Parser, Value};
+
+use client::Endpoint;
+use error::CliError;
+use output::OutputFormat;
+use token;
+
 pub struct Config {
     pub verbose: bool,
     pub no_send: bool,
-    pub auth: String
+    /// `--id`, droplets, images and keys are never looked up by name
+    pub literal_ids: bool,
+    pub output: OutputFormat,
+    pub auth: String,
+    /// `--token-file -`, stdin is used up by the time anything is confirmed
+    pub token_from_stdin: bool,
+    pub profile: Profile,
+    pub api: Endpoint,
+}
+
+/// A named set of defaults, typically one per DigitalOcean account or team
+#[derive(Clone, Default)]
+pub struct Profile {
+    pub name: String,
+    pub token: Option<String>,
+    /// A file holding the token, only readable by its owner
+    pub token_file: Option<String>,
+    /// A command printing the token, such as a password manager's CLI
+    pub token_command: Option<String>,
+    pub region: Option<String>,
+    pub size: Option<String>,
+    pub ssh_keys: Vec<String>,
+    pub output: Option<OutputFormat>,
+    /// Where to send requests instead of DigitalOcean
+    pub api_url: Option<String>,
+    /// Who `docli ssh` logs in as
+    pub ssh_user: Option<String>,
+    /// The private key `docli ssh` logs in with
+    pub ssh_identity: Option<String>,
+}
+
+impl Profile {
+    fn from_toml(name: &str, t: &toml::Table) -> Result<Profile, CliError> {
+        let string = |k: &str| t.get(k).and_then(|v| v.as_str()).map(|s| s.to_owned());
+        Ok(Profile {
+            name: name.to_owned(),
+            token: string("token"),
+            token_file: string("token_file"),
+            token_command: string("token_command"),
+            region: string("region"),
+            size: string("size"),
+            ssh_keys: t.get("ssh_keys")
+                       .and_then(|v| v.as_slice())
+                       .map(|keys| keys.iter()
+                                       .filter_map(|k| k.as_str().map(|s| s.to_owned()))
+                                       .collect())
+                       .unwrap_or(vec![]),
+            api_url: string("api_url"),
+            ssh_user: string("ssh_user"),
+            ssh_identity: string("ssh_identity"),
+            output: match string("output") {
+                Some(o) => match o.parse() {
+                    Ok(o)  => Some(o),
+                    Err(e) => return Err(CliError::Validation(
+                        format!("profile '{}' has an invalid output format, {}", name, e)))
+                },
+                None    => None
+            },
+        })
+    }
+
+    /// Returns `true` if the profile has any way of providing a token
+    pub fn has_token(&self) -> bool {
+        self.token.is_some() || self.token_file.is_some() || self.token_command.is_some()
+    }
+
+    /// The token of this profile, running its credential helper or reading its token file when
+    /// it has one
+    pub fn resolve_token(&self) -> Result<Option<String>, CliError> {
+        if let Some(ref cmd) = self.token_command {
+            return token::from_command(cmd).map(Some)
+        }
+        if let Some(ref path) = self.token_file {
+            return token::from_file(path).map(Some)
+        }
+        Ok(self.token.clone())
+    }
+
+    fn to_toml(&self) -> Value {
+        let mut t = BTreeMap::new();
+        if let Some(ref tok) = self.token { t.insert("token".to_owned(), Value::String(tok.clone())); }
+        if let Some(ref f) = self.token_file { t.insert("token_file".to_owned(), Value::String(f.clone())); }
+        if let Some(ref c) = self.token_command { t.insert("token_command".to_owned(), Value::String(c.clone())); }
+        if let Some(ref r) = self.region { t.insert("region".to_owned(), Value::String(r.clone())); }
+        if let Some(ref s) = self.size { t.insert("size".to_owned(), Value::String(s.clone())); }
+        if !self.ssh_keys.is_empty() {
+            t.insert("ssh_keys".to_owned(),
+                     Value::Array(self.ssh_keys.iter().map(|k| Value::String(k.clone())).collect()));
+        }
+        if let Some(o) = self.output { t.insert("output".to_owned(), Value::String(o.to_string())); }
+        if let Some(ref u) = self.api_url { t.insert("api_url".to_owned(), Value::String(u.clone())); }
+        if let Some(ref u) = self.ssh_user {
+            t.insert("ssh_user".to_owned(), Value::String(u.clone()));
+        }
+        if let Some(ref i) = self.ssh_identity {
+            t.insert("ssh_identity".to_owned(), Value::String(i.clone()));
+        }
+        Value::Table(t)
+    }
+}
+
+/// The contents of the configuration file (`~/.config/docli/config.toml`)
+///
+/// ```toml
+/// profile = "staging"
+///
+/// [profiles.staging]
+/// token = "..."                  # or one of
+/// token_file = "~/.secrets/do"    #   a file only readable by its owner
+/// token_command = "pass show do"  #   a credential helper printing the token
+/// region = "nyc3"
+/// size = "512mb"
+/// ssh_keys = ["1234", "ab:cd:..."]
+/// output = "table"
+/// api_url = "http://localhost:8080/v2"
+/// ssh_user = "deploy"
+/// ssh_identity = "~/.ssh/do_ed25519"
+/// ```
+#[derive(Default)]
+pub struct ConfigFile {
+    /// The profile used when neither `--profile` nor `DOCLI_PROFILE` are given
+    pub current: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// `$DOCLI_CONFIG` if set, otherwise `$XDG_CONFIG_HOME/docli/config.toml` falling back to
+    /// `~/.config/docli/config.toml`
+    pub fn path() -> PathBuf {
+        if let Ok(p) = env::var("DOCLI_CONFIG") {
+            return PathBuf::from(p)
+        }
+        let mut p = match env::var("XDG_CONFIG_HOME") {
+            Ok(ref d) if !d.is_empty() => PathBuf::from(d),
+            _ => {
+                let mut home = env::home_dir().unwrap_or(PathBuf::from("."));
+                home.push(".config");
+                home
+            }
+        };
+        p.push("docli");
+        p.push("config.toml");
+        p
+    }
+
+    /// Reads the configuration file, a missing file is the same as an empty one
+    pub fn load() -> Result<ConfigFile, CliError> {
+        let path = ConfigFile::path();
+        let mut s = String::new();
+        match File::open(&path) {
+            Ok(mut f) => {
+                if let Err(e) = f.read_to_string(&mut s) {
+                    return Err(CliError::Validation(format!("{}: {}", path.display(), e)))
+                }
+            },
+            Err(_) => return Ok(ConfigFile::default())
+        }
+        let mut parser = Parser::new(&s[..]);
+        let table = match parser.parse() {
+            Some(t) => t,
+            None    => {
+                let e = &parser.errors[0];
+                let (line, col) = parser.to_linecol(e.lo);
+                return Err(CliError::Validation(format!("{}:{}:{}: {}",
+                    path.display(), line + 1, col + 1, e.desc)))
+            }
+        };
+        let mut cfg = ConfigFile::default();
+        cfg.current = table.get("profile").and_then(|v| v.as_str()).map(|s| s.to_owned());
+        if let Some(profiles) = table.get("profiles").and_then(|v| v.as_table()) {
+            for (name, p) in profiles.iter() {
+                if let Some(t) = p.as_table() {
+                    cfg.profiles.insert(name.clone(), try!(Profile::from_toml(name, t)));
+                }
+            }
+        }
+        Ok(cfg)
+    }
+
+    /// Writes the configuration file, readable only by the current user since it holds tokens
+    pub fn save(&self) -> Result<(), CliError> {
+        let path = ConfigFile::path();
+        let err = |e: ::std::io::Error| CliError::Validation(format!("{}: {}", path.display(), e));
+        if let Some(dir) = path.parent() {
+            try!(fs::create_dir_all(dir).map_err(&err));
+        }
+        let mut table = BTreeMap::new();
+        if let Some(ref c) = self.current {
+            table.insert("profile".to_owned(), Value::String(c.clone()));
+        }
+        let mut profiles = BTreeMap::new();
+        for (name, p) in self.profiles.iter() {
+            profiles.insert(name.clone(), p.to_toml());
+        }
+        table.insert("profiles".to_owned(), Value::Table(profiles));
+        let mut f = try!(File::create(&path).map_err(&err));
+        try!(restrict(&path).map_err(&err));
+        try!(write!(f, "{}", Value::Table(table)).map_err(&err));
+        Ok(())
+    }
+
+    /// Picks the profile to use: `--profile`, then `DOCLI_PROFILE`, then the one set with
+    /// `docli config switch`. Asking for a profile that doesn't exist is an error, having none at
+    /// all is not.
+    pub fn select(&self, flag: Option<&str>) -> Result<Option<Profile>, CliError> {
+        let env_profile = env::var("DOCLI_PROFILE").ok();
+        let name = match (flag, env_profile) {
+            (Some(n), _)    => n.to_owned(),
+            (None, Some(n)) => n,
+            (None, None)    => match self.current {
+                Some(ref n) => n.clone(),
+                None        => return Ok(None)
+            }
+        };
+        match self.profiles.get(&name) {
+            Some(p) => Ok(Some(p.clone())),
+            None    => Err(CliError::NotFound(format!("no profile named '{}' in {}",
+                name, ConfigFile::path().display())))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn restrict(path: &PathBuf) -> ::std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict(_: &PathBuf) -> ::std::io::Result<()> {
+    Ok(())
 }
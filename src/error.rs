@@ -0,0 +1,98 @@
+use std::error::Error;
+use std::fmt;
+
+/// Everything that can go wrong while running a command. Each kind of failure exits with its
+/// own code so scripts can react to what actually happened
+#[derive(Debug)]
+pub enum CliError {
+    /// DigitalOcean rejected the request for some other reason, or the response was unusable
+    Api(String),
+    /// No token was found, or DigitalOcean refused it (401 / 403)
+    Auth(String),
+    /// The resource doesn't exist (404)
+    NotFound(String),
+    /// The input was invalid, either locally or according to DigitalOcean (400 / 422)
+    Validation(String),
+    /// Too many requests were made in the current rate limit window (429)
+    RateLimited(String),
+    /// DigitalOcean couldn't be reached
+    Network(String),
+    /// The user answered "no" when asked to confirm
+    Declined,
+    /// Some, but not all, operations of a bulk command failed
+    Partial(usize, usize),
+    /// An action was still in progress when `--wait` gave up on it
+    Timeout(String),
+    /// An action finished with the "errored" status
+    ActionFailed(String),
+    /// The account differs from a manifest in this many places
+    Drift(usize),
+}
+
+impl CliError {
+    /// Builds the error matching an HTTP status and the message DigitalOcean sent with it
+    pub fn from_status(status: u16, msg: String) -> CliError {
+        match status {
+            401 | 403 => CliError::Auth(msg),
+            404       => CliError::NotFound(msg),
+            400 | 422 => CliError::Validation(msg),
+            429       => CliError::RateLimited(msg),
+            _         => CliError::Api(msg),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            CliError::Api(_)         => 2,
+            CliError::Auth(_)        => 3,
+            CliError::NotFound(_)    => 4,
+            CliError::Validation(_)  => 5,
+            CliError::RateLimited(_) => 6,
+            CliError::Network(_)     => 7,
+            CliError::Declined       => 8,
+            CliError::Partial(..)    => 9,
+            CliError::Timeout(_)     => 10,
+            CliError::ActionFailed(_) => 11,
+            CliError::Drift(_)       => 12,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::Api(ref s)         => write!(f, "{}", s),
+            CliError::Auth(ref s)        => write!(f, "Authentication failed: {}", s),
+            CliError::NotFound(ref s)    => write!(f, "Not found: {}", s),
+            CliError::Validation(ref s)  => write!(f, "Invalid request: {}", s),
+            CliError::RateLimited(ref s) => write!(f, "Rate limit exceeded: {}", s),
+            CliError::Network(ref s)     => write!(f, "Unable to reach DigitalOcean: {}", s),
+            CliError::Declined           => write!(f, "Cancelled by user"),
+            CliError::Partial(failed, total) => write!(f, "{} of {} operations failed", failed, total),
+            CliError::Timeout(ref s)     => write!(f, "Timed out: {}", s),
+            CliError::ActionFailed(ref s) => write!(f, "Action failed: {}", s),
+            CliError::Drift(n)           => write!(f, "The account has drifted from the manifest in \
+                                                       {} place(s)", n),
+        }
+    }
+}
+
+impl Error for CliError {
+    fn description(&self) -> &str {
+        match *self {
+            CliError::Api(_)         => "DigitalOcean API error",
+            CliError::Auth(_)        => "authentication failed",
+            CliError::NotFound(_)    => "resource not found",
+            CliError::Validation(_)  => "invalid request",
+            CliError::RateLimited(_) => "rate limit exceeded",
+            CliError::Network(_)     => "network error",
+            CliError::Declined       => "cancelled by user",
+            CliError::Partial(..)    => "partial failure",
+            CliError::Timeout(_)     => "timed out",
+            CliError::ActionFailed(_) => "action failed",
+            CliError::Drift(_)       => "drift detected",
+        }
+    }
+}
+
+pub type CliResult = Result<(), CliError>;
@@ -0,0 +1,684 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde::json::{self, Value};
+
+use doapi::request::{DnsRecord, Droplet};
+
+use config::Profile;
+use error::CliError;
+use spec;
+use zone;
+
+// The sections of a manifest, in the order their resources are created
+const SECTIONS: [&'static str; 4] = ["ssh_keys", "domains", "records", "droplets"];
+const RECORD_KEYS: [&'static str; 6] = ["type", "name", "data", "priority", "port", "weight"];
+
+/// Something a manifest says should exist
+pub enum Resource {
+    SshKey { name: String, public_key: String },
+    Domain { name: String, ip: String },
+    Record { domain: String, record: DnsRecord },
+    Droplet(Droplet),
+}
+
+impl Resource {
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Resource::SshKey { .. } => "ssh_key",
+            Resource::Domain { .. } => "domain",
+            Resource::Record { .. } => "record",
+            Resource::Droplet(_)    => "droplet",
+        }
+    }
+
+    /// What identifies the resource in a plan and in the state file, i.e. `droplet/web-1` or
+    /// `record/example.com/A/www/1.2.3.4`
+    pub fn address(&self) -> String {
+        match *self {
+            Resource::SshKey { ref name, .. } => format!("ssh_key/{}", name),
+            Resource::Domain { ref name, .. } => format!("domain/{}", name),
+            Resource::Record { ref domain, ref record } => {
+                format!("record/{}/{}/{}/{}", domain, opt(&record.rec_type), opt(&record.name),
+                    opt(&record.data))
+            },
+            Resource::Droplet(ref d)          => format!("droplet/{}", d.name),
+        }
+    }
+
+    /// Whether an object listed by the API is this resource, going by names rather than IDs
+    pub fn is(&self, v: &Value) -> bool {
+        match *self {
+            Resource::SshKey { ref name, .. } |
+            Resource::Domain { ref name, .. } => text(v, "name") == &name[..],
+            Resource::Record { ref record, .. } => {
+                text(v, "type") == opt(&record.rec_type)
+                    && text(v, "name") == opt(&record.name)
+                    && text(v, "data") == opt(&record.data)
+            },
+            Resource::Droplet(ref d)          => text(v, "name") == &d.name[..],
+        }
+    }
+
+    // How the existing object `v` differs from the resource, and whether that takes replacing it
+    fn differences(&self, v: &Value) -> (Vec<String>, bool) {
+        match *self {
+            Resource::SshKey { ref public_key, .. } => {
+                if key_material(public_key) != key_material(text(v, "public_key")) {
+                    (vec!["the public key differs".to_owned()], true)
+                } else {
+                    (vec![], false)
+                }
+            },
+            Resource::Domain { .. } => (vec![], false),
+            Resource::Record { ref record, .. } => {
+                let mut diffs = vec![];
+                for &(k, want) in [("priority", record.priority), ("port", record.port),
+                                   ("weight", record.weight)].iter() {
+                    let have = v.find(k).and_then(|n| n.as_u64());
+                    if have != want { diffs.push(changed(k, number(have), number(want))); }
+                }
+                (diffs, false)
+            },
+            Resource::Droplet(ref d) => {
+                let mut diffs = vec![];
+                let region = v.find_path(&["region", "slug"]).and_then(|r| r.as_string())
+                              .unwrap_or("");
+                if region != d.region {
+                    diffs.push(changed("region", region.to_owned(), d.region.clone()));
+                }
+                if !same_image(&d.image[..], v) {
+                    diffs.push(changed("image", image_of(v), d.image.clone()));
+                }
+                let replace = !diffs.is_empty();
+                if text(v, "size_slug") != d.size {
+                    diffs.push(changed("size", text(v, "size_slug").to_owned(), d.size.clone()));
+                    if !replace && text(v, "status") != "off" {
+                        diffs.push("the droplet is powered off to be resized, and rebooted"
+                                       .to_owned());
+                    }
+                }
+                (diffs, replace)
+            }
+        }
+    }
+}
+
+fn opt(s: &Option<String>) -> &str {
+    s.as_ref().map(|s| &s[..]).unwrap_or("")
+}
+
+fn text<'a>(v: &'a Value, key: &str) -> &'a str {
+    v.find(key).and_then(|s| s.as_string()).unwrap_or("")
+}
+
+fn number(n: Option<u64>) -> String {
+    n.map(|n| n.to_string()).unwrap_or("none".to_owned())
+}
+
+fn changed(what: &str, old: String, new: String) -> String {
+    format!("{}: {} -> {}", what, old, new)
+}
+
+// The type and key of a public key, without the comment
+fn key_material(key: &str) -> Vec<&str> {
+    key.split_whitespace().take(2).collect()
+}
+
+/// The ID of an object listed by the API. Domains are known by their name.
+pub fn id_of(kind: &str, v: &Value) -> String {
+    if kind == "domain" { return text(v, "name").to_owned() }
+    match v.find("id") {
+        Some(&Value::String(ref id)) => id.clone(),
+        Some(id)                     => {
+            id.as_u64().map(|id| id.to_string()).unwrap_or(String::new())
+        },
+        None                         => String::new()
+    }
+}
+
+/// Reads the resources described by a manifest, a TOML, YAML or JSON file made of `ssh_keys`,
+/// `domains`, `records` and `droplets` lists. Droplets are described like in a `droplets create`
+/// spec, see `spec::droplets`. Every problem found is reported at once.
+pub fn load(path: &str, profile: &Profile) -> Result<Vec<Resource>, CliError> {
+    let v = try!(spec::load(path));
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    let mut errors = vec![];
+    match v.as_object() {
+        Some(m) => {
+            for k in m.keys().filter(|k| !SECTIONS.contains(&&k[..])) {
+                errors.push(format!("unknown section '{}', expected one of {}", k,
+                    SECTIONS.join(", ")));
+            }
+        },
+        None    => return Err(CliError::Validation(format!("{}: expected a table of sections",
+            path)))
+    }
+    let mut resources = vec![];
+    for (i, k) in list(&v, "ssh_keys", &mut errors).iter().enumerate() {
+        let mut item = Item::new(k, format!("ssh_keys[{}]", i), &["name", "public_key",
+            "public_key_file"], &mut errors);
+        let name = item.string("name", true);
+        let mut public_key = item.string("public_key", false);
+        if let Some(file) = item.string("public_key_file", false) {
+            let mut s = String::new();
+            match File::open(dir.join(&file)).and_then(|mut f| f.read_to_string(&mut s)) {
+                Ok(_)  => public_key = Some(s.trim().to_owned()),
+                Err(e) => item.error(format!("public_key_file {}: {}", file, e))
+            }
+        }
+        match public_key {
+            Some(ref k) if key_material(k).len() < 2 => {
+                item.error("public_key isn't an OpenSSH public key".to_owned())
+            },
+            None => item.error("either public_key or public_key_file is needed".to_owned()),
+            _    => {}
+        }
+        if let (Some(name), Some(public_key)) = (name, public_key) {
+            if item.ok() {
+                resources.push(Resource::SshKey { name: name, public_key: public_key });
+            }
+        }
+    }
+    for (i, d) in list(&v, "domains", &mut errors).iter().enumerate() {
+        let mut item = Item::new(d, format!("domains[{}]", i), &["name", "ip"], &mut errors);
+        let (name, ip) = (item.string("name", true), item.string("ip", true));
+        if let (Some(name), Some(ip)) = (name, ip) {
+            if item.ok() { resources.push(Resource::Domain { name: name, ip: ip }); }
+        }
+    }
+    for (i, r) in list(&v, "records", &mut errors).iter().enumerate() {
+        let mut keys = RECORD_KEYS.to_vec();
+        keys.push("domain");
+        let mut item = Item::new(r, format!("records[{}]", i), &keys, &mut errors);
+        let domain = item.string("domain", true);
+        let record = record_def(&mut item);
+        if let Some(domain) = domain {
+            if item.ok() { resources.push(Resource::Record { domain: domain, record: record }); }
+        }
+    }
+    for d in spec::droplet_list(&v, profile, dir, &mut errors) {
+        resources.push(Resource::Droplet(d));
+    }
+    {
+        let mut seen = BTreeSet::new();
+        for r in resources.iter() {
+            let address = r.address();
+            if !seen.insert(address.clone()) {
+                errors.push(format!("{} is described more than once", address));
+            }
+        }
+    }
+    if errors.is_empty() { Ok(resources) } else { Err(problems(path, errors)) }
+}
+
+/// Reads the `records` list of a TOML, YAML or JSON file describing the records of a single
+/// domain, written like those of a manifest without their `domain`
+pub fn load_records(path: &str) -> Result<Vec<DnsRecord>, CliError> {
+    let v = try!(spec::load(path));
+    let mut errors = vec![];
+    match v.as_object() {
+        Some(m) => {
+            for k in m.keys().filter(|k| &k[..] != "records") {
+                errors.push(format!("unknown section '{}', expected records", k));
+            }
+        },
+        None    => return Err(CliError::Validation(format!("{}: expected a records list", path)))
+    }
+    let mut records: Vec<DnsRecord> = vec![];
+    for (i, r) in list(&v, "records", &mut errors).iter().enumerate() {
+        let mut item = Item::new(r, format!("records[{}]", i), &RECORD_KEYS, &mut errors);
+        let record = record_def(&mut item);
+        if !item.ok() { continue }
+        if records.iter().any(|r| zone::same_key(r, &record)) {
+            item.error(format!("{} is listed more than once", zone::line(&record)));
+        } else {
+            records.push(record);
+        }
+    }
+    if errors.is_empty() { Ok(records) } else { Err(problems(path, errors)) }
+}
+
+fn problems(path: &str, errors: Vec<String>) -> CliError {
+    match spec::problems(errors) {
+        CliError::Validation(s) => CliError::Validation(format!("{}: {}", path, s)),
+        e                       => e
+    }
+}
+
+// The record an entry of a `records` list describes. Its name defaults to the domain itself.
+fn record_def(item: &mut Item) -> DnsRecord {
+    let rec_type = item.string("type", true).map(|t| t.to_uppercase());
+    if let Some(ref t) = rec_type {
+        if !zone::TYPES.contains(&&t[..]) {
+            item.error(format!("type {} isn't one of {}", t, zone::TYPES.join(", ")));
+        }
+    }
+    DnsRecord {
+        rec_type: rec_type,
+        name: Some(item.string("name", false).unwrap_or("@".to_owned())),
+        data: item.string("data", true),
+        priority: item.number("priority"),
+        port: item.number("port"),
+        weight: item.number("weight"),
+    }
+}
+
+fn list<'a>(v: &'a Value, section: &str, errors: &mut Vec<String>) -> &'a [Value] {
+    match v.find(section) {
+        Some(&Value::Array(ref a)) => &a[..],
+        Some(_)                    => {
+            errors.push(format!("{}: expected a list", section));
+            &[]
+        },
+        None                       => &[]
+    }
+}
+
+// A single entry of a manifest section, which collects the problems found with it
+struct Item<'a> {
+    map: Option<&'a BTreeMap<String, Value>>,
+    at: String,
+    found: usize,
+    errors: &'a mut Vec<String>,
+}
+
+impl<'a> Item<'a> {
+    fn new(v: &'a Value, at: String, keys: &[&str], errors: &'a mut Vec<String>) -> Item<'a> {
+        let before = errors.len();
+        let map = v.as_object();
+        match map {
+            Some(m) => {
+                for k in m.keys().filter(|k| !keys.contains(&&k[..])) {
+                    errors.push(format!("{}: unknown setting '{}'", at, k));
+                }
+            },
+            None    => errors.push(format!("{}: expected a table of settings", at))
+        }
+        Item { map: map, at: at, found: errors.len() - before, errors: errors }
+    }
+
+    fn error(&mut self, e: String) {
+        self.errors.push(format!("{}: {}", self.at, e));
+        self.found += 1;
+    }
+
+    fn ok(&self) -> bool {
+        self.found == 0
+    }
+
+    fn string(&mut self, key: &str, required: bool) -> Option<String> {
+        match self.map.and_then(|m| m.get(key)).cloned() {
+            Some(Value::String(s)) => Some(s),
+            Some(_)                => {
+                self.error(format!("{} must be a string", key));
+                None
+            },
+            None if required       => {
+                if self.map.is_some() { self.error(format!("no {} given", key)); }
+                None
+            },
+            None                   => None
+        }
+    }
+
+    fn number(&mut self, key: &str) -> Option<u64> {
+        match self.map.and_then(|m| m.get(key)).cloned() {
+            Some(n) => {
+                if n.as_u64().is_none() { self.error(format!("{} must be a number", key)); }
+                n.as_u64()
+            },
+            None    => None
+        }
+    }
+}
+
+/// What the account has, as listed by the API
+pub struct Live {
+    pub ssh_keys: Vec<Value>,
+    pub domains: Vec<Value>,
+    /// The records of the domains the manifest or state mention, by domain
+    pub records: BTreeMap<String, Vec<Value>>,
+    pub droplets: Vec<Value>,
+}
+
+impl Live {
+    // Everything of a kind, records being those of `domain`
+    fn all(&self, kind: &str, domain: &str) -> &[Value] {
+        match kind {
+            "ssh_key" => &self.ssh_keys[..],
+            "domain"  => &self.domains[..],
+            "record"  => self.records.get(domain).map(|r| &r[..]).unwrap_or(&[]),
+            _         => &self.droplets[..],
+        }
+    }
+
+    fn by_id(&self, address: &str, id: &str) -> Option<&Value> {
+        let (kind, domain) = split(address);
+        self.all(kind, domain).iter().find(|v| id_of(kind, v) == id)
+    }
+}
+
+/// The kind of resource an address is for, and the domain of a record
+pub fn split(address: &str) -> (&str, &str) {
+    let mut parts = address.split('/');
+    (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+}
+
+/// The domains whose records have to be listed, those of the manifest and of the state if any
+pub fn domains(resources: &[Resource], state: Option<&State>) -> BTreeSet<String> {
+    let mut domains: BTreeSet<String> = resources.iter().filter_map(|r| match *r {
+        Resource::Domain { ref name, .. }   => Some(name.clone()),
+        Resource::Record { ref domain, .. } => Some(domain.clone()),
+        _                                   => None
+    }).collect();
+    for a in state.iter().flat_map(|s| s.ids.keys()) {
+        if let ("record", domain) = split(a) { domains.insert(domain.to_owned()); }
+    }
+    domains
+}
+
+/// IDs of the resources `apply` has created or adopted, by address. Whatever is in there and no
+/// longer in the manifest is deleted, anything else in the account is left alone.
+pub struct State {
+    path: String,
+    pub ids: BTreeMap<String, String>,
+}
+
+impl State {
+    /// Loads the state at `path`, which is empty until the first `apply`
+    pub fn load(path: &str) -> Result<State, CliError> {
+        let err = |e: String| CliError::Validation(format!("{}: {}", path, e));
+        let mut state = State { path: path.to_owned(), ids: BTreeMap::new() };
+        let mut s = String::new();
+        if File::open(path).and_then(|mut f| f.read_to_string(&mut s)).is_err() {
+            return Ok(state)
+        }
+        let v = match json::from_str::<Value>(&s[..]) {
+            Ok(v)  => v,
+            Err(e) => return Err(err(e.to_string()))
+        };
+        match v.find("resources").and_then(|r| r.as_object()) {
+            Some(r) => {
+                for (a, id) in r.iter() {
+                    if let Some(id) = id.as_string() { state.ids.insert(a.clone(), id.to_owned()); }
+                }
+            },
+            None    => {
+                return Err(err("not a docli state file, \"resources\" is missing".to_owned()))
+            }
+        }
+        Ok(state)
+    }
+
+    pub fn save(&self) -> Result<(), CliError> {
+        let mut map = BTreeMap::new();
+        map.insert("version".to_owned(), Value::U64(1));
+        map.insert("resources".to_owned(), Value::Object(self.ids.iter().map(|(a, id)| {
+            (a.clone(), Value::String(id.clone()))
+        }).collect()));
+        let mut s = json::to_string_pretty(&Value::Object(map)).unwrap_or(String::new());
+        s.push('\n');
+        File::create(&self.path[..]).and_then(|mut f| f.write_all(s.as_bytes())).map_err(|e| {
+            CliError::Validation(format!("{}: {}", self.path, e))
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Create,
+    Update,
+    Replace,
+    Delete,
+}
+
+impl Op {
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            Op::Create  => "+",
+            Op::Update  => "~",
+            Op::Replace => "-/+",
+            Op::Delete  => "-",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Op::Create  => "create",
+            Op::Update  => "update",
+            Op::Replace => "replace",
+            Op::Delete  => "delete",
+        }
+    }
+}
+
+/// A single step of a plan
+pub struct Change {
+    pub op: Op,
+    pub address: String,
+    /// The ID of the existing resource, for everything but `Create`
+    pub id: Option<String>,
+    /// Where the resource is in the manifest, for everything but `Delete`
+    pub resource: Option<usize>,
+    /// What's different, i.e. "size: 512mb -> 1gb"
+    pub details: Vec<String>,
+}
+
+impl Change {
+    pub fn to_json(&self) -> Value {
+        let mut map = BTreeMap::new();
+        map.insert("op".to_owned(), Value::String(self.op.name().to_owned()));
+        map.insert("address".to_owned(), Value::String(self.address.clone()));
+        map.insert("id".to_owned(), self.id.clone().map(Value::String).unwrap_or(Value::Null));
+        map.insert("changes".to_owned(),
+                   Value::Array(self.details.iter().cloned().map(Value::String).collect()));
+        Value::Object(map)
+    }
+}
+
+/// The changes that make the account match a manifest
+pub struct Plan {
+    pub changes: Vec<Change>,
+    /// Addresses and IDs of the resources that already match
+    pub unchanged: Vec<(String, String)>,
+    /// Addresses in the state whose resources no longer exist
+    pub gone: Vec<String>,
+}
+
+impl Plan {
+    pub fn count(&self, op: Op) -> usize {
+        self.changes.iter().filter(|c| c.op == op).count()
+    }
+}
+
+/// Compares the manifest with the account. A resource is found by the ID the state has for it,
+/// otherwise by name, so existing resources are adopted rather than created twice. Only the
+/// resources in the state are ever deleted. Changes come in an order that can be applied as is:
+/// keys, domains, records and droplets are created, then deleted the other way around.
+pub fn plan(resources: &[Resource], live: &Live, state: &State) -> Plan {
+    let mut plan = Plan { changes: vec![], unchanged: vec![], gone: vec![] };
+    let mut wanted = BTreeSet::new();
+    for (i, r) in resources.iter().enumerate() {
+        let address = r.address();
+        let (kind, domain) = split(&address[..]);
+        let found = match state.ids.get(&address) {
+            Some(id) => live.by_id(&address[..], id),
+            None     => live.all(kind, domain).iter().find(|v| r.is(v)),
+        };
+        let change = match found {
+            None    => Some(Change { op: Op::Create, address: address.clone(), id: None,
+                                     resource: Some(i), details: vec![] }),
+            Some(v) => {
+                let id = id_of(kind, v);
+                let (details, replace) = r.differences(v);
+                if details.is_empty() {
+                    plan.unchanged.push((address.clone(), id));
+                    None
+                } else {
+                    Some(Change { op: if replace { Op::Replace } else { Op::Update },
+                                  address: address.clone(), id: Some(id), resource: Some(i),
+                                  details: details })
+                }
+            }
+        };
+        if let Some(c) = change { plan.changes.push(c); }
+        wanted.insert(address.clone());
+    }
+    for (address, id) in state.ids.iter().filter(|&(a, _)| !wanted.contains(a)) {
+        match live.by_id(address, id) {
+            Some(_) => plan.changes.push(Change { op: Op::Delete, address: address.clone(),
+                                                  id: Some(id.clone()), resource: None,
+                                                  details: vec![] }),
+            None    => plan.gone.push(address.clone())
+        }
+    }
+    plan.changes.sort_by(|a, b| order(a).cmp(&order(b)));
+    plan
+}
+
+fn order(c: &Change) -> (bool, usize) {
+    let (kind, _) = split(&c.address[..]);
+    let rank = SECTIONS.iter().position(|s| s.starts_with(kind)).unwrap_or(0);
+    if c.op == Op::Delete { (true, SECTIONS.len() - rank) } else { (false, rank) }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DriftKind {
+    /// In the manifest but not in the account
+    Missing,
+    /// In the account but not in the manifest
+    Unexpected,
+    /// In both, with different settings
+    Differs,
+}
+
+impl DriftKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DriftKind::Missing    => "missing",
+            DriftKind::Unexpected => "unexpected",
+            DriftKind::Differs    => "differs",
+        }
+    }
+}
+
+/// A single difference between the account and a manifest
+pub struct Drift {
+    pub kind: DriftKind,
+    pub address: String,
+    /// The ID of the resource in the account, unless it's missing
+    pub id: Option<String>,
+    pub details: Vec<String>,
+}
+
+impl Drift {
+    pub fn to_json(&self) -> Value {
+        let mut map = BTreeMap::new();
+        map.insert("status".to_owned(), Value::String(self.kind.name().to_owned()));
+        map.insert("address".to_owned(), Value::String(self.address.clone()));
+        map.insert("id".to_owned(), self.id.clone().map(Value::String).unwrap_or(Value::Null));
+        map.insert("changes".to_owned(),
+                   Value::Array(self.details.iter().cloned().map(Value::String).collect()));
+        Value::Object(map)
+    }
+}
+
+/// Compares the account with a manifest, by name and regardless of any state. Unlike a plan,
+/// everything in the account that the manifest doesn't describe counts, except the records of
+/// undescribed domains and the NS records DigitalOcean keeps at the apex of every domain. A
+/// record whose data changed is reported as differing when nothing else has its type and name.
+pub fn drift(resources: &[Resource], live: &Live) -> Vec<Drift> {
+    let mut drift = vec![];
+    // Every object the manifest accounts for, see `seen_key`
+    let mut seen = BTreeSet::new();
+    let mut missing = vec![];
+    for r in resources.iter() {
+        let address = r.address();
+        let (kind, domain) = split(&address[..]);
+        let v = match live.all(kind, domain).iter().find(|v| r.is(v)) {
+            Some(v) => v,
+            None    => {
+                missing.push(r);
+                continue
+            }
+        };
+        let id = id_of(kind, v);
+        let details = r.differences(v).0;
+        seen.insert(seen_key(kind, domain, &id));
+        if !details.is_empty() {
+            drift.push(Drift { kind: DriftKind::Differs, address: address.clone(), id: Some(id),
+                               details: details });
+        }
+    }
+    for r in missing {
+        let address = r.address();
+        let (kind, domain) = split(&address[..]);
+        let mut found = None;
+        if let Resource::Record { ref record, .. } = *r {
+            let similar: Vec<&Value> = live.all(kind, domain).iter().filter(|v| {
+                text(v, "type") == opt(&record.rec_type) && text(v, "name") == opt(&record.name)
+                    && !seen.contains(&seen_key(kind, domain, &id_of(kind, v)))
+            }).collect();
+            if similar.len() == 1 {
+                let v = similar[0];
+                found = Some((id_of(kind, v), changed("data", text(v, "data").to_owned(),
+                                                      opt(&record.data).to_owned())));
+            }
+        }
+        match found {
+            Some((id, detail)) => {
+                seen.insert(seen_key(kind, domain, &id));
+                drift.push(Drift { kind: DriftKind::Differs, address: address.clone(),
+                                   id: Some(id), details: vec![detail] });
+            },
+            None               => {
+                drift.push(Drift { kind: DriftKind::Missing, address: address.clone(), id: None,
+                                   details: vec![] });
+            }
+        }
+    }
+    let mut everything = vec![];
+    for v in live.ssh_keys.iter() { everything.push(("ssh_key", "", v)); }
+    for v in live.domains.iter() { everything.push(("domain", "", v)); }
+    for (domain, records) in live.records.iter() {
+        for v in records.iter() {
+            if text(v, "type") == "NS" && text(v, "name") == "@" { continue }
+            everything.push(("record", &domain[..], v));
+        }
+    }
+    for v in live.droplets.iter() { everything.push(("droplet", "", v)); }
+    for &(kind, domain, v) in everything.iter() {
+        let id = id_of(kind, v);
+        if seen.contains(&seen_key(kind, domain, &id)) { continue }
+        let address = if kind == "record" {
+            format!("record/{}/{}/{}/{}", domain, text(v, "type"), text(v, "name"), text(v, "data"))
+        } else {
+            format!("{}/{}", kind, text(v, "name"))
+        };
+        drift.push(Drift { kind: DriftKind::Unexpected, address: address, id: Some(id),
+                           details: vec![] });
+    }
+    drift
+}
+
+// Identifies a listed object, records being those of a domain
+fn seen_key(kind: &str, domain: &str, id: &str) -> String {
+    if kind == "record" { format!("record/{}/{}", domain, id) } else { format!("{}/{}", kind, id) }
+}
+
+// A manifest may name an image by slug, name or ID
+fn same_image(image: &str, v: &Value) -> bool {
+    image == image_of(v) || image == text(v.find("image").unwrap_or(&Value::Null), "name")
+        || image == id_of("image", v.find("image").unwrap_or(&Value::Null))
+}
+
+fn image_of(v: &Value) -> String {
+    let image = v.find("image").unwrap_or(&Value::Null);
+    match text(image, "slug") {
+        ""   => text(image, "name").to_owned(),
+        slug => slug.to_owned()
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+
+use serde::json::Value;
+
+use output::{self, OutputFormat};
+
+/// The formats `docli inventory` writes
+pub const FORMATS: [&'static str; 5] = ["ssh-config", "ansible-ini", "ansible-json", "hosts",
+    "prometheus-file-sd"];
+
+/// What droplets can be grouped by
+pub const GROUPINGS: [&'static str; 4] = ["region", "size", "image", "prefix"];
+
+// Group names Ansible gives a meaning of its own, which prefix groups can't take
+const RESERVED: [&'static str; 3] = ["all", "ungrouped", "_meta"];
+
+/// A droplet, as much of it as an inventory needs
+pub struct Host {
+    pub id: u64,
+    pub name: String,
+    /// Where the droplet is reached, which of its addresses depends on the command line
+    pub address: String,
+    pub region: String,
+    pub size: String,
+    /// The slug of the image, or its name for snapshots and backups which have none
+    pub image: String,
+    pub tags: Vec<String>,
+}
+
+impl Host {
+    /// A droplet as listed by the API, reached at `address`
+    pub fn of(d: &Value, address: String) -> Host {
+        let text = |path: &[&str]| d.find_path(path).and_then(|s| s.as_string()).unwrap_or("")
+                                    .to_owned();
+        let slug = text(&["image", "slug"]);
+        Host {
+            id: d.find("id").and_then(|i| i.as_u64()).unwrap_or(0),
+            name: text(&["name"]),
+            address: address,
+            region: text(&["region", "slug"]),
+            size: text(&["size_slug"]),
+            image: if slug.is_empty() { text(&["image", "name"]) } else { slug },
+            tags: d.find("tags").and_then(|t| t.as_array())
+                   .map(|t| t.iter().filter_map(|t| t.as_string()).map(|t| t.to_owned())
+                             .collect())
+                   .unwrap_or(vec![]),
+        }
+    }
+
+    /// The name without the number ending it, so that web-1 and web-2 are both `web`
+    pub fn prefix(&self) -> &str {
+        let prefix = self.name.trim_right_matches(|c: char| c.is_digit(10))
+                              .trim_right_matches(|c| c == '-' || c == '.' || c == '_');
+        if prefix.is_empty() { &self.name[..] } else { prefix }
+    }
+
+    fn grouped_by(&self, grouping: &str) -> &str {
+        match grouping {
+            "region" => &self.region[..],
+            "size"   => &self.size[..],
+            "image"  => &self.image[..],
+            _        => self.prefix()
+        }
+    }
+}
+
+/// How an inventory is written besides its format
+pub struct Options<'a> {
+    /// Some of `GROUPINGS`
+    pub groupings: Vec<&'a str>,
+    /// Who to log in as and with which private key, left to the tools when `None`
+    pub user: Option<String>,
+    pub identity: Option<String>,
+    /// The port Prometheus scrapes
+    pub port: u16,
+}
+
+/// Writes `hosts` in one of `FORMATS`. Ansible groups are named after what they group by and its
+/// value, such as `region_nyc3` or `size_1gb`, except those of name prefixes which are named
+/// after the prefix alone, such as `web`, unless Ansible has a use for that name already.
+pub fn render(format: &str, hosts: &[Host], opts: &Options) -> String {
+    match format {
+        "ssh-config"   => ssh_config(hosts, opts),
+        "ansible-ini"  => ansible_ini(hosts, opts),
+        "ansible-json" => output::render(OutputFormat::Json, &ansible_json(hosts, opts)),
+        "hosts"        => hosts_file(hosts),
+        _              => output::render(OutputFormat::Json, &file_sd(hosts, opts)),
+    }
+}
+
+fn ssh_config(hosts: &[Host], opts: &Options) -> String {
+    let mut s = String::from("# Written by docli inventory\n");
+    for h in hosts.iter() {
+        s.push_str(&format!("\nHost {}\n    HostName {}\n", h.name, h.address)[..]);
+        if let Some(ref u) = opts.user { s.push_str(&format!("    User {}\n", u)[..]); }
+        if let Some(ref i) = opts.identity { s.push_str(&format!("    IdentityFile {}\n", i)[..]); }
+    }
+    s
+}
+
+fn ansible_ini(hosts: &[Host], opts: &Options) -> String {
+    let mut s = String::from("# Written by docli inventory\n");
+    for h in hosts.iter() {
+        s.push_str(&format!("{} ansible_host={}", h.name, h.address)[..]);
+        if let Some(ref u) = opts.user { s.push_str(&format!(" ansible_user={}", u)[..]); }
+        if let Some(ref i) = opts.identity {
+            s.push_str(&format!(" ansible_ssh_private_key_file={}", i)[..]);
+        }
+        s.push('\n');
+    }
+    for (group, names) in groups(hosts, opts).iter() {
+        s.push_str(&format!("\n[{}]\n", group)[..]);
+        for n in names.iter() {
+            s.push_str(&format!("{}\n", n)[..]);
+        }
+    }
+    s
+}
+
+// The output of an Ansible dynamic inventory script
+fn ansible_json(hosts: &[Host], opts: &Options) -> Value {
+    let string = |s: &str| Value::String(s.to_owned());
+    let list = |names: &[&str]| {
+        let mut map = BTreeMap::new();
+        map.insert("hosts".to_owned(), Value::Array(names.iter().map(|n| string(n)).collect()));
+        Value::Object(map)
+    };
+    let mut vars = BTreeMap::new();
+    for h in hosts.iter() {
+        let mut v = BTreeMap::new();
+        v.insert("ansible_host".to_owned(), string(&h.address[..]));
+        if let Some(ref u) = opts.user { v.insert("ansible_user".to_owned(), string(&u[..])); }
+        if let Some(ref i) = opts.identity {
+            v.insert("ansible_ssh_private_key_file".to_owned(), string(&i[..]));
+        }
+        v.insert("do_id".to_owned(), Value::U64(h.id));
+        v.insert("do_region".to_owned(), string(&h.region[..]));
+        v.insert("do_size".to_owned(), string(&h.size[..]));
+        v.insert("do_image".to_owned(), string(&h.image[..]));
+        v.insert("do_tags".to_owned(), Value::Array(h.tags.iter().map(|t| string(t)).collect()));
+        vars.insert(h.name.clone(), Value::Object(v));
+    }
+    let mut meta = BTreeMap::new();
+    meta.insert("hostvars".to_owned(), Value::Object(vars));
+    let mut inv = BTreeMap::new();
+    inv.insert("_meta".to_owned(), Value::Object(meta));
+    let all: Vec<&str> = hosts.iter().map(|h| &h.name[..]).collect();
+    inv.insert("all".to_owned(), list(&all[..]));
+    for (group, names) in groups(hosts, opts).into_iter() {
+        inv.insert(group, list(&names[..]));
+    }
+    Value::Object(inv)
+}
+
+fn hosts_file(hosts: &[Host]) -> String {
+    let mut s = String::from("# Written by docli inventory\n");
+    for h in hosts.iter() {
+        s.push_str(&format!("{}\t{}\n", h.address, h.name)[..]);
+    }
+    s
+}
+
+// Prometheus file based service discovery, a target group per droplet labeled with its groups
+fn file_sd(hosts: &[Host], opts: &Options) -> Value {
+    Value::Array(hosts.iter().map(|h| {
+        let target = if h.address.contains(':') {
+            format!("[{}]:{}", h.address, opts.port)
+        } else {
+            format!("{}:{}", h.address, opts.port)
+        };
+        let mut labels = BTreeMap::new();
+        labels.insert("droplet".to_owned(), Value::String(h.name.clone()));
+        for g in opts.groupings.iter() {
+            labels.insert((*g).to_owned(), Value::String(h.grouped_by(g).to_owned()));
+        }
+        let mut group = BTreeMap::new();
+        group.insert("targets".to_owned(), Value::Array(vec![Value::String(target)]));
+        group.insert("labels".to_owned(), Value::Object(labels));
+        Value::Object(group)
+    }).collect())
+}
+
+// The names of the hosts in each group
+fn groups<'a>(hosts: &'a [Host], opts: &Options) -> BTreeMap<String, Vec<&'a str>> {
+    let mut groups = BTreeMap::new();
+    for h in hosts.iter() {
+        for g in opts.groupings.iter() {
+            let value = group_name(h.grouped_by(g));
+            let name = if *g == "prefix" && !RESERVED.contains(&&value[..]) {
+                value
+            } else {
+                format!("{}_{}", g, value)
+            };
+            groups.entry(name).or_insert(vec![]).push(&h.name[..]);
+        }
+    }
+    groups
+}
+
+// Ansible group names are made of letters, digits and underscores
+fn group_name(s: &str) -> String {
+    s.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect()
+}
@@ -1,56 +1,175 @@
 #[macro_use]
 extern crate clap;
 extern crate doapi;
+extern crate hyper;
+extern crate openssl;
+extern crate rustc_serialize;
+extern crate serde;
+extern crate time;
+extern crate toml;
+extern crate yaml_rust;
 #[cfg(feature = "color")]
 extern crate ansi_term;
 
+use std::io::{self, Write};
+use std::sync::Arc;
+
 use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
 
 use doapi::request::DnsRecType;
 
+mod address;
+mod cassette;
 mod cli;
+mod client;
 mod config;
+mod error;
+mod infra;
+mod inventory;
 mod message;
+mod output;
+mod proxy;
+mod pubkey;
+mod resolve;
+mod spec;
+mod token;
+mod userdata;
+mod zone;
 
-use config::Config;
+use cassette::Cassette;
+use client::{Endpoint, DO_API};
+use config::{Config, ConfigFile, Profile};
+use error::CliError;
+use output::OutputFormat;
 use cli::{list, account, dns, domains, droplet, droplets, image, ssh_keys};
 
-fn get_auth_token(m: &ArgMatches) -> String {
+// Precedence is `--token`, then `--token-file`, then the token of a profile picked with
+// `--profile` or DOCLI_PROFILE, then DO_AUTH_TOKEN, and finally the token of the default profile
+fn get_auth_token(m: &ArgMatches, profile: &Profile, explicit: bool)
+                  -> Result<String, CliError> {
+    let env_tok = std::env::vars().filter(|&(ref k, _)| k == "DO_AUTH_TOKEN")
+                                  .map(|(_, v)| v.clone() )
+                                  .next();
     let tok = if let Some(auth_tok) = m.value_of("token") {
-            auth_tok.to_owned()
+        auth_tok.to_owned()
+    } else if let Some(path) = m.value_of("token-file") {
+        try!(token::from_file(path))
+    } else if explicit && profile.has_token() {
+        try!(profile.resolve_token()).unwrap_or("".to_owned())
+    } else if let Some(env_tok) = env_tok {
+        env_tok
     } else {
-        std::env::vars().filter(|&(ref k, _)| k == "DO_AUTH_TOKEN")
-                        .map(|(_, v)| v.clone() )
-                        .next().unwrap_or("".to_owned())
+        try!(profile.resolve_token()).unwrap_or("".to_owned())
     };
     if tok.len() != 64 {
-        println!("No DigitalOcean Auth Token found.\n\n\
-        Use `docli --token <token>` or set the DO_AUTH_TOKEN environment variable and try again");
-        std::process::exit(1);
+        return Err(CliError::Auth("No DigitalOcean Auth Token found.\n\n\
+        Use `docli --token-file <file>`, set the DO_AUTH_TOKEN environment variable or add a \
+        profile with `docli config add` and try again"
+            .to_owned()));
+    }
+    Ok(tok)
+}
+
+// Ends docli with an error found before any command runs, written to stderr when stdout is for
+// data, the way `cli::fail` does
+fn exit_with(e: CliError, output: OutputFormat) -> ! {
+    if output.is_structured() {
+        writeln!(&mut io::stderr(), "{}", e).ok();
+    } else {
+        println!("{}", e);
     }
-    tok
+    std::process::exit(e.exit_code())
 }
 
 fn main() {
     let dns_types = DnsRecType::variants();
+    let output_fmts = OutputFormat::variants();
+    let zone_fmts = ["bind"];
+    let ddns_types = ["A", "AAAA"];
+    let inventory_fmts = inventory::FORMATS;
+    let groupings = inventory::GROUPINGS;
     let dns_args = "-N --name [name]         'Name of the DNS record'
                     -d --data [data]         'Data for the DNS record'
                     -P --priority [priority] 'The priority to set'
                     -p --port [port]         'The port to use'
                     -w --weight [weight]     'The weight value'";
     let noconfirm = "--noconfirm 'Don't confirm, just do it'";
+    let select = "--match [match]       'Selects droplets whose name matches a glob such as web-*'
+                  --tag [tag]           'Selects droplets with this tag'
+                  --region [region]     'Selects droplets in this region'
+                  --size [size]         'Selects droplets of this size'";
+    let parallel = "--parallel [parallel] 'How many droplets to work on at once (Defaults to 4)'";
+    let manifest = "-f --file <file>  'A TOML, YAML or JSON manifest of SSH keys, domains, \
+                                       records and droplets'
+                    --state [state] 'The state file recording what was created (Defaults to \
+                                     the manifest with a .state.json extension)'";
+    let wait = "--wait              'Waits for the action to finish'
+                --timeout [timeout] 'How many seconds to wait for at most (Defaults to 300)'";
     let m = App::new("docli")
         .version(&format!("v{}", crate_version!()))
         .about("A utility for managing DigitalOcean infrastructure")
         .author("Kevin K. <kbknapp@gmail.com>")
         .subcommand_required(true)
         .args_from_usage("-t --token [token] 'Digital Ocean Auth Token (Defaults to contents \
-                                              of DO_AUTH_TOKEN env var if omitted)'")
+                                              of DO_AUTH_TOKEN env var if omitted)'
+                          --token-file [token-file] 'Reads the Digital Ocean Auth Token from a \
+                                                     file only readable by its owner, or from \
+                                                     stdin when given -'
+                          --api-url [api-url] 'Sends requests to another API root, such as a \
+                                               local mock server (Defaults to DOCLI_API_URL or \
+                                               https://api.digitalocean.com/v2)'")
+        .arg(Arg::from_usage("--record [record] 'Saves every request and response of this run \
+                                                 to a cassette file'")
+            .conflicts_with("replay"))
+        .arg(Arg::from_usage("--replay [replay] 'Answers requests from a cassette file made with \
+                                                 --record instead of the network'")
+            .conflicts_with("record"))
         .arg(Arg::from_usage("-v --verbose   'Displays the request being sent to server and JSON reply'")
             .global(true))
         .arg(Arg::from_usage("-n --nosend        'Does NOT send request over the network (useful \
                                                   with --verbose)'")
             .global(true))
+        .arg(Arg::from_usage("-o --output [output] 'Displays results as json, yaml, csv or an aligned \
+                                                   table instead of text (Defaults to text)'")
+            .possible_values(output_fmts.iter())
+            .global(true))
+        .arg(Arg::with_name("as-id")
+            .long("id")
+            .help("Takes droplet, image and SSH key arguments as IDs instead of looking up names")
+            .global(true))
+        .arg(Arg::from_usage("--profile [profile] 'The configuration profile to use (Defaults to \
+                                                   DOCLI_PROFILE or the profile set with `docli \
+                                                   config switch`)'")
+            .global(true))
+        .subcommand(SubCommand::with_name("config")
+            .about("Manage configuration profiles")
+            .subcommand(SubCommand::with_name("add")
+                .about("Adds a new profile, or updates an existing one")
+                .args_from_usage("<name>                  'The name of the profile'
+                                  --token [tok]           'The DigitalOcean Auth Token to use, \
+                                                           or - to read it from stdin'
+                                  --token-file [file]     'A file only readable by its owner \
+                                                           holding the token'
+                                  --token-command [cmd]   'A credential helper command that \
+                                                           prints the token'
+                                  --region [region]       'The default region for new droplets'
+                                  --size [size]           'The default size for new droplets'
+                                  --ssh-keys [keys]...    'The default ssh keys for new droplets'
+                                  --api-url [url]         'Sends requests to another API root'
+                                  --ssh-user [ssh-user]   'Who docli ssh logs in as'
+                                  --ssh-identity [identity] 'The private key docli ssh logs in \
+                                                             with'")
+                .arg(Arg::from_usage("--format [format] 'The default output format'")
+                    .possible_values(output_fmts.iter())))
+            .subcommand(SubCommand::with_name("list")
+                .about("Lists all profiles"))
+            .subcommand(SubCommand::with_name("switch")
+                .about("Makes a profile the default")
+                .arg_from_usage("<name> 'The profile to use from now on'"))
+            .subcommand(SubCommand::with_name("remove")
+                .about("Removes a profile")
+                .arg_from_usage(noconfirm)
+                .arg_from_usage("<name> 'The profile to remove'")))
         .subcommand(SubCommand::with_name("list")
             .about("Get information from DigitalOcean about various sections")
             .subcommand_required(true)
@@ -78,6 +197,8 @@ fn main() {
                 .about("Displays all current and previous account actions")))
         .subcommand(SubCommand::with_name("account")
             .about("Show account information and actions")
+            .arg_from_usage("--show-token 'Displays the full auth token with --verbose instead \
+                                           of hiding it'")
             .subcommand(SubCommand::with_name("actions")
                 .about("Lists all the account actions"))
             .subcommand(SubCommand::with_name("action")
@@ -120,7 +241,81 @@ fn main() {
             .subcommand(SubCommand::with_name("delete-record")
                 .about("Deletes a DNS record")
                 .arg_from_usage(noconfirm)
-                .arg_from_usage("<id>   'The DSN record ID to delete'")))
+                .arg_from_usage("<id>   'The DSN record ID to delete'"))
+            .subcommand(SubCommand::with_name("export")
+                .about("Prints the records of the domain as a zone file")
+                .arg(Arg::from_usage("--format [format] 'The format of the zone file (Defaults \
+                                                         to bind)'")
+                    .possible_values(zone_fmts.iter())))
+            .subcommand(SubCommand::with_name("import")
+                .about("Creates the records of a BIND zone file the domain doesn't have yet")
+                .args_from_usage("<file>    'The zone file to read'
+                                  --dry-run 'Only displays the records that would be created'")
+                .arg_from_usage(noconfirm))
+            .subcommand(SubCommand::with_name("sync")
+                .about("Creates, updates and deletes records until the domain matches a file")
+                .args_from_usage("<file>    'A TOML, YAML or JSON file with a list of records'
+                                  --dry-run 'Only displays the changes that would be made'")
+                .arg_from_usage(noconfirm))
+            .subcommand(SubCommand::with_name("ddns")
+                .about("Points a record at the current address of this host")
+                .args_from_usage("--name <name>             'The name of the record, such as home'
+                                  --lookup-url [lookup-url] 'A URL answering with the public \
+                                                             address (Defaults to api.ipify.org \
+                                                             or api6.ipify.org)'
+                                  --interface [interface]   'Uses the address of a network \
+                                                             interface instead of looking it up'
+                                  --daemon                  'Keeps checking the address until \
+                                                             stopped'")
+                .arg(Arg::from_usage("-t --type [type] 'The type of the record (Defaults to A)'")
+                    .possible_values(ddns_types.iter()))
+                .arg(Arg::from_usage("--interval [interval] 'Seconds between checks with \
+                                                             --daemon (Defaults to 300)'")
+                    .requires("daemon"))
+                .arg_group(ArgGroup::with_name("source").add_all(vec!["lookup-url",
+                                                                      "interface"]))))
+        .subcommand(SubCommand::with_name("plan")
+            .about("Displays the changes needed for the account to match a manifest")
+            .args_from_usage(manifest))
+        .subcommand(SubCommand::with_name("apply")
+            .about("Changes the account to match a manifest")
+            .args_from_usage(manifest)
+            .arg_from_usage(noconfirm))
+        .subcommand(SubCommand::with_name("drift")
+            .about("Displays how the account differs from a manifest, failing if it does")
+            .arg_from_usage("-f --file <file> 'A TOML, YAML or JSON manifest of SSH keys, \
+                                               domains, records and droplets'"))
+        .subcommand(SubCommand::with_name("ssh")
+            .about("Opens an SSH session on a droplet, or runs a command there")
+            .args_from_usage("<droplet>                'The name or ID of the droplet'
+                              [cmd]...                 'The command to run instead of a shell, \
+                                                        after --'
+                              -u --user [user]         'Who to log in as (Defaults to the \
+                                                        profile ssh user, then root)'
+                              -i --identity [identity] 'The private key to log in with \
+                                                        (Defaults to the profile ssh identity)'
+                              --private                'Connects to the private address'
+                              --ipv6                   'Connects to the IPv6 address'
+                              --wait                   'Waits until the droplet is active and \
+                                                        port 22 answers'
+                              --timeout [timeout]      'How many seconds to wait for at most \
+                                                        (Defaults to 300)'
+                              --print                  'Prints the ssh command instead of \
+                                                        running it'")
+            .arg_group(ArgGroup::with_name("network").add_all(vec!["private", "ipv6"])))
+        .subcommand(SubCommand::with_name("inventory")
+            .about("Writes the droplets as an SSH config, Ansible inventory, hosts file or \
+                    Prometheus targets")
+            .arg(Arg::from_usage("-f --format <format> 'What to write'")
+                .possible_values(inventory_fmts.iter()))
+            .arg(Arg::from_usage("--group-by [group-by]... 'What to group droplets by (Defaults \
+                                                            to all of them)'")
+                .possible_values(groupings.iter()))
+            .args_from_usage("--private       'Uses the private addresses'
+                              --ipv6          'Uses the IPv6 addresses'
+                              --port [port]   'The port Prometheus scrapes (Defaults to 9100)'")
+            .args_from_usage(select)
+            .arg_group(ArgGroup::with_name("network").add_all(vec!["private", "ipv6"])))
         .subcommand(SubCommand::with_name("droplets")
             .about("Manage droplets")
             .subcommand(SubCommand::with_name("neighbors")
@@ -129,18 +324,60 @@ fn main() {
                 .about("Displays all droplets with pending upgrades"))
             .subcommand(SubCommand::with_name("create")
                 .about("Creates a new droplet")
-                .args_from_usage("<name>                      'The name of the droplet'
-                                  -r --region <region>        'The region of the droplet'
-                                  -s --size <size>            'The size of the droplet'
-                                  -i --image <image>          'The image to use'
-                                  -k --ssh-keys [keys]...     'Any ssh keys to add'
+                .args_from_usage("[name]                      'The name of the droplet'
+                                  -f --file [file]            'Creates the droplets described in \
+                                                               a TOML, YAML or JSON file instead'
+                                  -r --region [region]        'The region of the droplet (Defaults \
+                                                               to the profile region)'
+                                  -s --size [size]            'The size of the droplet (Defaults \
+                                                               to the profile size)'
+                                  -i --image [image]          'The image to use'
+                                  -k --ssh-keys [keys]...     'Any ssh keys to add (Defaults to the \
+                                                               profile ssh keys)'
                                   --backups                   'Allow backups'
                                   --ipv6                      'Use IPv6'
                                   --private-networking        'Use private networking'
-                                  -u --user-data [data]       'User data'")))
+                                  -u --user-data [data]       'User data'
+                                  --user-data-file [user-data-file] 'Reads the user data from a \
+                                                                     file, or stdin when given -'
+                                  --template                  'Replaces {{ var }} in the user \
+                                                               data, such as {{ droplet.name }} \
+                                                               or {{ env.HOME }}'"))
+            .subcommand(SubCommand::with_name("reboot")
+                .about("Reboots every selected droplet")
+                .args_from_usage(select)
+                .arg_from_usage(parallel)
+                .arg_from_usage(noconfirm))
+            .subcommand(SubCommand::with_name("power-off")
+                .about("Powers off every selected droplet")
+                .args_from_usage(select)
+                .arg_from_usage(parallel)
+                .arg_from_usage(noconfirm))
+            .subcommand(SubCommand::with_name("power-on")
+                .about("Powers on every selected droplet")
+                .args_from_usage(select)
+                .arg_from_usage(parallel)
+                .arg_from_usage(noconfirm))
+            .subcommand(SubCommand::with_name("shutdown")
+                .about("Shuts down every selected droplet")
+                .args_from_usage(select)
+                .arg_from_usage(parallel)
+                .arg_from_usage(noconfirm))
+            .subcommand(SubCommand::with_name("snapshot")
+                .about("Snapshots every selected droplet")
+                .arg_from_usage("<name> 'The name of the snapshots, {name} and {id} are replaced \
+                                         by those of each droplet'")
+                .args_from_usage(select)
+                .arg_from_usage(parallel)
+                .arg_from_usage(noconfirm))
+            .subcommand(SubCommand::with_name("delete")
+                .about("Deletes every selected droplet")
+                .args_from_usage(select)
+                .arg_from_usage(parallel)
+                .arg_from_usage(noconfirm)))
         .subcommand(SubCommand::with_name("droplet")
             .about("Manage a specific droplet")
-            .arg_from_usage("<id> 'The droplet ID to use'")
+            .arg_from_usage("<id> 'The droplet ID or name to use'")
             .subcommand(SubCommand::with_name("kernels")
                 .about("Display all available kernels"))
             .subcommand(SubCommand::with_name("snapshots")
@@ -155,55 +392,76 @@ fn main() {
                 .about("Deletes a droplet")
                 .arg_from_usage(noconfirm))
             .subcommand(SubCommand::with_name("disable-backups")
-                .about("Disables backups for a droplet"))
+                .about("Disables backups for a droplet")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("reboot")
-                .about("Reboots a droplet"))
+                .about("Reboots a droplet")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("power-cycle")
-                .about("Performs a power cycle on a droplet"))
+                .about("Performs a power cycle on a droplet")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("shutdown")
-                .about("Shutsdown a droplet"))
+                .about("Shutsdown a droplet")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("power-off")
-                .about("Powers a droplet off"))
+                .about("Powers a droplet off")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("power-on")
-                .about("Turns on a droplet"))
+                .about("Turns on a droplet")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("restore")
                 .about("Restores a droplet from an image")
+                .args_from_usage(wait)
                 .arg_from_usage(noconfirm)
                 .arg_from_usage("<image> 'The image ID or slug to restore to'"))
             .subcommand(SubCommand::with_name("reset-password")
-                .about("Resets the root password for a droplet"))
+                .about("Resets the root password for a droplet")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("resize")
                 .about("Resizes a droplet")
+                .args_from_usage(wait)
                 .args_from_usage("--disk 'Resizes the disk'
                                   <size> 'The new size to use (i.e. 15gb)'"))
             .subcommand(SubCommand::with_name("rebuild")
                 .about("Rebuilds a droplet from an image")
+                .args_from_usage(wait)
                 .arg_from_usage(noconfirm)
                 .arg_from_usage("<image> 'The image ID or slug to use'"))
             .subcommand(SubCommand::with_name("rename")
                 .about("Renames a droplet")
+                .args_from_usage(wait)
                 .arg_from_usage("<name> 'The new name to use'")
                 .arg_from_usage(noconfirm))
             .subcommand(SubCommand::with_name("change-kernel")
                 .about("Changes the kernel of a droplet")
+                .args_from_usage(wait)
                 .arg_from_usage(noconfirm)
                 .arg_from_usage("<kernel_id> 'The kernel ID to use'"))
             .subcommand(SubCommand::with_name("enable-ipv6")
-                .about("Enables IPv6 addresses"))
+                .about("Enables IPv6 addresses")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("enable-private-networking")
-                .about("Enables private networking"))
+                .about("Enables private networking")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("snapshot")
                 .arg_from_usage("<name> 'What to name the new snapshot image'")
-                .about("Creates a snapshot of a droplet"))
+                .about("Creates a snapshot of a droplet")
+                .args_from_usage(wait))
             .subcommand(SubCommand::with_name("action")
                 .about("Displays a specific action for a droplet")
                 .arg_from_usage("<action_id> 'The action ID to display'"))
+            .subcommand(SubCommand::with_name("wait")
+                .about("Waits for an action of a droplet to finish")
+                .arg_from_usage("<action_id> 'The action ID to wait for'")
+                .arg_from_usage("--timeout [timeout] 'How many seconds to wait for at most \
+                                                      (Defaults to 300)'"))
             .subcommand(SubCommand::with_name("upgrade")
                 .arg_from_usage(noconfirm)
-                .about("Upgrades a droplet")))
+                .about("Upgrades a droplet")
+                .args_from_usage(wait)))
         .subcommand(SubCommand::with_name("image")
             .about("Manage images")
-            .arg_from_usage("<id> 'The image ID or slug to use (not all commands support using a slug)'")
+            .arg_from_usage("<id> 'The image ID, slug or name to use'")
             .subcommand(SubCommand::with_name("actions")
                 .about("Lists all previous and current actions for an image"))
             .subcommand(SubCommand::with_name("rename")
@@ -228,35 +486,109 @@ fn main() {
                 .about("Creatse a new SSH key")
                 .args_from_usage("<name>       'The name of the SSH key'
                                   <public_key> 'The public key of the SSH key'"))
+            .subcommand(SubCommand::with_name("import")
+                .about("Uploads the public keys of files, directories or ssh-agent not yet there")
+                .args_from_usage("[paths]...    'Public key files, or directories of .pub files'
+                                  --agent       'Imports the keys ssh-agent holds'
+                                  --name [name] 'The name of the key (Defaults to its comment)'
+                                  --dry-run     'Only displays the keys that would be uploaded'")
+                .arg_from_usage(noconfirm))
+            .subcommand(SubCommand::with_name("generate")
+                .about("Makes a new ed25519 key pair and uploads its public key")
+                .args_from_usage("<name>        'The name of the key, also its comment'
+                                  --file [file] 'Where to write the private key (Defaults to \
+                                                 ~/.ssh/<name>)'
+                                  --passphrase  'Lets ssh-keygen ask for a passphrase (Defaults \
+                                                 to none)'"))
+            .subcommand(SubCommand::with_name("sync")
+                .about("Uploads and renames keys until the account matches a directory or file")
+                .args_from_usage("<source>  'A directory of .pub files, or a file of public keys'
+                                  --prune   'Also destroys the keys that aren't in the source'
+                                  --dry-run 'Only displays the changes that would be made'")
+                .arg_from_usage(noconfirm))
             .subcommand(SubCommand::with_name("key")
                 .about("Displays information on a particular key")
-                .args_from_usage("<id> 'The key ID or finger print of the key to display'"))
+                .args_from_usage("<id> 'The ID, finger print, name or .pub file of the key'"))
             .subcommand(SubCommand::with_name("rename")
                 .about("Renames a particular SSH key")
                 .arg_from_usage(noconfirm)
-                .args_from_usage("<id>   'The key ID or finger print of the key to update'
+                .args_from_usage("<id>   'The ID, finger print, name or .pub file of the key'
                                   <name> 'The new name to use'"))
             .subcommand(SubCommand::with_name("destroy")
                 .about("Destroys a particular SSH key")
                 .arg_from_usage(noconfirm)
-                .args_from_usage("<id> 'The key ID or finger print of the key to destroy'")))
+                .args_from_usage("<id> 'The ID, finger print, name or .pub file of the key'")))
         .get_matches();
 
+    // Until the profile is known, only --output says whether stdout is for data
+    let output = value_t!(m.value_of("output"), OutputFormat).ok();
+    let file = match ConfigFile::load() {
+        Ok(f)  => f,
+        Err(e) => exit_with(e, output.unwrap_or(OutputFormat::Text))
+    };
+    if let ("config", Some(m)) = m.subcommand() {
+        if let Err(e) = cli::config::run(m, file) {
+            std::process::exit(e.exit_code());
+        }
+        return
+    }
+    let profile = match file.select(m.value_of("profile")) {
+        Ok(p)  => p.unwrap_or(Profile::default()),
+        Err(e) => exit_with(e, output.unwrap_or(OutputFormat::Text))
+    };
+    let output = output.unwrap_or(profile.output.unwrap_or(OutputFormat::Text));
+    let explicit = m.is_present("profile") || std::env::var("DOCLI_PROFILE").is_ok();
+    let api_url = m.value_of("api-url").map(|u| u.to_owned())
+                   .or(std::env::var("DOCLI_API_URL").ok())
+                   .or(profile.api_url.clone())
+                   .unwrap_or(DO_API.to_owned());
+    let cassette = match (m.value_of("record"), m.value_of("replay")) {
+        (Some(path), _) => Some(Cassette::record(path)),
+        (_, Some(path)) => Some(Cassette::replay(path)),
+        _               => None
+    };
+    let mut api = Endpoint::new(&api_url[..]);
+    match cassette {
+        Some(Ok(c))  => api.cassette = Some(Arc::new(c)),
+        Some(Err(e)) => exit_with(e, output),
+        None         => ()
+    }
+    let replaying = api.cassette.as_ref().map(|c| c.replaying()).unwrap_or(false);
+
     let mut cfg = Config {
         verbose: m.is_present("verbose"),
         no_send: m.is_present("nosend"),
-        auth: get_auth_token(&m)
+        literal_ids: m.is_present("as-id"),
+        output: output,
+        auth: match get_auth_token(&m, &profile, explicit) {
+            Ok(tok) => tok,
+            // Nothing is sent when replaying, so whoever recorded the cassette needn't share
+            // their token
+            Err(_) if replaying => (0..64).map(|_| "0").collect(),
+            Err(e)  => exit_with(e, output)
+        },
+        token_from_stdin: !m.is_present("token") && m.value_of("token-file") == Some("-"),
+        profile: profile,
+        api: api,
     };
 
-    match m.subcommand() {
-        ("account", Some(m))  => account::run(m, &mut cfg),
-        ("domains", Some(m))  => domains::run(m, &mut cfg),
-        ("dns", Some(m))      => dns::run(m, &mut cfg),
-        ("droplets", Some(m)) => droplets::run(m, &mut cfg),
-        ("droplet", Some(m))  => droplet::run(m, &mut cfg),
-        ("image", Some(m))    => image::run(m, &mut cfg),
-        ("ssh-keys", Some(m)) => ssh_keys::run(m, &mut cfg),
-        ("list", Some(m))     => list::run(m, &mut cfg),
-        _                     => ()
+    let res = match m.subcommand() {
+        ("account", Some(m))   => account::run(m, &mut cfg),
+        ("domains", Some(m))   => domains::run(m, &mut cfg),
+        ("dns", Some(m))       => dns::run(m, &mut cfg),
+        ("droplets", Some(m))  => droplets::run(m, &mut cfg),
+        ("droplet", Some(m))   => droplet::run(m, &mut cfg),
+        ("image", Some(m))     => image::run(m, &mut cfg),
+        ("ssh-keys", Some(m))  => ssh_keys::run(m, &mut cfg),
+        ("list", Some(m))      => list::run(m, &mut cfg),
+        ("plan", Some(m))      => cli::infra::plan(m, &mut cfg),
+        ("apply", Some(m))     => cli::infra::apply(m, &mut cfg),
+        ("drift", Some(m))     => cli::infra::drift(m, &mut cfg),
+        ("ssh", Some(m))       => cli::ssh::run(m, &mut cfg),
+        ("inventory", Some(m)) => cli::inventory::run(m, &mut cfg),
+        _                      => Ok(())
+    };
+    if let Err(e) = res {
+        std::process::exit(e.exit_code());
     }
 }
@@ -79,6 +79,27 @@ pub enum CliMessage<'a> {
     SshKey(&'a str),
     UpdateSshKey(&'a str, &'a str),
     CreateDns(&'a DnsRecord),
+    Profiles,
+    AddProfile(&'a str),
+    SwitchProfile(&'a str),
+    RemoveProfile(&'a str),
+    WaitAction(&'a str),
+    SelectedDroplets(&'a str, usize),
+    BulkDroplets(&'a str, usize),
+    Plan(&'a str),
+    Apply(&'a str),
+    Drift(&'a str),
+    ImportZone(&'a str, &'a str),
+    SyncZone(&'a str, &'a str),
+    ApplySync(usize),
+    Rollback(usize),
+    Ddns(&'a str, &'a str),
+    DdnsDaemon(&'a str, &'a str, u32),
+    Ssh(&'a str, &'a str),
+    WaitSsh(&'a str),
+    ImportKeys,
+    SyncKeys(&'a str),
+    GenerateKey(&'a str),
 }
 
 impl<'a> CliMessage<'a> {
@@ -560,6 +581,153 @@ impl<'a> CliMessage<'a> {
                     White.bold().underline().paint(id),
                     White.bold().paint("..."));
             },
+            CliMessage::Profiles => {
+                println!("{} {}\n",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Displaying configured profiles..."));
+            },
+            CliMessage::AddProfile(name) => {
+                print!("{} {} {}{}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Saving profile"),
+                    White.bold().underline().paint(name),
+                    White.bold().paint("..."));
+            },
+            CliMessage::SwitchProfile(name) => {
+                print!("{} {} {}{}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Switching to profile"),
+                    White.bold().underline().paint(name),
+                    White.bold().paint("..."));
+            },
+            CliMessage::RemoveProfile(name) => {
+                print!("{} {} {}{}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Removing profile"),
+                    White.bold().underline().paint(name),
+                    White.bold().paint("..."));
+            },
+            CliMessage::WaitAction(id) => {
+                print!("{} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Waiting for action"),
+                    White.bold().underline().paint(id),
+                    White.bold().paint("to finish..."));
+            },
+            CliMessage::SelectedDroplets(op, n) => {
+                println!("{} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Selected"),
+                    White.bold().underline().paint(&n.to_string()[..]),
+                    White.bold().paint(&format!("droplet(s) to {}:", op)[..]));
+            },
+            CliMessage::BulkDroplets(doing, n) => {
+                println!("{} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint(doing),
+                    White.bold().underline().paint(&n.to_string()[..]),
+                    White.bold().paint("droplet(s)..."));
+            },
+            CliMessage::Plan(file) => {
+                println!("{} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Changes needed to match"),
+                    White.bold().underline().paint(file));
+            },
+            CliMessage::Apply(file) => {
+                println!("{} {} {}{}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Applying"),
+                    White.bold().underline().paint(file),
+                    White.bold().paint("..."));
+            },
+            CliMessage::Drift(file) => {
+                println!("{} {} {}{}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Comparing the account with"),
+                    White.bold().underline().paint(file),
+                    White.bold().paint("..."));
+            },
+            CliMessage::ImportZone(file, domain) => {
+                println!("{} {} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Records of"),
+                    White.bold().underline().paint(file),
+                    White.bold().paint("for"),
+                    White.bold().underline().paint(domain));
+            },
+            CliMessage::ImportKeys => {
+                println!("{} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Public keys to import"));
+            },
+            CliMessage::SyncKeys(source) => {
+                println!("{} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("SSH keys of the account against"),
+                    White.bold().underline().paint(source));
+            },
+            CliMessage::GenerateKey(path) => {
+                println!("{} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Generating an ed25519 key pair in"),
+                    White.bold().underline().paint(path));
+            },
+            CliMessage::SyncZone(domain, file) => {
+                println!("{} {} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Changes needed for"),
+                    White.bold().underline().paint(domain),
+                    White.bold().paint("to match"),
+                    White.bold().underline().paint(file));
+            },
+            CliMessage::ApplySync(n) => {
+                println!("{} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Making"),
+                    White.bold().underline().paint(&n.to_string()[..]),
+                    White.bold().paint("change(s)..."));
+            },
+            CliMessage::Rollback(n) => {
+                println!("{} {} {} {}",
+                    Blue.bold().paint("::"),
+                    Red.bold().paint("Undoing"),
+                    White.bold().underline().paint(&n.to_string()[..]),
+                    White.bold().paint("change(s) already made..."));
+            },
+            CliMessage::Ddns(rec_type, host) => {
+                println!("{} {} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Checking the"),
+                    White.bold().underline().paint(rec_type),
+                    White.bold().paint("record of"),
+                    White.bold().underline().paint(host));
+            },
+            CliMessage::DdnsDaemon(rec_type, host, interval) => {
+                println!("{} {} {} {} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Checking the"),
+                    White.bold().underline().paint(rec_type),
+                    White.bold().paint("record of"),
+                    White.bold().underline().paint(host),
+                    White.bold().paint("every"),
+                    White.bold().paint(&format!("{}s, until stopped", interval)[..]));
+            },
+            CliMessage::Ssh(droplet, target) => {
+                println!("{} {} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Connecting to"),
+                    White.bold().underline().paint(droplet),
+                    White.bold().paint("as"),
+                    White.bold().underline().paint(target));
+            },
+            CliMessage::WaitSsh(droplet) => {
+                print!("{} {} {} {}",
+                    Blue.bold().paint("::"),
+                    White.bold().paint("Waiting for"),
+                    White.bold().underline().paint(droplet),
+                    White.bold().paint("to be active and answer on port 22..."));
+            },
             CliMessage::Confirm => {
                 println!("{} {} {}",
                     Blue.bold().paint("::"),
@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::json::{self, Value};
+
+/// The formats results can be displayed in via `--output`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
+    Csv,
+    Table
+}
+
+impl OutputFormat {
+    /// Lists all formats as they're accepted on the command line
+    pub fn variants() -> Vec<&'static str> {
+        vec!["text", "json", "yaml", "csv", "table"]
+    }
+
+    /// Returns `true` for any format meant to be consumed by other programs
+    pub fn is_structured(&self) -> bool {
+        *self != OutputFormat::Text
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "text"  => Ok(OutputFormat::Text),
+            "json"  => Ok(OutputFormat::Json),
+            "yaml"  => Ok(OutputFormat::Yaml),
+            "csv"   => Ok(OutputFormat::Csv),
+            "table" => Ok(OutputFormat::Table),
+            _       => Err(format!("valid values: {}", OutputFormat::variants().join(" ")))
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            OutputFormat::Text  => "text",
+            OutputFormat::Json  => "json",
+            OutputFormat::Yaml  => "yaml",
+            OutputFormat::Csv   => "csv",
+            OutputFormat::Table => "table",
+        })
+    }
+}
+
+/// Renders a value in the given format. `OutputFormat::Text` falls back to pretty JSON since
+/// the human readable output is produced by each command directly
+pub fn render(fmt: OutputFormat, v: &Value) -> String {
+    match fmt {
+        OutputFormat::Text |
+        OutputFormat::Json  => {
+            let mut s = json::to_string_pretty(v).unwrap_or(String::new());
+            s.push('\n');
+            s
+        },
+        OutputFormat::Yaml  => {
+            let mut s = String::from("---\n");
+            yaml(v, 0, &mut s);
+            s
+        },
+        OutputFormat::Csv   => csv(&rows(v)),
+        OutputFormat::Table => table(&rows(v)),
+    }
+}
+
+fn scalar(v: &Value) -> String {
+    match *v {
+        Value::Null          => String::new(),
+        Value::Bool(b)       => b.to_string(),
+        Value::I64(n)        => n.to_string(),
+        Value::U64(n)        => n.to_string(),
+        Value::F64(n)        => n.to_string(),
+        Value::String(ref s) => s.clone(),
+        _                    => json::to_string(v).unwrap_or(String::new())
+    }
+}
+
+fn yaml_scalar(v: &Value) -> String {
+    match *v {
+        Value::Null          => "~".to_owned(),
+        // Double quoted YAML scalars use the same escapes as JSON strings
+        Value::String(_)     => json::to_string(v).unwrap_or(String::new()),
+        _                    => scalar(v)
+    }
+}
+
+fn yaml_key(k: &str) -> String {
+    if !k.is_empty() && k.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        k.to_owned()
+    } else {
+        json::to_string(&Value::String(k.to_owned())).unwrap_or(String::new())
+    }
+}
+
+fn yaml(v: &Value, indent: usize, out: &mut String) {
+    let pad = (0..indent).map(|_| "  ").collect::<String>();
+    match *v {
+        Value::Object(ref map) if !map.is_empty() => {
+            for (k, val) in map.iter() {
+                match *val {
+                    Value::Object(ref m) if !m.is_empty() => {
+                        out.push_str(&format!("{}{}:\n", pad, yaml_key(k)));
+                        yaml(val, indent + 1, out);
+                    },
+                    Value::Array(ref a) if !a.is_empty() => {
+                        out.push_str(&format!("{}{}:\n", pad, yaml_key(k)));
+                        yaml(val, indent, out);
+                    },
+                    _ => out.push_str(&format!("{}{}: {}\n", pad, yaml_key(k), yaml_empty(val))),
+                }
+            }
+        },
+        Value::Array(ref arr) if !arr.is_empty() => {
+            for val in arr.iter() {
+                match *val {
+                    Value::Object(ref m) if !m.is_empty() => {
+                        // Render the nested object one level deeper, then hang the first key
+                        // off of the list marker
+                        let mut nested = String::new();
+                        yaml(val, indent + 1, &mut nested);
+                        let inner = (0..indent + 1).map(|_| "  ").collect::<String>();
+                        out.push_str(&format!("{}- {}", pad, &nested[inner.len()..]));
+                    },
+                    Value::Array(ref a) if !a.is_empty() => {
+                        out.push_str(&format!("{}-\n", pad));
+                        yaml(val, indent + 1, out);
+                    },
+                    _ => out.push_str(&format!("{}- {}\n", pad, yaml_empty(val))),
+                }
+            }
+        },
+        _ => out.push_str(&format!("{}{}\n", pad, yaml_empty(v))),
+    }
+}
+
+fn yaml_empty(v: &Value) -> String {
+    match *v {
+        Value::Object(_) => "{}".to_owned(),
+        Value::Array(_)  => "[]".to_owned(),
+        _                => yaml_scalar(v)
+    }
+}
+
+// Flattens nested objects into dotted column names (i.e. "region.slug") so that every
+// response can be displayed as rows and columns
+fn flatten(prefix: &str, v: &Value, row: &mut BTreeMap<String, String>) {
+    match *v {
+        Value::Object(ref map) => {
+            for (k, val) in map.iter() {
+                let key = if prefix.is_empty() { k.clone() } else { format!("{}.{}", prefix, k) };
+                flatten(&key, val, row);
+            }
+        },
+        Value::Array(ref arr) if arr.iter().all(|a| !a.is_object() && !a.is_array()) => {
+            row.insert(prefix.to_owned(),
+                       arr.iter().map(scalar).collect::<Vec<_>>().join(" "));
+        },
+        Value::Array(ref arr) => {
+            for (i, val) in arr.iter().enumerate() {
+                flatten(&format!("{}.{}", prefix, i), val, row);
+            }
+        },
+        _ => { row.insert(if prefix.is_empty() { "value".to_owned() } else { prefix.to_owned() },
+                          scalar(v)); }
+    }
+}
+
+fn rows(v: &Value) -> Vec<BTreeMap<String, String>> {
+    match *v {
+        Value::Null            => vec![],
+        Value::Array(ref arr)  => {
+            arr.iter().map(|val| {
+                let mut row = BTreeMap::new();
+                flatten("", val, &mut row);
+                row
+            }).collect()
+        },
+        _                      => {
+            let mut row = BTreeMap::new();
+            flatten("", v, &mut row);
+            vec![row]
+        }
+    }
+}
+
+// Columns are the union of all keys, with "id" and "name" moved to the front when present
+fn columns(rows: &[BTreeMap<String, String>]) -> Vec<String> {
+    let mut cols: Vec<String> = vec![];
+    for row in rows.iter() {
+        for k in row.keys() {
+            if !cols.contains(k) { cols.push(k.clone()); }
+        }
+    }
+    cols.sort();
+    for first in ["name", "id"].iter() {
+        if let Some(pos) = cols.iter().position(|c| c == first) {
+            let c = cols.remove(pos);
+            cols.insert(0, c);
+        }
+    }
+    cols
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(',') || s.contains('"') || s.contains('\n') || s.contains('\r') {
+        format!("\"{}\"", s.replace("\"", "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn csv(rows: &[BTreeMap<String, String>]) -> String {
+    let cols = columns(rows);
+    if cols.is_empty() { return String::new() }
+    let mut out = cols.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(",");
+    out.push('\n');
+    for row in rows.iter() {
+        out.push_str(&cols.iter()
+                          .map(|c| csv_field(row.get(c).map(|s| &s[..]).unwrap_or("")))
+                          .collect::<Vec<_>>()
+                          .join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn table(rows: &[BTreeMap<String, String>]) -> String {
+    let cols = columns(rows);
+    if cols.is_empty() { return String::new() }
+    let cell = |row: &BTreeMap<String, String>, c: &String| {
+        row.get(c).map(|s| s.replace("\n", " ")).unwrap_or(String::new())
+    };
+    let widths = cols.iter().map(|c| {
+        rows.iter().fold(c.chars().count(), |w, r| {
+            let len = cell(r, c).chars().count();
+            if len > w { len } else { w }
+        })
+    }).collect::<Vec<_>>();
+    let line = |vals: Vec<String>| {
+        let mut l = vals.iter()
+                        .zip(widths.iter())
+                        .map(|(v, &w)| format!("{}{}", v, (v.chars().count()..w).map(|_| " ").collect::<String>()))
+                        .collect::<Vec<_>>()
+                        .join("  ");
+        let trimmed = l.trim_right().len();
+        l.truncate(trimmed);
+        l.push('\n');
+        l
+    };
+    let mut out = line(cols.iter().map(|c| c.to_uppercase()).collect());
+    for row in rows.iter() {
+        out.push_str(&line(cols.iter().map(|c| cell(row, c)).collect()));
+    }
+    out
+}
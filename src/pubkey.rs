@@ -0,0 +1,219 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use openssl::crypto::hash::{self, Type};
+use rustc_serialize::base64::FromBase64;
+
+use error::CliError;
+use token;
+
+/// The key types DigitalOcean accepts
+const TYPES: [&'static str; 6] = ["ssh-rsa", "ssh-dss", "ssh-ed25519", "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384", "ecdsa-sha2-nistp521"];
+
+/// An OpenSSH public key
+pub struct PublicKey {
+    /// The type and the base64 encoded key, what tells keys apart
+    pub key: String,
+    pub comment: String,
+    /// The file the key was read from, or "ssh-agent"
+    pub source: String,
+    blob: Vec<u8>,
+}
+
+impl PublicKey {
+    /// Reads a key written the OpenSSH way, as `type key [comment]`. The options `authorized_keys`
+    /// lines may start with are skipped.
+    pub fn parse(line: &str, source: &str) -> Result<PublicKey, String> {
+        let mut fields: Vec<&str> = line.split_whitespace().collect();
+        if let Some(i) = fields.iter().position(|f| TYPES.contains(f)) {
+            fields = fields[i..].to_vec();
+        }
+        if fields.len() < 2 {
+            return Err("expected a key of the form 'type key [comment]'".to_owned())
+        }
+        let kind = fields[0];
+        if !TYPES.contains(&kind) {
+            return Err(format!("{} keys aren't supported, only {} are", kind, TYPES.join(", ")))
+        }
+        let blob = match fields[1].from_base64() {
+            Ok(b)  => b,
+            Err(_) => return Err(format!("the {} key isn't valid base64", kind))
+        };
+        // The decoded key starts over with its type, after the length of it
+        if blob.len() < 4 + kind.len() || &blob[4..4 + kind.len()] != kind.as_bytes() {
+            return Err(format!("the key doesn't hold a {} key", kind))
+        }
+        Ok(PublicKey {
+            key: format!("{} {}", kind, fields[1]),
+            comment: fields[2..].join(" "),
+            source: source.to_owned(),
+            blob: blob,
+        })
+    }
+
+    /// The MD5 fingerprint DigitalOcean tells keys apart with, such as `d6:9a:30:...:0e`
+    pub fn fingerprint(&self) -> String {
+        hash::hash(Type::MD5, &self.blob[..]).iter().map(|b| format!("{:02x}", b))
+                                             .collect::<Vec<_>>().join(":")
+    }
+
+    /// The key along with its comment, the way it's uploaded
+    pub fn line(&self) -> String {
+        if self.comment.is_empty() {
+            self.key.clone()
+        } else {
+            format!("{} {}", self.key, self.comment)
+        }
+    }
+
+    /// What to name the key when uploading it: its comment, or the name of its file
+    pub fn default_name(&self) -> String {
+        if !self.comment.is_empty() { return self.comment.clone() }
+        Path::new(&self.source).file_stem().map(|s| s.to_string_lossy().into_owned())
+                               .unwrap_or(self.source.clone())
+    }
+}
+
+/// The keys of a file, one per line like in `authorized_keys`, or those of every `.pub` file of a
+/// directory. Every problem found is reported at once.
+pub fn read(path: &str) -> Result<Vec<PublicKey>, CliError> {
+    let path = token::expand_home(path);
+    let err = |e: String| CliError::Validation(format!("{}: {}", path.display(), e));
+    let files = if path.is_dir() {
+        let entries = try!(fs::read_dir(&path).map_err(|e| err(e.to_string())));
+        let mut files: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path())
+            .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("pub"))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.clone()]
+    };
+    let mut keys = vec![];
+    let mut errors = vec![];
+    for f in files.iter() {
+        let mut s = String::new();
+        if let Err(e) = File::open(f).and_then(|mut file| file.read_to_string(&mut s)) {
+            errors.push(format!("{}: {}", f.display(), e));
+            continue
+        }
+        let source = f.to_string_lossy().into_owned();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+            match PublicKey::parse(line, &source[..]) {
+                Ok(k)  => keys.push(k),
+                Err(e) => errors.push(format!("{}:{}: {}", f.display(), i + 1, e))
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(CliError::Validation(errors.join("\n\t")))
+    }
+    if keys.is_empty() {
+        return Err(err("no public keys found".to_owned()))
+    }
+    Ok(keys)
+}
+
+/// Drops the keys found more than once, keeping the first of each
+pub fn dedup(keys: &mut Vec<PublicKey>) {
+    let mut seen = vec![];
+    keys.retain(|k| {
+        let fp = k.fingerprint();
+        if seen.contains(&fp) { return false }
+        seen.push(fp);
+        true
+    });
+}
+
+/// The keys the SSH agent holds, as listed by `ssh-add -L`
+pub fn from_agent() -> Result<Vec<PublicKey>, CliError> {
+    let out = match Command::new("ssh-add").arg("-L").output() {
+        Ok(out) => out,
+        Err(e)  => return Err(CliError::Validation(format!("couldn't run ssh-add: {}", e)))
+    };
+    let listed = String::from_utf8_lossy(&out.stdout).into_owned();
+    // ssh-add exits with 1 when the agent holds no keys, and 2 when there's no agent
+    match out.status.code() {
+        Some(0) => (),
+        Some(1) => return Ok(vec![]),
+        _       => {
+            return Err(CliError::Validation(format!("couldn't list the keys of ssh-agent: {}",
+                String::from_utf8_lossy(&out.stderr).trim())))
+        }
+    }
+    let mut keys = vec![];
+    for line in listed.lines().filter(|l| !l.trim().is_empty()) {
+        match PublicKey::parse(line, "ssh-agent") {
+            Ok(k)  => keys.push(k),
+            Err(e) => return Err(CliError::Validation(format!("ssh-agent: {}", e)))
+        }
+    }
+    Ok(keys)
+}
+
+/// The fingerprint of the key in a local file, for key arguments that are paths, that is which
+/// have a `/` or end with `.pub`. A private key stands for the `.pub` file next to it. Anything
+/// else, or a file that doesn't exist, gives `None`.
+pub fn local_fingerprint(arg: &str) -> Option<Result<String, CliError>> {
+    if !arg.contains('/') && !arg.ends_with(".pub") { return None }
+    let path = token::expand_home(arg);
+    let public = public_of(&path);
+    let file = if public.is_file() {
+        public
+    } else if path.is_file() {
+        path
+    } else {
+        return None
+    };
+    Some(read(&file.to_string_lossy()[..]).map(|keys| keys[0].fingerprint()))
+}
+
+/// Where the public half of the private key at `path` is, `path` with `.pub` added
+pub fn public_of(path: &Path) -> PathBuf {
+    path.with_file_name(format!("{}.pub",
+        path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or(String::new())))
+}
+
+/// Makes an ed25519 key pair at `path` with `ssh-keygen`, commented with `comment`. The private key
+/// is only readable by its owner, and so is the directory when it's created along. Without
+/// `passphrase` the key has none, otherwise `ssh-keygen` asks for it.
+pub fn generate(path: &Path, comment: &str, passphrase: bool) -> Result<PublicKey, CliError> {
+    let err = |e: String| CliError::Validation(format!("{}: {}", path.display(), e));
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() && !dir.exists() {
+            try!(fs::create_dir_all(dir).and_then(|_| restrict(dir, 0o700))
+                                        .map_err(|e| err(e.to_string())));
+        }
+    }
+    let mut cmd = Command::new("ssh-keygen");
+    cmd.args(&["-q", "-t", "ed25519", "-C", comment, "-f"]).arg(path);
+    if !passphrase { cmd.args(&["-N", ""]); }
+    match cmd.status() {
+        Ok(s) if s.success() => (),
+        Ok(s)                => return Err(err(format!("ssh-keygen failed ({})", s))),
+        Err(e)               => {
+            return Err(CliError::Validation(format!("couldn't run ssh-keygen, is it installed? {}",
+                e)))
+        }
+    }
+    let public = public_of(path);
+    try!(restrict(path, 0o600).and_then(|_| restrict(&public, 0o644))
+                              .map_err(|e| err(e.to_string())));
+    read(&public.to_string_lossy()[..]).map(|mut keys| keys.remove(0))
+}
+
+#[cfg(unix)]
+fn restrict(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn restrict(_: &Path, _: u32) -> io::Result<()> {
+    Ok(())
+}
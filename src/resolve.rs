@@ -0,0 +1,134 @@
+use std::io::{self, Write};
+
+use clap::ArgMatches;
+
+use doapi::DoManager;
+use serde::json::Value;
+
+use client;
+use config::Config;
+use error::CliError;
+use pubkey;
+
+/// Turns the droplet argument of a command into a droplet ID, see `find`
+pub fn droplet(m: &ArgMatches, cfg: &Config, arg: &str) -> Result<String, CliError> {
+    if literal(m, cfg, arg) { return Ok(arg.to_owned()) }
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    report(cfg, client::send(&domgr.droplets(), &cfg.api).and_then(|r| {
+        find("droplet", arg, &r.data, &["name"])
+    }))
+}
+
+/// Turns the image argument of a command into an image ID, matching the names of snapshots and
+/// backups as well as the slugs of public images
+pub fn image(m: &ArgMatches, cfg: &Config, arg: &str) -> Result<String, CliError> {
+    if literal(m, cfg, arg) { return Ok(arg.to_owned()) }
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    report(cfg, client::send(&domgr.images(), &cfg.api).and_then(|r| {
+        find("image", arg, &r.data, &["slug", "name"])
+    }))
+}
+
+/// Turns the key argument of a command into an SSH key ID. Fingerprints are used as they are, and
+/// so are those of local public key files given by path.
+pub fn ssh_key(m: &ArgMatches, cfg: &Config, arg: &str) -> Result<String, CliError> {
+    if let Some(fp) = pubkey::local_fingerprint(arg) { return report(cfg, fp) }
+    if literal(m, cfg, arg) || arg.contains(':') { return Ok(arg.to_owned()) }
+    let domgr = DoManager::with_token(&cfg.auth[..]);
+    report(cfg, client::send(&domgr.ssh_keys(), &cfg.api).and_then(|r| {
+        find("SSH key", arg, &r.data, &["name"])
+    }))
+}
+
+// Numeric arguments are IDs already. `--id` says everything is, and nothing is looked up with
+// `--nosend`, the request is displayed with the name instead.
+fn literal(m: &ArgMatches, cfg: &Config, arg: &str) -> bool {
+    let sub = m.subcommand().1;
+    cfg.literal_ids
+        || m.is_present("as-id")
+        || sub.map(|s| s.is_present("as-id")).unwrap_or(false)
+        || cfg.no_send
+        || m.is_present("nosend")
+        || sub.map(|s| s.is_present("nosend")).unwrap_or(false)
+        || (!arg.is_empty() && arg.chars().all(|c| c.is_digit(10)))
+}
+
+// Failures are displayed like those of any other request since they end the command
+fn report(cfg: &Config, res: Result<String, CliError>) -> Result<String, CliError> {
+    if let Err(ref e) = res {
+        if cfg.output.is_structured() {
+            writeln!(&mut io::stderr(), "{}", e).ok();
+        } else {
+            println!("{}", e);
+        }
+    }
+    res
+}
+
+/// Picks the single object in `all` whose `fields` match `arg`. An exact match wins, otherwise
+/// `arg` is a glob when it has a `*` or `?` in it and a prefix when it doesn't. Anything but
+/// exactly one match is an error, listing the candidates when there are several.
+pub fn find(what: &str, arg: &str, all: &Value, fields: &[&str]) -> Result<String, CliError> {
+    let all = match all.as_array() {
+        Some(a) => a,
+        None    => return Err(CliError::Api(format!("unable to list the {}s", what)))
+    };
+    let mut found = matching(all, fields, &|n| n == arg);
+    if found.is_empty() {
+        found = if arg.contains('*') || arg.contains('?') {
+            matching(all, fields, &|n| glob(arg, n))
+        } else {
+            matching(all, fields, &|n| n.starts_with(arg))
+        };
+    }
+    match found.len() {
+        1 => Ok(id_of(found[0])),
+        0 => Err(CliError::NotFound(format!("no {} matches '{}'", what, arg))),
+        n => Err(CliError::Validation(format!("'{}' matches {} {}s, use a more specific name or \
+            the ID (or --id):\n\t{}", arg, n, what,
+            found.iter().map(|v| describe(v, fields)).collect::<Vec<_>>().join("\n\t"))))
+    }
+}
+
+fn matching<'a>(all: &'a [Value], fields: &[&str], test: &Fn(&str) -> bool) -> Vec<&'a Value> {
+    all.iter().filter(|v| {
+        fields.iter().any(|f| {
+            v.find(f).and_then(|n| n.as_string()).map(|n| test(n)).unwrap_or(false)
+        })
+    }).collect()
+}
+
+/// Matches `s` against a shell style pattern where `*` stands for any number of characters and
+/// `?` for exactly one
+pub fn glob(pattern: &str, s: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    glob_from(&p[..], &s[..])
+}
+
+fn glob_from(p: &[char], s: &[char]) -> bool {
+    if p.is_empty() { return s.is_empty() }
+    match p[0] {
+        '*' => (0..s.len() + 1).any(|i| glob_from(&p[1..], &s[i..])),
+        '?' => !s.is_empty() && glob_from(&p[1..], &s[1..]),
+        c   => !s.is_empty() && s[0] == c && glob_from(&p[1..], &s[1..]),
+    }
+}
+
+fn id_of(v: &Value) -> String {
+    match v.find("id") {
+        Some(&Value::U64(id)) => id.to_string(),
+        Some(&Value::I64(id)) => id.to_string(),
+        Some(&Value::F64(id)) => (id as u64).to_string(),
+        Some(&Value::String(ref id)) => id.clone(),
+        _                     => String::new()
+    }
+}
+
+// "web-1 (ID 1234)", using the first field the object has
+fn describe(v: &Value, fields: &[&str]) -> String {
+    let name = fields.iter().filter_map(|f| v.find(f).and_then(|n| n.as_string()))
+                     .next()
+                     .unwrap_or("");
+    format!("{} (ID {})", name, id_of(v))
+}
@@ -0,0 +1,293 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::json::{self, Value};
+use toml::{self, Parser};
+use yaml_rust::{Yaml, YamlLoader};
+
+use doapi::request::Droplet;
+
+use config::Profile;
+use error::CliError;
+use userdata;
+
+// Everything a droplet definition may contain
+const DROPLET_KEYS: [&'static str; 12] = ["name", "count", "region", "size", "image", "ssh_keys",
+    "backups", "ipv6", "private_networking", "user_data", "user_data_file", "user_data_template"];
+
+/// Reads a TOML, YAML or JSON file, depending on its extension, into a JSON value so that every
+/// format is dealt with the same way afterwards
+pub fn load(path: &str) -> Result<Value, CliError> {
+    let err = |e: String| CliError::Validation(format!("{}: {}", path, e));
+    let mut s = String::new();
+    if let Err(e) = File::open(path).and_then(|mut f| f.read_to_string(&mut s)) {
+        return Err(err(e.to_string()))
+    }
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("toml")               => {
+            let mut parser = Parser::new(&s[..]);
+            match parser.parse() {
+                Some(t) => Ok(from_toml(&toml::Value::Table(t))),
+                None    => {
+                    let e = &parser.errors[0];
+                    let (line, col) = parser.to_linecol(e.lo);
+                    Err(CliError::Validation(format!("{}:{}:{}: {}", path, line + 1, col + 1,
+                        e.desc)))
+                }
+            }
+        },
+        Some("yaml") | Some("yml") => {
+            match YamlLoader::load_from_str(&s[..]) {
+                Ok(docs) => Ok(docs.get(0).map(from_yaml).unwrap_or(Value::Null)),
+                Err(e)   => Err(err(e.to_string()))
+            }
+        },
+        Some("json")               => json::from_str(&s[..]).map_err(|e| err(e.to_string())),
+        _                          => Err(err("expected a .toml, .yaml, .yml or .json file"
+                                              .to_owned()))
+    }
+}
+
+fn from_toml(v: &toml::Value) -> Value {
+    match *v {
+        toml::Value::String(ref s)   => Value::String(s.clone()),
+        toml::Value::Integer(i)      => Value::I64(i),
+        toml::Value::Float(f)        => Value::F64(f),
+        toml::Value::Boolean(b)      => Value::Bool(b),
+        toml::Value::Datetime(ref d) => Value::String(d.clone()),
+        toml::Value::Array(ref a)    => Value::Array(a.iter().map(from_toml).collect()),
+        toml::Value::Table(ref t)    => {
+            Value::Object(t.iter().map(|(k, v)| (k.clone(), from_toml(v))).collect())
+        }
+    }
+}
+
+fn from_yaml(v: &Yaml) -> Value {
+    match *v {
+        Yaml::String(ref s)  => Value::String(s.clone()),
+        Yaml::Integer(i)     => Value::I64(i),
+        Yaml::Real(ref r)    => r.parse().map(Value::F64).unwrap_or(Value::String(r.clone())),
+        Yaml::Boolean(b)     => Value::Bool(b),
+        Yaml::Array(ref a)   => Value::Array(a.iter().map(from_yaml).collect()),
+        Yaml::Hash(ref h)    => {
+            Value::Object(h.iter().map(|(k, v)| (yaml_key(k), from_yaml(v))).collect())
+        },
+        _                    => Value::Null
+    }
+}
+
+fn yaml_key(k: &Yaml) -> String {
+    match *k {
+        Yaml::String(ref s) | Yaml::Real(ref s) => s.clone(),
+        Yaml::Integer(i)                        => i.to_string(),
+        Yaml::Boolean(b)                        => b.to_string(),
+        _                                       => String::new()
+    }
+}
+
+/// Builds the droplets described by a spec, which is either a single droplet definition or a
+/// `droplets` list of them. A definition with a `count` stands for that many droplets, numbered
+/// from 1 through the `{n}` in its name. Region, size and SSH keys fall back to the profile, and
+/// `user_data_file` is read relative to `dir`. With `user_data_template` the user data is rendered
+/// for each droplet, see `userdata::render`. Every problem found is reported at once.
+pub fn droplets(v: &Value, profile: &Profile, dir: &Path) -> Result<Vec<Droplet>, CliError> {
+    let mut errors = vec![];
+    let droplets = if v.find("droplets").is_some() {
+        // Settings next to the list would otherwise be ignored, i.e. a misspelt region
+        if let Some(map) = v.as_object() {
+            for k in map.keys().filter(|k| &k[..] != "droplets") {
+                errors.push(format!("unknown setting '{}', settings go in each droplet", k));
+            }
+        }
+        droplet_list(v, profile, dir, &mut errors)
+    } else {
+        match droplet_def(v, profile, dir) {
+            Ok(ds)  => ds,
+            Err(es) => {
+                errors.extend(es.into_iter().map(|e| format!("droplet: {}", e)));
+                vec![]
+            }
+        }
+    };
+    if errors.is_empty() { Ok(droplets) } else { Err(problems(errors)) }
+}
+
+/// The droplets of the `droplets` list in `v` (see `droplets`), adding whatever is wrong with them
+/// to `errors`
+pub fn droplet_list(v: &Value, profile: &Profile, dir: &Path, errors: &mut Vec<String>)
+                    -> Vec<Droplet> {
+    let defs = match v.find("droplets") {
+        Some(&Value::Array(ref a)) => &a[..],
+        Some(_)                    => {
+            errors.push("droplets: expected a list of droplets".to_owned());
+            return vec![]
+        },
+        None                       => return vec![]
+    };
+    let mut droplets = vec![];
+    for (i, d) in defs.iter().enumerate() {
+        match droplet_def(d, profile, dir) {
+            Ok(ds)  => droplets.extend(ds.into_iter()),
+            Err(es) => errors.extend(es.into_iter().map(|e| format!("droplets[{}]: {}", i, e)))
+        }
+    }
+    {
+        let mut names = BTreeSet::new();
+        for d in droplets.iter() {
+            if !names.insert(&d.name[..]) {
+                errors.push(format!("the name '{}' is used more than once", d.name));
+            }
+        }
+    }
+    droplets
+}
+
+/// A single error listing every problem found in a file
+pub fn problems(errors: Vec<String>) -> CliError {
+    CliError::Validation(format!("the spec has {} problem(s):\n\t{}", errors.len(),
+        errors.join("\n\t")))
+}
+
+fn droplet_def(d: &Value, profile: &Profile, dir: &Path) -> Result<Vec<Droplet>, Vec<String>> {
+    let map = match d.as_object() {
+        Some(m) => m,
+        None    => return Err(vec!["expected a table of settings".to_owned()])
+    };
+    let mut errors = vec![];
+    for k in map.keys().filter(|k| !DROPLET_KEYS.contains(&&k[..])) {
+        errors.push(format!("unknown setting '{}'", k));
+    }
+    let text = |k: &str, errors: &mut Vec<String>| -> Option<String> {
+        match map.get(k) {
+            Some(&Value::String(ref s)) => Some(s.clone()),
+            Some(_)                     => {
+                errors.push(format!("{} must be a string", k));
+                None
+            },
+            None                        => None
+        }
+    };
+    let flag = |k: &str, errors: &mut Vec<String>| -> bool {
+        match map.get(k) {
+            Some(&Value::Bool(b)) => b,
+            Some(_)               => {
+                errors.push(format!("{} must be true or false", k));
+                false
+            },
+            None                  => false
+        }
+    };
+    let name = text("name", &mut errors);
+    let image = text("image", &mut errors);
+    let region = text("region", &mut errors).or(profile.region.clone());
+    let size = text("size", &mut errors).or(profile.size.clone());
+    if name.is_none() { errors.push("no name given".to_owned()); }
+    if image.is_none() { errors.push("no image given".to_owned()); }
+    if region.is_none() { errors.push("no region given, and the profile has none".to_owned()); }
+    if size.is_none() { errors.push("no size given, and the profile has none".to_owned()); }
+    let count = match map.get("count") {
+        Some(c) => match c.as_u64() {
+            Some(c) if c > 0 => c,
+            _                => {
+                errors.push("count must be a number above 0".to_owned());
+                1
+            }
+        },
+        None    => 1
+    };
+    let ssh_keys = match map.get("ssh_keys") {
+        Some(&Value::Array(ref list)) => {
+            let keys: Vec<String> = list.iter().filter_map(|k| match *k {
+                Value::String(ref s)          => Some(s.clone()),
+                Value::I64(_) | Value::U64(_) => k.as_u64().map(|k| k.to_string()),
+                _                             => None
+            }).collect();
+            if keys.len() != list.len() {
+                errors.push("ssh_keys must be a list of names, IDs or fingerprints".to_owned());
+            }
+            Some(keys)
+        },
+        Some(_)                       => {
+            errors.push("ssh_keys must be a list of names, IDs or fingerprints".to_owned());
+            None
+        },
+        None if !profile.ssh_keys.is_empty() => Some(profile.ssh_keys.clone()),
+        None                          => None
+    };
+    let backups = flag("backups", &mut errors);
+    let ipv6 = flag("ipv6", &mut errors);
+    let private_networking = flag("private_networking", &mut errors);
+    let mut user_data = text("user_data", &mut errors);
+    if let Some(file) = text("user_data_file", &mut errors) {
+        if user_data.is_some() {
+            errors.push("user_data and user_data_file can't both be given".to_owned());
+        }
+        // Several definitions could ask for stdin, which can only be read once
+        if file == "-" {
+            errors.push("user_data_file can't be - in a file, only --user-data-file reads stdin"
+                            .to_owned());
+        } else {
+            match userdata::read(&dir.join(&file).to_string_lossy()[..]) {
+                Ok(s)  => user_data = Some(s),
+                Err(e) => errors.push(reason(e))
+            }
+        }
+    }
+    let template = flag("user_data_template", &mut errors);
+    let name = name.unwrap_or(String::new());
+    if count > 1 && !name.contains("{n}") {
+        errors.push(format!("a count of {} needs {{n}} in the name to tell the droplets apart",
+            count));
+    }
+    let names: Vec<String> = (1..count + 1).map(|n| name.replace("{n}", &n.to_string()[..]))
+                                           .collect();
+    for n in names.iter().filter(|n| !valid_name(n)) {
+        errors.push(format!("'{}' isn't a valid droplet name, only letters, digits, '.' and '-' \
+            are allowed", n));
+    }
+    if !errors.is_empty() { return Err(errors) }
+    let mut droplets = vec![];
+    for n in names {
+        let mut d = Droplet {
+            name: n,
+            region: region.clone().unwrap(),
+            size: size.clone().unwrap(),
+            image: image.clone().unwrap(),
+            ssh_keys: ssh_keys.clone(),
+            backups: backups,
+            ipv6: ipv6,
+            private_networking: private_networking,
+            user_data: None,
+        };
+        if let Some(ref data) = user_data {
+            let data = if template {
+                userdata::render(data, &userdata::vars(&d, profile))
+            } else {
+                Ok(data.clone())
+            };
+            match data.and_then(|data| userdata::check(&data[..]).map(|_| data)) {
+                Ok(data) => d.user_data = Some(data),
+                Err(e)   => errors.push(format!("{}: {}", d.name, reason(e)))
+            }
+        }
+        droplets.push(d);
+    }
+    if errors.is_empty() { Ok(droplets) } else { Err(errors) }
+}
+
+// The message of an error without the "Invalid request" it would start with
+fn reason(e: CliError) -> String {
+    match e {
+        CliError::Validation(s) => s,
+        e                       => e.to_string()
+    }
+}
+
+/// Droplet names double as hostnames
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 255
+        && name.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-')
+        && !name.starts_with('-') && !name.starts_with('.')
+}
@@ -0,0 +1,97 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use error::CliError;
+
+/// Reads a token from `path`, or from stdin when `path` is `-`. Files readable by anyone but
+/// their owner are refused, the same way ssh treats private keys.
+pub fn from_file(path: &str) -> Result<String, CliError> {
+    let mut s = String::new();
+    if path == "-" {
+        if let Err(e) = io::stdin().read_to_string(&mut s) {
+            return Err(CliError::Auth(format!("unable to read the token from stdin: {}", e)))
+        }
+        return Ok(s.trim().to_owned())
+    }
+    let path = expand_home(path).to_string_lossy().into_owned();
+    let path = &path[..];
+    try!(check_permissions(path));
+    let res = File::open(path).and_then(|mut f| f.read_to_string(&mut s));
+    if let Err(e) = res {
+        return Err(CliError::Auth(format!("unable to read the token from {}: {}", path, e)))
+    }
+    Ok(s.trim().to_owned())
+}
+
+/// Runs a credential helper through the shell and uses the first line it prints as the token.
+/// Its stderr and stdin are left alone so it can prompt for a passphrase.
+pub fn from_command(cmd: &str) -> Result<String, CliError> {
+    let out = shell(cmd).stdin(Stdio::inherit())
+                        .stderr(Stdio::inherit())
+                        .output();
+    match out {
+        Ok(ref o) if o.status.success() => {
+            Ok(String::from_utf8_lossy(&o.stdout[..]).lines().next().unwrap_or("").trim().to_owned())
+        },
+        Ok(o)  => Err(CliError::Auth(format!("the credential helper `{}` failed ({})", cmd, o.status))),
+        Err(e) => Err(CliError::Auth(format!("unable to run the credential helper `{}`: {}", cmd, e)))
+    }
+}
+
+/// Hides all but the last four characters of a token
+pub fn redact(tok: &str) -> String {
+    if tok.len() <= 4 { return "****".to_owned() }
+    format!("****{}", &tok[tok.len() - 4..])
+}
+
+/// Allows paths such as "~/.secrets/do" in the configuration file
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.starts_with("~/"), env::home_dir()) {
+        (true, Some(mut home)) => {
+            home.push(&path[2..]);
+            home
+        },
+        _                      => PathBuf::from(path)
+    }
+}
+
+#[cfg(unix)]
+fn shell(cmd: &str) -> Command {
+    let mut c = Command::new("sh");
+    c.arg("-c").arg(cmd);
+    c
+}
+
+#[cfg(not(unix))]
+fn shell(cmd: &str) -> Command {
+    let mut c = Command::new("cmd");
+    c.arg("/C").arg(cmd);
+    c
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &str) -> Result<(), CliError> {
+    use std::os::unix::fs::PermissionsExt;
+    match fs::metadata(path) {
+        Ok(md) => {
+            let mode = md.permissions().mode();
+            if mode & 0o077 != 0 {
+                return Err(CliError::Auth(format!("permissions {:o} for {} are too open, the token \
+                    file must only be accessible by its owner (i.e. chmod 600 {})",
+                    mode & 0o777, path, path)))
+            }
+            Ok(())
+        },
+        Err(e) => Err(CliError::Auth(format!("unable to read the token from {}: {}", path, e)))
+    }
+}
+
+#[cfg(not(unix))]
+fn check_permissions(path: &str) -> Result<(), CliError> {
+    fs::metadata(path).map(|_| ()).map_err(|e| {
+        CliError::Auth(format!("unable to read the token from {}: {}", path, e))
+    })
+}
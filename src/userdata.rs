@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+
+use doapi::request::Droplet;
+
+use config::Profile;
+use error::CliError;
+
+/// The most user data DigitalOcean accepts for a droplet, in bytes
+pub const MAX_SIZE: usize = 64 * 1024;
+
+/// Reads user data from a file, or from stdin when `path` is `-`
+pub fn read(path: &str) -> Result<String, CliError> {
+    let mut s = String::new();
+    let res = if path == "-" {
+        io::stdin().read_to_string(&mut s)
+    } else {
+        File::open(path).and_then(|mut f| f.read_to_string(&mut s))
+    };
+    match res {
+        Ok(_)  => Ok(s),
+        Err(e) => Err(CliError::Validation(format!("unable to read user data from {}: {}",
+            if path == "-" { "stdin" } else { path }, e)))
+    }
+}
+
+/// Makes sure DigitalOcean won't turn the user data down for its size
+pub fn check(data: &str) -> Result<(), CliError> {
+    if data.len() > MAX_SIZE {
+        return Err(CliError::Validation(format!("the user data is {} bytes, DigitalOcean accepts \
+            at most {}", data.len(), MAX_SIZE)))
+    }
+    Ok(())
+}
+
+/// The variables a user data template can use for a droplet: `droplet.name`, `droplet.region`,
+/// `droplet.size` and `droplet.image`, and `profile.name`, `profile.region` and `profile.size`
+/// when the profile has them
+pub fn vars(d: &Droplet, profile: &Profile) -> BTreeMap<String, String> {
+    let mut vars = BTreeMap::new();
+    vars.insert("droplet.name".to_owned(), d.name.clone());
+    vars.insert("droplet.region".to_owned(), d.region.clone());
+    vars.insert("droplet.size".to_owned(), d.size.clone());
+    vars.insert("droplet.image".to_owned(), d.image.clone());
+    if !profile.name.is_empty() { vars.insert("profile.name".to_owned(), profile.name.clone()); }
+    if let Some(ref r) = profile.region { vars.insert("profile.region".to_owned(), r.clone()); }
+    if let Some(ref s) = profile.size { vars.insert("profile.size".to_owned(), s.clone()); }
+    vars
+}
+
+/// Replaces every `{{ var }}` in `template` by its value from `vars`, or from the environment for
+/// `env.NAME`. A variable without a value is an error rather than silently left empty.
+pub fn render(template: &str, vars: &BTreeMap<String, String>) -> Result<String, CliError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None      => return Err(CliError::Validation("the user data template has a {{ \
+                without a matching }}".to_owned()))
+        };
+        let name = rest[start + 2..end].trim();
+        let value = if name.starts_with("env.") {
+            env::var(&name[4..]).ok()
+        } else {
+            vars.get(name).cloned()
+        };
+        match value {
+            Some(v) => out.push_str(&v[..]),
+            None    => return Err(CliError::Validation(format!("the user data template uses \
+                '{}', which has no value (known are {} and env.<NAME>)", name,
+                vars.keys().map(|k| &k[..]).collect::<Vec<_>>().join(", "))))
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
@@ -0,0 +1,444 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use serde::json::Value;
+
+use doapi::request::DnsRecord;
+
+use infra::{self, Op};
+
+/// The record types DigitalOcean supports, and so the only ones a zone may hold besides SOA
+pub const TYPES: [&'static str; 7] = ["A", "AAAA", "CNAME", "MX", "TXT", "SRV", "NS"];
+
+const CLASSES: [&'static str; 4] = ["IN", "CH", "HS", "CS"];
+
+/// The longest text DigitalOcean accepts for a TXT record, in characters
+pub const TXT_MAX: usize = 512;
+
+/// Writes a BIND zone file for `domain` holding `records` as listed by the API
+pub fn export(domain: &str, ttl: u64, records: &[Value]) -> String {
+    let mut zone = format!("$ORIGIN {}.\n$TTL {}\n", domain.trim_right_matches('.'), ttl);
+    for v in records.iter() {
+        zone.push_str(&line(&record_of(v))[..]);
+        zone.push('\n');
+    }
+    zone
+}
+
+/// A DNS record as listed by the API
+pub fn record_of(v: &Value) -> DnsRecord {
+    let text = |k: &str| v.find(k).and_then(|s| s.as_string()).map(|s| s.to_owned());
+    let number = |k: &str| v.find(k).and_then(|n| n.as_u64());
+    DnsRecord {
+        rec_type: text("type"),
+        name: text("name"),
+        data: text("data"),
+        priority: number("priority"),
+        port: number("port"),
+        weight: number("weight"),
+    }
+}
+
+pub fn to_json(rec: &DnsRecord) -> Value {
+    let text = |s: &Option<String>| s.clone().map(Value::String).unwrap_or(Value::Null);
+    let number = |n: Option<u64>| n.map(Value::U64).unwrap_or(Value::Null);
+    let mut map = BTreeMap::new();
+    map.insert("type".to_owned(), text(&rec.rec_type));
+    map.insert("name".to_owned(), text(&rec.name));
+    map.insert("data".to_owned(), text(&rec.data));
+    map.insert("priority".to_owned(), number(rec.priority));
+    map.insert("port".to_owned(), number(rec.port));
+    map.insert("weight".to_owned(), number(rec.weight));
+    Value::Object(map)
+}
+
+/// A record as a line of a zone file, i.e. `@ IN MX 10 mail.example.com.`
+pub fn line(rec: &DnsRecord) -> String {
+    let t = opt(&rec.rec_type);
+    let data = opt(&rec.data);
+    let rdata = match t {
+        "CNAME" | "NS" => host(data),
+        "MX"           => format!("{} {}", rec.priority.unwrap_or(0), host(data)),
+        "SRV"          => {
+            format!("{} {} {} {}", rec.priority.unwrap_or(0), rec.weight.unwrap_or(0),
+                rec.port.unwrap_or(0), host(data))
+        },
+        "TXT"          => quote(data),
+        _              => data.to_owned()
+    };
+    let name = match opt(&rec.name) {
+        "" => "@",
+        n  => n
+    };
+    format!("{}\tIN\t{}\t{}", name, t, rdata)
+}
+
+/// Whether two records are the same, regardless of how their host names are written
+pub fn same(a: &DnsRecord, b: &DnsRecord) -> bool {
+    same_key(a, b) && differences(a, b).is_empty()
+}
+
+/// Whether two records have the same type, name and data, which is what tells records apart
+pub fn same_key(a: &DnsRecord, b: &DnsRecord) -> bool {
+    let host = |s: &Option<String>| opt(s).trim_right_matches('.').to_lowercase();
+    let data = if opt(&a.rec_type) == "TXT" {
+        a.data == b.data
+    } else {
+        host(&a.data) == host(&b.data)
+    };
+    opt(&a.rec_type) == opt(&b.rec_type) && host(&a.name) == host(&b.name) && data
+}
+
+/// How the priority, port and weight of `new` differ from those of `old`
+pub fn differences(old: &DnsRecord, new: &DnsRecord) -> Vec<String> {
+    let number = |n: Option<u64>| n.map(|n| n.to_string()).unwrap_or("none".to_owned());
+    let mut diffs = vec![];
+    for &(what, a, b) in [("priority", old.priority, new.priority), ("port", old.port, new.port),
+                          ("weight", old.weight, new.weight)].iter() {
+        if a != b { diffs.push(format!("{}: {} -> {}", what, number(a), number(b))); }
+    }
+    diffs
+}
+
+/// A single step of making the records of a domain match a list
+pub struct RecordChange {
+    pub op: Op,
+    /// The ID of the existing record, for everything but `Create`
+    pub id: Option<String>,
+    /// The record wanted, or the one being deleted
+    pub record: DnsRecord,
+    /// The record as it was before an update
+    pub old: Option<DnsRecord>,
+}
+
+impl RecordChange {
+    pub fn to_json(&self) -> Value {
+        let mut map = match to_json(&self.record) {
+            Value::Object(map) => map,
+            _                  => BTreeMap::new()
+        };
+        map.insert("op".to_owned(), Value::String(self.op.name().to_owned()));
+        map.insert("id".to_owned(), self.id.clone().map(Value::String).unwrap_or(Value::Null));
+        Value::Object(map)
+    }
+}
+
+/// The fewest creates, updates and deletes that make the records `live` of a domain match
+/// `wanted`, along with how many already do. Records are matched by type, name and data, so only
+/// a different priority, port or weight makes an update. The NS records at the apex belong to
+/// DigitalOcean and are left alone unless `wanted` has some of its own. Creates come first and
+/// deletes last, so names keep resolving for as long as possible.
+pub fn diff(wanted: &[DnsRecord], live: &[Value]) -> (Vec<RecordChange>, usize) {
+    let apex_ns = |r: &DnsRecord| opt(&r.rec_type) == "NS" && opt(&r.name) == "@";
+    let own_ns = wanted.iter().any(|r| apex_ns(r));
+    let mut live: Vec<(String, DnsRecord)> = live.iter().map(|v| {
+        (infra::id_of("record", v), record_of(v))
+    }).filter(|&(_, ref r)| own_ns || !apex_ns(r)).collect();
+    let (mut creates, mut updates, mut unchanged) = (vec![], vec![], 0);
+    for w in wanted.iter() {
+        let found = live.iter().position(|&(_, ref l)| same_key(l, w));
+        match found {
+            Some(i) => {
+                let (id, old) = live.remove(i);
+                if differences(&old, w).is_empty() {
+                    unchanged += 1;
+                } else {
+                    updates.push(RecordChange { op: Op::Update, id: Some(id), record: copy(w),
+                                                old: Some(old) });
+                }
+            },
+            None    => {
+                creates.push(RecordChange { op: Op::Create, id: None, record: copy(w), old: None });
+            }
+        }
+    }
+    let deletes = live.into_iter().map(|(id, r)| {
+        RecordChange { op: Op::Delete, id: Some(id), record: r, old: None }
+    });
+    creates.extend(updates.into_iter());
+    creates.extend(deletes);
+    (creates, unchanged)
+}
+
+/// Request records can't be cloned
+pub fn copy(r: &DnsRecord) -> DnsRecord {
+    DnsRecord {
+        rec_type: r.rec_type.clone(),
+        name: r.name.clone(),
+        data: r.data.clone(),
+        priority: r.priority,
+        port: r.port,
+        weight: r.weight,
+    }
+}
+
+/// Whether `name` can be the target of a CNAME, MX, NS or SRV record: `@` for the domain itself,
+/// or a host name made of labels of at most 63 letters, digits, `-` and `_`
+pub fn is_host(name: &str) -> bool {
+    if name == "@" { return true }
+    let name = if name.ends_with('.') { &name[..name.len() - 1] } else { name };
+    !name.is_empty() && name.len() <= 253 && name.split('.').all(|l| {
+        !l.is_empty() && l.len() <= 63 && !l.starts_with('-') && !l.ends_with('-')
+            && l.chars().all(|c| c.is_digit(36) || c == '-' || c == '_')
+    })
+}
+
+fn opt(s: &Option<String>) -> &str {
+    s.as_ref().map(|s| &s[..]).unwrap_or("")
+}
+
+// DigitalOcean gives host names without the final dot, which a zone file needs to tell them from
+// names relative to the origin
+fn host(name: &str) -> String {
+    if name == "@" || name.ends_with('.') || !name.contains('.') {
+        name.to_owned()
+    } else {
+        format!("{}.", name)
+    }
+}
+
+// A single string of a zone file holds at most 255 characters, longer text is split up
+fn quote(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() { return "\"\"".to_owned() }
+    chars.chunks(255).map(|c| {
+        let s: String = c.iter().cloned().collect();
+        format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+    }).collect::<Vec<_>>().join(" ")
+}
+
+/// Reads the records of a BIND zone file for `domain`. Names end up relative to the domain and
+/// targets fully qualified without the final dot, like DigitalOcean has them. SOA records are
+/// left out since DigitalOcean writes its own, and so are TTLs since records can't have their
+/// own. Every problem is reported at once, along with its line.
+pub fn parse(zone: &str, domain: &str) -> Result<Vec<DnsRecord>, Vec<String>> {
+    let (entries, mut errors) = entries(zone);
+    let apex = format!("{}.", domain.trim_right_matches('.').to_lowercase());
+    let mut origin = apex.clone();
+    let mut last: Option<String> = None;
+    let mut records = vec![];
+    for (line, indented, fields) in entries {
+        if fields[0].starts_with('$') {
+            match (&fields[0].to_uppercase()[..], fields.get(1)) {
+                ("$ORIGIN", Some(o)) => origin = absolute(o, &origin),
+                ("$TTL", Some(_))    => {},
+                (d, _)               => {
+                    errors.push(format!("line {}: {} isn't supported here", line, d));
+                }
+            }
+            continue
+        }
+        let mut rest = &fields[..];
+        let name = if indented {
+            match last {
+                Some(ref n) => n.clone(),
+                None        => {
+                    errors.push(format!("line {}: the first record needs a name", line));
+                    continue
+                }
+            }
+        } else {
+            rest = &rest[1..];
+            absolute(&fields[0], &origin)
+        };
+        last = Some(name.clone());
+        // The TTL and the class may come in either order, and are both optional
+        let skip = rest.iter().take_while(|f| {
+            is_ttl(f) || CLASSES.contains(&&f.to_uppercase()[..])
+        }).count();
+        rest = &rest[skip..];
+        if rest.is_empty() {
+            errors.push(format!("line {}: no record type given", line));
+            continue
+        }
+        let t = rest[0].to_uppercase();
+        let rdata = &rest[1..];
+        if t == "SOA" { continue }
+        match record(&t[..], &name[..], rdata, &origin[..], &apex[..]) {
+            Ok(r)  => records.push(r),
+            Err(e) => errors.push(format!("line {}: {}", line, e))
+        }
+    }
+    if errors.is_empty() { Ok(records) } else { Err(errors) }
+}
+
+fn record(t: &str, name: &str, rdata: &[String], origin: &str, apex: &str)
+          -> Result<DnsRecord, String> {
+    if !TYPES.contains(&t) {
+        return Err(format!("type {} isn't supported, only {} are", t, TYPES.join(", ")))
+    }
+    let name = match relative(name, apex) {
+        Some(n) => n,
+        None    => return Err(format!("{} is outside of {}", name, apex))
+    };
+    let fields = match t {
+        "MX"  => 2,
+        "SRV" => 4,
+        "TXT" => cmp::max(rdata.len(), 1),
+        _     => 1
+    };
+    if rdata.len() != fields {
+        return Err(format!("a {} record takes {} value(s), not {}", t, fields, rdata.len()))
+    }
+    let number = |what: &str, s: &str| s.parse::<u64>().map_err(|_| {
+        format!("the {} of a {} record must be a number, not '{}'", what, t, s)
+    });
+    let target = |s: &str| {
+        let s = absolute(s, origin);
+        if s == apex { "@".to_owned() } else { s.trim_right_matches('.').to_owned() }
+    };
+    let mut rec = DnsRecord {
+        rec_type: Some(t.to_owned()),
+        name: Some(name),
+        data: None,
+        priority: None,
+        port: None,
+        weight: None,
+    };
+    let data = match t {
+        "A"            => {
+            if rdata[0].parse::<Ipv4Addr>().is_err() {
+                return Err(format!("'{}' isn't an IPv4 address", rdata[0]))
+            }
+            rdata[0].clone()
+        },
+        "AAAA"         => {
+            if rdata[0].parse::<Ipv6Addr>().is_err() {
+                return Err(format!("'{}' isn't an IPv6 address", rdata[0]))
+            }
+            rdata[0].clone()
+        },
+        "MX"           => {
+            rec.priority = Some(try!(number("priority", &rdata[0][..])));
+            target(&rdata[1][..])
+        },
+        "SRV"          => {
+            rec.priority = Some(try!(number("priority", &rdata[0][..])));
+            rec.weight = Some(try!(number("weight", &rdata[1][..])));
+            rec.port = Some(try!(number("port", &rdata[2][..])));
+            target(&rdata[3][..])
+        },
+        "TXT"          => rdata.iter().map(|s| unquote(s)).collect::<Vec<_>>().concat(),
+        _              => target(&rdata[0][..])
+    };
+    rec.data = Some(data);
+    Ok(rec)
+}
+
+// A name made absolute, `@` standing for the origin
+fn absolute(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_owned()
+    } else if name.ends_with('.') {
+        name.to_lowercase()
+    } else {
+        format!("{}.{}", name.to_lowercase(), origin)
+    }
+}
+
+// An absolute name relative to the apex of the domain, if it's part of it
+fn relative(name: &str, apex: &str) -> Option<String> {
+    if name == apex {
+        Some("@".to_owned())
+    } else if name.ends_with(&format!(".{}", apex)[..]) {
+        Some(name[..name.len() - apex.len() - 1].to_owned())
+    } else {
+        None
+    }
+}
+
+// TTLs are seconds, or a mix of weeks, days, hours, minutes and seconds such as 1h30m
+fn is_ttl(s: &str) -> bool {
+    s.chars().next().map(|c| c.is_digit(10)).unwrap_or(false)
+        && s.chars().all(|c| c.is_digit(10) || "smhdwSMHDW".contains(c))
+}
+
+fn unquote(s: &str) -> String {
+    if !(s.len() >= 2 && s.starts_with('"') && s.ends_with('"')) { return s.to_owned() }
+    let mut out = String::new();
+    let mut escaped = false;
+    for c in s[1..s.len() - 1].chars() {
+        if escaped || c != '\\' {
+            out.push(c);
+            escaped = false;
+        } else {
+            escaped = true;
+        }
+    }
+    out
+}
+
+// The entries of a zone: the line each starts on, whether it starts with blank space (making it
+// another record for the previous name) and its fields. Quoted strings keep their quotes,
+// comments are dropped and parentheses join lines.
+fn entries(zone: &str) -> (Vec<(usize, bool, Vec<String>)>, Vec<String>) {
+    let mut entries = vec![];
+    let mut errors = vec![];
+    let mut fields = vec![];
+    let mut field = String::new();
+    let (mut line, mut start) = (1, 1);
+    let (mut depth, mut indented, mut at_start) = (0, false, true);
+    let (mut quoted, mut escaped, mut comment) = (false, false, false);
+    for c in zone.chars() {
+        if comment && c != '\n' { continue }
+        comment = false;
+        if quoted {
+            field.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                quoted = false;
+            }
+            if c == '\n' { line += 1; }
+            continue
+        }
+        if at_start && c.is_whitespace() && c != '\n' && depth == 0 { indented = true; }
+        at_start = false;
+        match c {
+            '\n'                   => {
+                flush(&mut field, &mut fields);
+                if depth == 0 && !fields.is_empty() {
+                    entries.push((start, indented, fields));
+                    fields = vec![];
+                }
+                if depth == 0 { indented = false; }
+                line += 1;
+                at_start = true;
+            },
+            ';'                    => comment = true,
+            '('                    => {
+                flush(&mut field, &mut fields);
+                depth += 1;
+            },
+            ')'                    => {
+                flush(&mut field, &mut fields);
+                if depth == 0 {
+                    errors.push(format!("line {}: a ) without a matching (", line));
+                } else {
+                    depth -= 1;
+                }
+            },
+            c if c.is_whitespace() => flush(&mut field, &mut fields),
+            c                      => {
+                if fields.is_empty() && field.is_empty() { start = line; }
+                if c == '"' { quoted = true; }
+                field.push(c);
+            }
+        }
+    }
+    if quoted { errors.push(format!("line {}: a quoted string isn't closed", start)); }
+    if depth > 0 { errors.push(format!("line {}: a ( isn't closed", start)); }
+    flush(&mut field, &mut fields);
+    if !fields.is_empty() { entries.push((start, indented, fields)); }
+    (entries, errors)
+}
+
+fn flush(field: &mut String, fields: &mut Vec<String>) {
+    if !field.is_empty() {
+        fields.push(field.clone());
+        field.clear();
+    }
+}
@@ -0,0 +1,1160 @@
+extern crate openssl;
+extern crate serde;
+
+mod support;
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde::json::{self, Value};
+
+use support::Mock;
+
+// Exit codes, see CliError::exit_code
+const OK: i32 = 0;
+// What clap exits with when the command line is wrong
+const USAGE: i32 = 1;
+const API: i32 = 2;
+const AUTH: i32 = 3;
+const NOT_FOUND: i32 = 4;
+const INVALID: i32 = 5;
+const DECLINED: i32 = 8;
+const NETWORK: i32 = 7;
+const TIMEOUT: i32 = 10;
+const PARTIAL: i32 = 9;
+const ACTION_FAILED: i32 = 11;
+const DRIFT: i32 = 12;
+
+fn json_of(out: &str) -> Value {
+    json::from_str(out).ok().expect(&format!("not JSON:\n{}", out)[..])
+}
+
+#[test]
+fn list() {
+    let mock = Mock::start();
+    mock.run(&["list", "regions"]).expect(OK, &["Retrieving all regions...", "Success", "nyc3", "sfo1"]);
+    mock.run(&["list", "sizes"]).expect(OK, &["Retrieving all sizes...", "512mb", "2gb"]);
+    mock.run(&["list", "images"]).expect(OK, &["ubuntu-14-04-x64", "web-base"]);
+    mock.run(&["list", "images", "--private"]).expect(OK, &["web-base"]).lacks(&["ubuntu-14-04-x64"]);
+    mock.run(&["list", "images", "--applications"]).expect(OK, &["wordpress"]).lacks(&["web-base"]);
+    mock.run(&["list", "ssh-keys"]).expect(OK, &["Retrieving all SSH keys...", "laptop"]);
+    mock.run(&["list", "droplets"]).expect(OK, &["web-1", "web-2", "db-1"]);
+    mock.run(&["list", "domains"]).expect(OK, &["example.com"]);
+    mock.run(&["list", "account-actions"]).expect(OK, &["Retrieving all account actions...", "create"]);
+}
+
+#[test]
+fn account() {
+    let mock = Mock::start();
+    mock.run(&["account"]).expect(OK, &["Displaying account information...", "sammy@digitalocean.com"]);
+    mock.run(&["account", "actions"]).expect(OK, &["completed"]);
+    let id = json_of(&mock.run(&["-o", "json", "account", "actions"]).stdout[..])
+        .as_array().unwrap()[0].find("id").unwrap().as_u64().unwrap().to_string();
+    mock.run(&["account", "action", &id[..]]).expect(OK, &["Displaying account action ID", &id[..]]);
+    mock.run(&["account", "action", "1"]).expect(NOT_FOUND, &["Failed"]);
+}
+
+#[test]
+fn verbose_hides_token() {
+    let mock = Mock::start();
+    mock.run(&["--verbose", "account"]).expect(OK, &["Displaying sent request...", "****4a3a"])
+        .lacks(&[support::TOKEN]);
+    mock.run(&["account", "--verbose", "--show-token"]).expect(OK, &[support::TOKEN]);
+}
+
+#[test]
+fn domains() {
+    let mock = Mock::start();
+    mock.run(&["domains"]).expect(OK, &["Retrieving all domains...", "example.com"]);
+    mock.run(&["domains", "create", "example.org", "10.0.0.2"])
+        .expect(OK, &["Creating domain", "example.org", "Success"]);
+    mock.run(&["domains", "show-domain", "example.org"]).expect(OK, &["example.org"]);
+    mock.run(&["domains", "create", "example.org", "10.0.0.2"]).expect(INVALID, &["Failed"]);
+    mock.run(&["domains", "create", "example.net", "not-an-ip"]).expect(INVALID, &["Failed"]);
+    mock.run_with_input(&["domains", "delete", "example.org"], "n\n").expect(DECLINED, &["Warning"]);
+    mock.run(&["domains", "show-domain", "example.org"]).expect(OK, &["example.org"]);
+    mock.run_with_input(&["domains", "delete", "example.org"], "y\n").expect(OK, &["Success"]);
+    mock.run(&["domains", "show-domain", "example.org"]).expect(NOT_FOUND, &["Failed"]);
+}
+
+#[test]
+fn dns() {
+    let mock = Mock::start();
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &["Retrieving all DNS records...",
+        "10.0.0.1", "mail.example.com"]);
+    let out = mock.run(&["-o", "json", "dns", "example.com", "create-record", "TXT", "-N", "@",
+        "-d", "v=spf1 -all"]);
+    out.expect(OK, &["v=spf1 -all"]);
+    let id = json_of(&out.stdout[..]).find("id").unwrap().as_u64().unwrap().to_string();
+    mock.run(&["dns", "example.com", "record", &id[..]]).expect(OK, &["v=spf1 -all"]);
+    mock.run(&["dns", "example.com", "update-record", &id[..], "--noconfirm", "-d", "v=spf1 mx -all"])
+        .expect(OK, &["Updating DNS record", "v=spf1 mx -all"]);
+    mock.run(&["dns", "example.com", "record", &id[..]]).expect(OK, &["v=spf1 mx -all"]);
+    mock.run(&["dns", "example.com", "delete-record", &id[..], "--noconfirm"]).expect(OK, &["Success"]);
+    mock.run(&["dns", "example.com", "record", &id[..]]).expect(NOT_FOUND, &["Failed"]);
+    mock.run(&["dns", "example.invalid", "records"]).expect(NOT_FOUND, &["Failed"]);
+}
+
+#[test]
+fn record_validation() {
+    let mock = Mock::start();
+    let create = |args: &[&str]| {
+        let mut all = vec!["dns", "example.com", "create-record"];
+        all.extend(args.iter().cloned());
+        mock.run(&all[..])
+    };
+    create(&["A", "-N", "api", "-d", "10.0.0"]).expect(INVALID, &["--data must be an IPv4 address"]);
+    create(&["AAAA", "-N", "api", "-d", "10.0.0.5"]).expect(INVALID, &["--data must be an IPv6 address"]);
+    create(&["CNAME", "-N", "docs", "-d", "not a host"]).expect(INVALID, &["--data must be a host name"]);
+    create(&["A", "-N", "api"]).expect(INVALID, &["--data is needed for A records"]);
+    create(&["A", "-N", "api", "-d", "10.0.0.5", "-P", "10"])
+        .expect(INVALID, &["--priority only applies to MX and SRV records"]);
+    create(&["MX", "-d", "mail.example.com"]).expect(INVALID, &["--priority is needed for MX records"]);
+    create(&["MX", "-d", "mail.example.com", "-P", "ten"])
+        .expect(INVALID, &["--priority must be a number from 0 to 65535, not 'ten'"]);
+    create(&["SRV", "-N", "_sip._tcp", "-d", "sip.example.com", "-P", "10"])
+        .expect(INVALID, &["the record has 2 problems", "--port is needed", "--weight is needed"]);
+    let long = (0..600).map(|_| "x").collect::<Vec<_>>().concat();
+    create(&["TXT", "-N", "@", "-d", &long[..]])
+        .expect(INVALID, &["--data can't be longer than 512 characters for TXT records, not 600"]);
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &[]).lacks(&["api", "docs", "_sip"]);
+
+    create(&["SRV", "-N", "_sip._tcp", "-d", "sip.example.com.", "-P", "10", "-p", "5060", "-w", "5"])
+        .expect(OK, &["sip.example.com"]);
+    create(&["MX", "-d", "@", "-P", "20"]).expect(OK, &["Success"]);
+    mock.run(&["dns", "example.com", "update-record", "1", "--noconfirm", "-t", "A", "-d", "nope"])
+        .expect(INVALID, &["--data must be an IPv4 address for A records, not 'nope'"]);
+}
+
+#[test]
+fn droplets() {
+    let mock = Mock::start();
+    mock.run(&["droplets"]).expect(OK, &["Retrieving all droplets...", "web-1", "db-1"]);
+    mock.run(&["droplets", "neighbors"]).expect(OK, &["Retrieving all droplet neighbors...", "web-2"]);
+    mock.run(&["droplets", "upgrades"]).expect(OK, &["No upgrades to display"]);
+    mock.run(&["droplets", "create", "api-1", "-r", "nyc3", "-s", "1gb", "-i", "ubuntu-14-04-x64",
+        "-k", "3001", "--ipv6"]).expect(OK, &["Creating droplet with configuration...", "api-1"]);
+    mock.run(&["list", "droplets"]).expect(OK, &["api-1"]);
+    mock.run(&["droplets", "create", "api-2", "-r", "nyc3", "-i", "ubuntu-14-04-x64"])
+        .expect(INVALID, &["no size given"]);
+    mock.run(&["droplets", "create", "api-2", "-r", "mars1", "-s", "1gb", "-i", "ubuntu-14-04-x64"])
+        .expect(INVALID, &["Region is not available"]);
+    mock.run(&["droplets", "create", "api-2", "-r", "nyc3", "-s", "1gb", "-i", "no-such-image"])
+        .expect(NOT_FOUND, &["no image matches 'no-such-image'"]);
+    mock.run(&["droplets", "create", "api-2", "-r", "nyc3", "-s", "1gb", "-i", "no-such-image", "--id"])
+        .expect(INVALID, &["invalid image"]);
+    mock.run(&["droplets", "create", "api 2", "-r", "nyc3", "-s", "1gb", "-i", "ubuntu-14-04-x64"])
+        .expect(INVALID, &["hostname characters"]);
+}
+
+#[test]
+fn droplet_information() {
+    let mock = Mock::start();
+    mock.run(&["droplet", "1001"]).expect(OK, &["web-1"]);
+    mock.run(&["droplet", "1001", "kernels"]).expect(OK, &["Success"]);
+    mock.run(&["droplet", "1001", "snapshots"]).expect(OK, &["Retrieving all snapshots for droplet"]);
+    mock.run(&["droplet", "1001", "backups"]).expect(OK, &["Retrieving all backups for droplet"]);
+    mock.run(&["droplet", "1001", "actions"]).expect(OK, &["Retrieving all actions for droplet"]);
+    mock.run(&["droplet", "1001", "neighbors"]).expect(OK, &["web-2"]).lacks(&["db-1"]);
+    mock.run(&["droplet", "9999"]).expect(NOT_FOUND, &["Failed", "could not be found"]);
+}
+
+#[test]
+fn droplet_actions() {
+    let mock = Mock::start();
+    let simple = [
+        ("disable-backups", "disable_backups"),
+        ("reboot", "reboot"),
+        ("power-cycle", "power_cycle"),
+        ("shutdown", "shutdown"),
+        ("power-off", "power_off"),
+        ("power-on", "power_on"),
+        ("reset-password", "password_reset"),
+        ("enable-ipv6", "enable_ipv6"),
+        ("enable-private-networking", "enable_private_networking"),
+    ];
+    for &(cmd, action) in simple.iter() {
+        mock.run(&["droplet", "1001", cmd]).expect(OK, &["Success"]);
+        let out = mock.run(&["-o", "json", "droplet", "1001", cmd]);
+        out.expect(OK, &[]);
+        assert_eq!(action_type(&out), action);
+    }
+    let confirmed = [
+        (vec!["upgrade", "--noconfirm"], "upgrade"),
+        (vec!["restore", "2003", "--noconfirm"], "restore"),
+        (vec!["rebuild", "ubuntu-14-04-x64", "--noconfirm"], "rebuild"),
+        (vec!["change-kernel", "2233", "--noconfirm"], "change_kernel"),
+        (vec!["power-off"], "power_off"),
+        (vec!["resize", "2gb", "--disk"], "resize"),
+        (vec!["rename", "web-01", "--noconfirm"], "rename"),
+    ];
+    for &(ref args, action) in confirmed.iter() {
+        let mut full = vec!["-o", "json", "droplet", "1001"];
+        full.extend(args.iter().cloned());
+        let out = mock.run(&full[..]);
+        out.expect(OK, &[]);
+        assert_eq!(action_type(&out), action);
+    }
+    mock.run(&["droplet", "1001", "resize", "64gb"]).expect(INVALID, &["Failed"]);
+    mock.run_with_input(&["droplet", "1001", "rename", "web-02"], "n\n").expect(DECLINED, &[]);
+    let d = json_of(&mock.run(&["-o", "json", "droplet", "1001"]).stdout[..]);
+    assert_eq!(d.find("name").unwrap().as_string(), Some("web-01"));
+    assert_eq!(d.find("size_slug").unwrap().as_string(), Some("2gb"));
+
+    let out = mock.run(&["-o", "json", "droplet", "1001", "snapshot", "web-01-backup"]);
+    out.expect(OK, &[]);
+    assert_eq!(action_type(&out), "snapshot");
+    let id = json_of(&out.stdout[..]).find("id").unwrap().as_u64().unwrap().to_string();
+    mock.run(&["droplet", "1001", "action", &id[..]]).expect(OK, &["Displaying action", "Success"]);
+    mock.run(&["droplet", "1001", "snapshots"]).expect(OK, &["web-01-backup"]);
+    mock.run_with_input(&["droplet", "1001", "delete"], "n\n").expect(DECLINED, &[]);
+    mock.run(&["droplet", "1001", "delete", "--noconfirm"]).expect(OK, &["Deleting droplet", "Success"]);
+    mock.run(&["droplet", "1001"]).expect(NOT_FOUND, &[]);
+}
+
+fn action_type(out: &support::Run) -> String {
+    json_of(&out.stdout[..]).find("type").and_then(|t| t.as_string()).unwrap_or("").to_owned()
+}
+
+#[test]
+fn images() {
+    let mock = Mock::start();
+    mock.run(&["image", "2003"]).expect(OK, &["Displaying image", "web-base"]);
+    mock.run(&["image", "ubuntu-14-04-x64"]).expect(OK, &["14.04 x64"]);
+    mock.run(&["image", "2003", "rename", "web-base-v2", "--noconfirm"]).expect(OK, &["Success"]);
+    mock.run(&["image", "2003"]).expect(OK, &["web-base-v2"]);
+    let out = mock.run(&["-o", "json", "image", "2003", "transfer", "ams3", "--noconfirm"]);
+    out.expect(OK, &[]);
+    assert_eq!(action_type(&out), "transfer");
+    let id = json_of(&out.stdout[..]).find("id").unwrap().as_u64().unwrap().to_string();
+    let out = mock.run(&["-o", "json", "image", "2003", "convert"]);
+    out.expect(OK, &[]);
+    assert_eq!(action_type(&out), "convert");
+    let actions = json_of(&mock.run(&["-o", "json", "image", "2003", "actions"]).stdout[..]);
+    assert_eq!(actions.as_array().unwrap().len(), 2);
+    mock.run(&["image", "2003", "action", &id[..]]).expect(OK, &["Success"]);
+    mock.run(&["image", "2003", "transfer", "mars1", "--noconfirm"]).expect(INVALID, &["Failed"]);
+    mock.run(&["image", "2003", "delete", "--noconfirm"]).expect(OK, &["Deleting image", "Success"]);
+    mock.run(&["image", "2003"]).expect(NOT_FOUND, &[]);
+}
+
+#[test]
+fn ssh_keys() {
+    let mock = Mock::start();
+    mock.run(&["ssh-keys"]).expect(OK, &["laptop"]);
+    let out = mock.run(&["-o", "json", "ssh-keys", "create", "desktop",
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIDesktop me@desktop"]);
+    out.expect(OK, &["desktop"]);
+    let key = json_of(&out.stdout[..]);
+    let id = key.find("id").unwrap().as_u64().unwrap().to_string();
+    let fp = key.find("fingerprint").unwrap().as_string().unwrap().to_owned();
+    mock.run(&["ssh-keys", "key", &id[..]]).expect(OK, &["Displaying SSH key", "desktop"]);
+    mock.run(&["ssh-keys", "key", &fp[..]]).expect(OK, &["desktop"]);
+    mock.run(&["ssh-keys", "rename", &fp[..], "workstation", "--noconfirm"]).expect(OK, &["Success"]);
+    mock.run(&["ssh-keys", "key", &id[..]]).expect(OK, &["workstation"]);
+    mock.run(&["ssh-keys", "create", "again", "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIDesktop"])
+        .expect(INVALID, &["already in use"]);
+    mock.run(&["ssh-keys", "create", "bad", "not a key"]).expect(INVALID, &["Failed"]);
+    mock.run(&["ssh-keys", "destroy", &id[..], "--noconfirm"]).expect(OK, &["Destroying SSH key"]);
+    mock.run(&["ssh-keys", "key", &id[..]]).expect(NOT_FOUND, &[]);
+}
+
+#[test]
+fn ssh_keys_import() {
+    let mock = Mock::start();
+    let alice = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEB alice@home";
+    let bob = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgIC";
+    let carol = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMD carol";
+    let dir = mock.config.with_extension("keys");
+    fs::create_dir_all(&dir).unwrap();
+    for &(name, key) in [("alice.pub", alice), ("bob.pub", bob), ("id_carol.pub", carol)].iter() {
+        File::create(dir.join(name)).unwrap().write_all(key.as_bytes()).unwrap();
+    }
+    File::create(dir.join("id_carol")).unwrap().write_all(b"not the public key").unwrap();
+    let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+
+    mock.run(&["ssh-keys", "import", &path("alice.pub")[..], "--dry-run"])
+        .expect(OK, &["+ alice@home (d6:9a:30:45:17:cf:91:c4:27:94:f3:22:92:a3:00:0e)",
+            "1 to upload, 0 already there"]);
+    mock.run(&["ssh-keys", "import", &path("alice.pub")[..], "-n", "--noconfirm"])
+        .expect(OK, &[]).lacks(&["to upload", "Creating"]);
+    mock.run(&["ssh-keys", "import", &path("alice.pub")[..], "--noconfirm"])
+        .expect(OK, &["Creating SSH key", "alice@home", "Success"]);
+    mock.run(&["ssh-keys", "key", &path("alice.pub")[..]]).expect(OK, &["alice@home"]);
+
+    // The directory holds alice's key again, bob's which is named after its file and carol's
+    let out = mock.run(&["-o", "json", "ssh-keys", "import", &dir.to_string_lossy()[..],
+        "--dry-run"]);
+    out.expect(OK, &[]);
+    assert!(out.stderr.contains("already there as alice@home"));
+    let keys = json_of(&out.stdout[..]);
+    let names: Vec<&str> = keys.as_array().unwrap().iter()
+                               .filter_map(|k| k.find("name").and_then(|n| n.as_string())).collect();
+    assert_eq!(names, vec!["bob", "carol"]);
+    mock.run(&["ssh-keys", "import", &dir.to_string_lossy()[..], "--noconfirm"])
+        .expect(OK, &["2 to upload, 1 already there"]);
+    mock.run(&["ssh-keys", "import", &dir.to_string_lossy()[..], "--noconfirm"])
+        .expect(OK, &["0 to upload, 3 already there"]).lacks(&["Creating"]);
+
+    // A private key stands for the public one next to it
+    mock.run(&["ssh-keys", "rename", &path("id_carol")[..], "carol@work", "--noconfirm"])
+        .expect(OK, &["Success"]);
+    mock.run(&["ssh-keys", "key", &path("id_carol.pub")[..]]).expect(OK, &["carol@work"]);
+    mock.run(&["ssh-keys", "destroy", &path("bob.pub")[..], "--noconfirm"]).expect(OK, &["Success"]);
+    mock.run(&["ssh-keys", "import", &path("bob.pub")[..], "--name", "robert", "--noconfirm"])
+        .expect(OK, &["robert"]);
+    mock.run(&["ssh-keys", "import", &dir.to_string_lossy()[..], "--name", "all"])
+        .expect(INVALID, &["--name only applies to a single key"]);
+
+    mock.run(&["ssh-keys", "import"]).expect(INVALID, &["nothing to import"]);
+    mock.run(&["ssh-keys", "import", &path("id_carol")[..]])
+        .expect(INVALID, &["id_carol:1: not keys aren't supported"]);
+    mock.run(&["ssh-keys", "import", &path("missing.pub")[..]]).expect(INVALID, &["missing.pub"]);
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn ssh_keys_sync() {
+    let mock = Mock::start();
+    let alice = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEB";
+    let bob = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgIC bob@work";
+    let carol = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIAMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMD carol";
+    let team = mock.config.with_extension("team");
+    fs::create_dir_all(&team).unwrap();
+    for &(name, key) in [("alice.pub", alice), ("bob.pub", bob)].iter() {
+        File::create(team.join(name)).unwrap().write_all(key.as_bytes()).unwrap();
+    }
+    let team = team.to_string_lossy().into_owned();
+    mock.run(&["ssh-keys", "create", "alice-old", alice]).expect(OK, &["Success"]);
+
+    mock.run(&["ssh-keys", "sync", &team[..], "--dry-run"])
+        .expect(OK, &["+ bob@work (85:11:48:cb:b8:23:cb:85:ab:30:c3:0c:47:0a:1d:6d)",
+            "~ alice-old to alice (d6:9a:30:45:17:cf:91:c4:27:94:f3:22:92:a3:00:0e)",
+            "1 to upload, 1 to rename, 0 to destroy, 0 unchanged",
+            "1 key(s) of the account aren't in the source"]);
+    let out = mock.run(&["-o", "json", "ssh-keys", "sync", &team[..], "--prune", "--dry-run"]);
+    out.expect(OK, &[]);
+    let changes = json_of(&out.stdout[..]);
+    let ops: Vec<&str> = changes.as_array().unwrap().iter()
+                                .filter_map(|c| c.find("op").and_then(|o| o.as_string())).collect();
+    assert_eq!(ops, vec!["create", "update", "delete"]);
+    assert!(out.stderr.contains("- laptop"));
+    mock.run(&["ssh-keys", "sync", &team[..], "--prune", "-n", "--noconfirm"])
+        .expect(OK, &[]).lacks(&["to upload", "Destroying"]);
+    mock.run_with_input(&["ssh-keys", "sync", &team[..], "--prune"], "n\n")
+        .expect(DECLINED, &["- laptop"]);
+
+    mock.run(&["ssh-keys", "sync", &team[..], "--prune", "--noconfirm"])
+        .expect(OK, &["Creating SSH key", "Updating SSH key", "Destroying SSH key 3001"]);
+    mock.run(&["ssh-keys"]).expect(OK, &["alice", "bob@work"]).lacks(&["laptop", "alice-old"]);
+    mock.run(&["ssh-keys", "sync", &team[..], "--prune", "--noconfirm"])
+        .expect(OK, &["0 to upload, 0 to rename, 0 to destroy, 2 unchanged"]).lacks(&["Creating"]);
+
+    // authorized_keys lines may start with options
+    let authorized = mock.config.with_extension("authorized_keys");
+    File::create(&authorized).unwrap()
+        .write_all(format!("# the on-call\nfrom=\"10.0.0.1\" {}\n{}\n", carol, carol).as_bytes())
+        .unwrap();
+    mock.run(&["ssh-keys", "sync", &authorized.to_string_lossy()[..], "--noconfirm"])
+        .expect(OK, &["+ carol", "1 to upload", "2 key(s) of the account aren't in the source"]);
+    let nowhere = mock.config.with_extension("nowhere");
+    mock.run(&["ssh-keys", "sync", &nowhere.to_string_lossy()[..]]).expect(INVALID, &["nowhere"]);
+    fs::remove_dir_all(&team).ok();
+    fs::remove_file(&authorized).ok();
+}
+
+#[test]
+fn ssh_keys_generate() {
+    let mock = Mock::start();
+    let dir = mock.config.with_extension("generated");
+    let path = dir.join("throwaway");
+    let file = path.to_string_lossy().into_owned();
+    mock.run(&["--nosend", "--verbose", "ssh-keys", "generate", "throwaway", "--file", &file[..]])
+        .expect(OK, &["Displaying sent request...", "/account/keys"]);
+    assert!(!dir.exists());
+    mock.run(&["ssh-keys", "generate", "throwaway", "--file", &file[..], "-n"]).expect(OK, &[]);
+    assert!(!dir.exists());
+    mock.run(&["ssh-keys"]).expect(OK, &[]).lacks(&["throwaway"]);
+    let out = mock.run(&["-o", "json", "ssh-keys", "generate", "throwaway", "--file", &file[..]]);
+    out.expect(OK, &["\"name\": \"throwaway\""]);
+    let key = json_of(&out.stdout[..]);
+    assert!(key.find("id").and_then(|i| i.as_u64()).is_some());
+    let fp = key.find("fingerprint").and_then(|f| f.as_string()).unwrap().to_owned();
+    mock.run(&["ssh-keys", "key", &file[..]]).expect(OK, &[&fp[..], "throwaway"]);
+    assert_private(&path, 0o600);
+    assert_private(&dir, 0o700);
+    mock.run(&["ssh-keys", "generate", "again", "--file", &file[..]])
+        .expect(INVALID, &["throwaway already exists"]);
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(unix)]
+fn assert_private(path: &Path, expected: u32) {
+    use std::os::unix::fs::PermissionsExt;
+    assert_eq!(fs::metadata(path).unwrap().permissions().mode() & 0o777, expected);
+}
+
+#[cfg(not(unix))]
+fn assert_private(_: &Path, _: u32) {}
+
+#[test]
+fn structured_output() {
+    let mock = Mock::start();
+    let out = mock.run(&["--output", "json", "list", "droplets"]);
+    out.expect(OK, &[]).lacks(&["::", "Success"]);
+    assert_eq!(json_of(&out.stdout[..]).as_array().unwrap().len(), 3);
+    mock.run(&["-o", "csv", "list", "regions"]).expect(OK, &["slug", "nyc3"]).lacks(&["::"]);
+    mock.run(&["-o", "table", "list", "sizes"]).expect(OK, &["slug", "512mb"]).lacks(&["::"]);
+    mock.run(&["-o", "yaml", "account"]).expect(OK, &["---\n", "email: \"sammy@digitalocean.com\""]);
+    let err = mock.run(&["-o", "json", "droplet", "9999"]);
+    err.expect(NOT_FOUND, &[]);
+    assert!(err.stdout.is_empty());
+    assert!(err.stderr.contains("could not be found"));
+
+    // So are the errors found before anything is sent
+    let url = &mock.url[..];
+    for args in [vec!["-o", "json", "--profile", "nowhere", "list", "droplets"],
+                 vec!["-o", "json", "config", "switch", "nowhere"]].iter() {
+        let err = mock.raw(&args[..]);
+        err.expect(NOT_FOUND, &[]);
+        assert!(err.stdout.is_empty(), "{:?} wrote to stdout:\n{}", args, err.stdout);
+        assert!(err.stderr.contains("no profile named 'nowhere'"));
+    }
+    let err = mock.raw(&["-o", "yaml", "--api-url", url, "account"]);
+    err.expect(AUTH, &[]);
+    assert!(err.stdout.is_empty());
+    assert!(err.stderr.contains("No DigitalOcean Auth Token found"));
+}
+
+#[test]
+fn nosend() {
+    let mock = Mock::start();
+    mock.run(&["--nosend", "--verbose", "droplet", "1001", "delete", "--noconfirm"])
+        .expect(OK, &["Displaying sent request...", "/droplets/1001"]);
+    mock.run(&["droplet", "1001"]).expect(OK, &["web-1"]);
+}
+
+#[test]
+fn auth() {
+    let mock = Mock::start();
+    let url = &mock.url[..];
+    let wrong = (0..64).map(|_| "0").collect::<String>();
+    mock.raw(&["--api-url", url, "account"]).expect(AUTH, &["No DigitalOcean Auth Token found"]);
+    mock.raw(&["--token", "short", "--api-url", url, "account"]).expect(AUTH, &[]);
+    mock.raw(&["--token", &wrong[..], "--api-url", url, "account"]).expect(AUTH, &["Failed"]);
+    mock.raw_with_input(&["--token-file", "-", "--api-url", url, "account"], support::TOKEN)
+        .expect(OK, &["sammy@digitalocean.com"]);
+
+    // With the token on stdin there's no answer left there, so nothing is asked
+    mock.raw_with_input(&["--token-file", "-", "--api-url", url, "droplet", "1001", "delete"],
+        support::TOKEN).expect(INVALID, &["use --noconfirm"]);
+    mock.raw_with_input(&["--token-file", "-", "--api-url", url, "droplet", "1001", "delete",
+        "--noconfirm"], support::TOKEN).expect(OK, &["Success"]);
+}
+
+#[test]
+fn unreachable_api() {
+    let mock = Mock::start();
+    mock.raw(&["--token", support::TOKEN, "--api-url", "http://127.0.0.1:1/v2", "account"])
+        .expect(NETWORK, &["Failed"]);
+    // Something answering HTTP that isn't the DigitalOcean API
+    mock.raw(&["--token", support::TOKEN, "--api-url", &mock.url.replace("/v2", "/v1")[..], "account"])
+        .expect(NOT_FOUND, &["Failed"]);
+}
+
+#[test]
+fn proxy_certificates() {
+    let mock = Mock::start();
+    let (proxy, cert) = support::tls_proxy();
+    // Nothing vouches for a self-signed certificate, so the token is never sent
+    mock.raw_with_env(&["--token", support::TOKEN, "--api-url", "https://localhost:4443/v2", "account"],
+        &[("HTTPS_PROXY", &proxy[..])]).expect(NETWORK, &["certificate verify failed"]);
+    // Trusted, but issued to another host
+    mock.raw_with_env(&["--token", support::TOKEN, "--api-url", "https://127.0.0.1:4443/v2", "account"],
+        &[("HTTPS_PROXY", &proxy[..]), ("SSL_CERT_FILE", &cert[..])])
+        .expect(NETWORK, &["isn't valid for 127.0.0.1 (it names localhost)"]);
+    fs::remove_file(&cert).ok();
+}
+
+#[test]
+fn config_profiles() {
+    let mock = Mock::start();
+    let url = &mock.url[..];
+    let wrong = (0..64).map(|_| "0").collect::<String>();
+    mock.raw(&["config", "list"]).expect(OK, &["No profiles to display"]);
+    mock.raw(&["config", "add", "work", "--token", support::TOKEN, "--region", "nyc3", "--size",
+        "512mb", "--api-url", url]).expect(OK, &["Saving profile", "Success"]);
+    mock.raw(&["config", "add", "other", "--token", &wrong[..], "--api-url", url]).expect(OK, &[]);
+    mock.raw(&["config", "list"]).expect(OK, &["* ", "work", "other", "nyc3", "****4a3a"])
+        .lacks(&[support::TOKEN]);
+    // The default profile supplies the token, the API URL and the droplet defaults
+    mock.raw(&["account"]).expect(OK, &["sammy@digitalocean.com"]);
+    let out = mock.raw(&["-o", "json", "droplets", "create", "from-profile", "-i", "ubuntu-14-04-x64"]);
+    out.expect(OK, &[]);
+    let d = json_of(&out.stdout[..]);
+    assert_eq!(d.find("size_slug").unwrap().as_string(), Some("512mb"));
+    assert_eq!(d.find_path(&["region", "slug"]).unwrap().as_string(), Some("nyc3"));
+    mock.raw(&["--profile", "other", "account"]).expect(AUTH, &[]);
+    mock.raw(&["--profile", "missing", "account"]).expect(NOT_FOUND, &["no profile named 'missing'"]);
+    mock.raw(&["config", "switch", "other"]).expect(OK, &["Switching to profile"]);
+    mock.raw(&["account"]).expect(AUTH, &[]);
+    mock.raw(&["config", "switch", "missing"]).expect(NOT_FOUND, &["Failed"]);
+    mock.raw_with_input(&["config", "remove", "other"], "n\n").expect(DECLINED, &[]);
+    mock.raw(&["config", "remove", "other", "--noconfirm"]).expect(OK, &["Removing profile"]);
+    mock.raw(&["--profile", "work", "account"]).expect(OK, &["sammy@digitalocean.com"]);
+}
+
+#[test]
+fn ssh() {
+    let mock = Mock::start();
+    mock.run(&["ssh", "web-1", "--print"]).expect(OK, &["ssh root@104.131.3.233"]);
+    mock.run(&["ssh", "1002", "--print", "-u", "deploy", "-i", "/keys/do", "--", "sh", "-c", "echo hi"])
+        .expect(OK, &["ssh -i /keys/do deploy@104.131.3.234 sh -c 'echo hi'"]);
+    mock.run(&["ssh", "web-1", "--private", "--print"])
+        .expect(INVALID, &["droplet web-1 has no private address"]);
+    mock.run(&["ssh", "web-1", "--ipv6", "--print"]).expect(INVALID, &["has no IPv6 address"]);
+    mock.run(&["droplet", "web-1", "enable-ipv6"]).expect(OK, &[]);
+    mock.run(&["ssh", "web-1", "--ipv6", "--print"]).expect(OK, &["root@2604:A880:0800:0010"]);
+    mock.run(&["ssh", "db-1", "--wait", "--timeout", "0", "--print"])
+        .expect(TIMEOUT, &["droplet db-1 was still off after 0s"]);
+    mock.run(&["ssh", "nowhere", "--print"]).expect(NOT_FOUND, &[]);
+
+    // The user and the key may come from the profile
+    mock.raw(&["config", "add", "work", "--token", support::TOKEN, "--api-url", &mock.url[..],
+        "--ssh-user", "admin", "--ssh-identity", "/keys/work"]).expect(OK, &[]);
+    mock.raw(&["config", "list"]).expect(OK, &["ssh user: admin", "ssh identity: /keys/work"]);
+    mock.raw(&["ssh", "web-2", "--print"]).expect(OK, &["ssh -i /keys/work admin@104.131.3.234"]);
+    mock.raw(&["ssh", "web-2", "--print", "-u", "root"]).expect(OK, &["root@104.131.3.234"]);
+}
+
+#[test]
+fn inventory() {
+    let mock = Mock::start();
+    mock.run(&["inventory", "--format", "ansible-ini"])
+        .expect(OK, &["web-1 ansible_host=104.131.3.233\n", "[region_nyc3]\nweb-1\nweb-2\n",
+            "[web]\nweb-1\nweb-2\n", "[size_2gb]\ndb-1\n", "[image_ubuntu_14_04_x64]\ndb-1\nweb-1"])
+        .lacks(&["Retrieving"]);
+    mock.run(&["inventory", "-f", "ssh-config", "--match", "db-*"])
+        .expect(OK, &["Host db-1\n    HostName 104.131.3.235\n"]).lacks(&["web-1"]);
+    mock.run(&["inventory", "-f", "hosts"]).expect(OK, &["104.131.3.233\tweb-1\n", "104.131.3.235\tdb-1\n"]);
+
+    let out = mock.run(&["inventory", "-f", "ansible-json", "--group-by", "region"]);
+    let inv = json_of(&out.stdout[..]);
+    assert_eq!(inv.find_path(&["_meta", "hostvars", "web-2", "ansible_host"]).and_then(|a| a.as_string()),
+        Some("104.131.3.234"));
+    assert_eq!(inv.find_path(&["region_sfo1", "hosts"]).and_then(|h| h.as_array()).map(|h| h.len()), Some(1));
+    assert_eq!(inv.find_path(&["all", "hosts"]).and_then(|h| h.as_array()).map(|h| h.len()), Some(3));
+    assert!(inv.find("web").is_none());
+
+    // Prefixes that Ansible already has a use for make groups named like the others
+    mock.run(&["droplets", "create", "all-1", "-r", "ams3", "-s", "512mb", "-i", "ubuntu-14-04-x64"])
+        .expect(OK, &[]);
+    let inv = json_of(&mock.run(&["inventory", "-f", "ansible-json"]).stdout[..]);
+    assert_eq!(inv.find_path(&["all", "hosts"]).and_then(|h| h.as_array()).map(|h| h.len()), Some(4));
+    assert_eq!(inv.find_path(&["prefix_all", "hosts"]).and_then(|h| h.as_array()).map(|h| h.len()),
+        Some(1));
+
+    let out = mock.run(&["inventory", "-f", "prometheus-file-sd", "--region", "nyc3", "--port", "9101",
+                         "--group-by", "size", "--group-by", "prefix"]);
+    let groups = json_of(&out.stdout[..]);
+    let groups = groups.as_array().unwrap();
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].find("targets").and_then(|t| t.as_array()).and_then(|t| t[0].as_string()),
+        Some("104.131.3.233:9101"));
+    assert_eq!(groups[1].find_path(&["labels", "size"]).and_then(|s| s.as_string()), Some("1gb"));
+    assert_eq!(groups[1].find_path(&["labels", "prefix"]).and_then(|s| s.as_string()), Some("web"));
+    assert!(groups[1].find_path(&["labels", "region"]).is_none());
+
+    let out = mock.run(&["inventory", "-f", "hosts", "--private"]);
+    out.expect(OK, &[]).lacks(&["web-1"]);
+    assert!(out.stderr.contains("web-1 has no private address, it's left out"));
+}
+
+#[test]
+fn record_and_replay() {
+    let mock = Mock::start();
+    let tape = mock.config.with_extension("json");
+    let tape = tape.to_str().unwrap();
+    let recorded = mock.run(&["--record", tape, "-o", "json", "list", "droplets"]);
+    recorded.expect(OK, &["web-1"]);
+    let mut saved = String::new();
+    File::open(tape).unwrap().read_to_string(&mut saved).unwrap();
+    assert!(saved.contains("\"/droplets"));
+    assert!(!saved.contains(support::TOKEN));
+    // Neither a token nor a reachable API is needed to replay
+    let replayed = mock.raw(&["--replay", tape, "--api-url", "http://127.0.0.1:1/v2", "-o", "json",
+        "list", "droplets"]);
+    replayed.expect(OK, &[]);
+    assert_eq!(recorded.stdout, replayed.stdout);
+    mock.raw(&["--replay", tape, "list", "regions"]).expect(NETWORK, &["was never recorded"]);
+    mock.raw(&["--replay", "/nonexistent/tape.json", "account"]).expect(INVALID, &[]);
+    fs::remove_file(tape).ok();
+}
+
+#[test]
+fn replay_fixture() {
+    let mock = Mock::start();
+    let tape = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/reboot.json");
+    mock.raw(&["--replay", tape, "account"]).expect(OK, &["Displaying account information...",
+        "sammy@digitalocean.com"]);
+    mock.raw(&["--replay", tape, "droplet", "1001", "reboot"]).expect(OK, &["Rebooting droplet",
+        "Success"]);
+    mock.raw(&["--replay", tape, "droplet", "9999"]).expect(NOT_FOUND, &["could not be found"]);
+
+    // Errors without a code of their own are still told apart from a wrong command line
+    let failing = mock.write("cassette.json", r#"{"interactions": [{
+        "request": {"body": null, "method": "GET", "path": "/account"},
+        "response": {"body": "{\"id\": \"server_error\", \"message\": \"Server was unable to give you a response.\"}",
+            "content-type": "application/json; charset=utf-8", "ratelimit-limit": 1200.0,
+            "ratelimit-remaining": 1199.0, "ratelimit-reset": 1444931833.0,
+            "status": "500 Internal Server Error"}
+    }]}"#);
+    mock.raw(&["--replay", &failing[..], "account"]).expect(API, &["Server was unable"]);
+    fs::remove_file(&failing).ok();
+}
+
+#[test]
+fn wait_for_actions() {
+    let mock = Mock::start();
+    mock.run(&["droplet", "1001", "reboot", "--wait"])
+        .expect(OK, &["Waiting for action", "Status: completed"]);
+    // Only the finished action is printed
+    let out = mock.run(&["-o", "json", "droplet", "1001", "power-off", "--wait"]);
+    out.expect(OK, &[]);
+    assert_eq!(json_of(&out.stdout[..]).find("status").unwrap().as_string(), Some("completed"));
+
+    let out = mock.run(&["-o", "json", "droplet", "1001", "power-on"]);
+    let action = json_of(&out.stdout[..]);
+    assert_eq!(action.find("status").unwrap().as_string(), Some("in-progress"));
+    let id = action.find("id").unwrap().as_u64().unwrap().to_string();
+    mock.run(&["droplet", "1001", "wait", &id[..], "--timeout", "soon"]).expect(INVALID, &[]);
+    mock.run(&["droplet", "1001", "wait", &id[..], "--timeout", "0"])
+        .expect(TIMEOUT, &["Failed", "still in progress"]);
+    mock.run(&["droplet", "1001", "wait", &id[..]]).expect(OK, &["Success", "Status: completed"]);
+
+    mock.run(&["droplet", "1001", "snapshot", "fail-nightly", "--wait"])
+        .expect(ACTION_FAILED, &["Failed", "Status: errored"]);
+    let err = mock.run(&["-o", "json", "droplet", "1001", "snapshot", "fail-weekly", "--wait"]);
+    err.expect(ACTION_FAILED, &["errored"]);
+    assert!(err.stderr.contains("finished as errored"));
+}
+
+#[test]
+fn names_instead_of_ids() {
+    let mock = Mock::start();
+    mock.run(&["droplet", "web-1"]).expect(OK, &["web-1"]);
+    let d = json_of(&mock.run(&["-o", "json", "droplet", "db"]).stdout[..]);
+    assert_eq!(d.find("id").unwrap().as_u64(), Some(1003));
+    mock.run(&["droplet", "web"]).expect(INVALID, &["'web' matches 2 droplets", "web-1 (ID 1001)",
+        "web-2 (ID 1002)"]);
+    mock.run(&["droplet", "web-*", "reboot"]).expect(INVALID, &["matches 2 droplets"]);
+    mock.run(&["droplet", "*-2", "reboot"]).expect(OK, &["Rebooting droplet", "1002"]);
+    mock.run(&["droplet", "nope"]).expect(NOT_FOUND, &["no droplet matches 'nope'"]);
+    mock.run(&["droplet", "web-1", "--id"]).expect(NOT_FOUND, &["could not be found"]);
+    mock.run(&["--nosend", "--verbose", "droplet", "web-1", "reboot"]).expect(OK, &["/droplets/web-1/"]);
+
+    mock.run(&["image", "web-base"]).expect(OK, &["Displaying image", "2003"]);
+    mock.run(&["image", "word"]).expect(OK, &["2002"]);
+    mock.run(&["image", "*14.04*"]).expect(INVALID, &["'*14.04*' matches 2 images",
+        "ubuntu-14-04-x64 (ID 2001)", "wordpress (ID 2002)"]);
+    let out = mock.run(&["-o", "json", "droplet", "web-2", "rebuild", "web-base", "--noconfirm"]);
+    out.expect(OK, &[]);
+    assert_eq!(action_type(&out), "rebuild");
+
+    mock.run(&["ssh-keys", "key", "lap"]).expect(OK, &["Displaying SSH key", "3001"]);
+    mock.run(&["ssh-keys", "rename", "laptop", "old-laptop", "--noconfirm"]).expect(OK, &["Success"]);
+    mock.run(&["ssh-keys", "key", "laptop"]).expect(NOT_FOUND, &["no SSH key matches 'laptop'"]);
+
+    let out = mock.run(&["-o", "json", "droplets", "create", "api-1", "-r", "nyc3", "-s", "1gb",
+        "-i", "web-base", "-k", "old-laptop"]);
+    out.expect(OK, &[]);
+    let d = json_of(&out.stdout[..]);
+    assert_eq!(d.find_path(&["image", "id"]).unwrap().as_u64(), Some(2003));
+}
+
+#[test]
+fn bulk_operations() {
+    let mock = Mock::start();
+    mock.run(&["droplets", "reboot"]).expect(INVALID, &["--match, --tag, --region or --size"]);
+    mock.run(&["droplets", "reboot", "--match", "nope-*"]).expect(NOT_FOUND, &["no droplets match"]);
+    mock.run(&["droplets", "reboot", "--tag", "web", "--parallel", "none"])
+        .expect(INVALID, &["--parallel expects a number"]);
+    mock.run_with_input(&["droplets", "delete", "--region", "nyc3"], "n\n")
+        .expect(DECLINED, &["web-1 (ID 1001)", "web-2 (ID 1002)"])
+        .lacks(&["db-1"]);
+    // The prompt isn't mixed into the data
+    let out = mock.run_with_input(&["-o", "json", "droplets", "delete", "--region", "nyc3"], "n\n");
+    out.expect(DECLINED, &[]);
+    assert!(out.stdout.is_empty(), "the prompt went to stdout:\n{}", out.stdout);
+    assert!(out.stderr.contains("Are you sure you want to continue?"));
+
+    let out = mock.run(&["-o", "json", "droplets", "reboot", "--match", "web-*", "--noconfirm"]);
+    out.expect(OK, &[]);
+    let results = json_of(&out.stdout[..]);
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|r| r.find("success") == Some(&Value::Bool(true))));
+    assert!(out.stderr.contains("web-1 (ID 1001)"));
+
+    mock.run(&["droplets", "power-off", "--tag", "db", "--noconfirm"])
+        .expect(OK, &["db-1 (ID 1003): ", "Success"])
+        .lacks(&["web-1"]);
+    // Each request is displayed like those of single droplets, without the token
+    mock.run(&["droplets", "power-on", "--tag", "db", "--noconfirm", "-v"])
+        .expect(OK, &["Displaying sent request...", "/droplets/1003/actions", "****4a3a"])
+        .lacks(&[support::TOKEN]);
+    mock.run(&["droplets", "snapshot", "{name}-nightly", "--size", "1gb", "--noconfirm"])
+        .expect(OK, &["web-2 (ID 1002): "]);
+    mock.run(&["image", "web-2-nightly"]).expect(OK, &["Displaying image"]);
+
+    // DigitalOcean turns down anything asked of a broken-* droplet in the mock
+    mock.run(&["droplets", "create", "broken-1", "-r", "nyc3", "-s", "512mb", "-i",
+        "ubuntu-14-04-x64"]).expect(OK, &[]);
+    mock.run(&["droplets", "power-on", "--match", "*", "--noconfirm", "--parallel", "2"])
+        .expect(PARTIAL, &["broken-1 (ID", "Droplet is currently unavailable",
+            "1 of 4 operations failed"]);
+    mock.run(&["droplets", "reboot", "--match", "broken-*", "--noconfirm"])
+        .expect(INVALID, &["Droplet is currently unavailable"]);
+
+    mock.run(&["droplets", "delete", "--match", "web-*", "--noconfirm"]).expect(OK, &[]);
+    mock.run(&["list", "droplets"]).expect(OK, &["db-1"]).lacks(&["web-1", "web-2"]);
+}
+
+#[test]
+fn droplets_from_a_spec() {
+    let mock = Mock::start();
+    let cloud_init = mock.write("cloud-init", "#cloud-config\npackages:\n  - nginx\n");
+    let many = mock.write("spec.toml", &format!("
+        [[droplets]]
+        name = \"app-{{n}}\"
+        count = 2
+        region = \"nyc3\"
+        size = \"1gb\"
+        image = \"ubuntu-14-04-x64\"
+        ssh_keys = [\"laptop\"]
+        ipv6 = true
+        user_data_file = \"{}\"
+
+        [[droplets]]
+        name = \"cache\"
+        region = \"sfo1\"
+        size = \"512mb\"
+        image = \"web-base\"
+    ", cloud_init.rsplit('/').next().unwrap())[..]);
+    mock.run(&["droplets", "create", "--file", &many[..]])
+        .expect(OK, &["app-1", "app-2", "cache"]);
+    mock.run(&["list", "droplets"]).expect(OK, &["app-1", "app-2", "cache"]);
+    let d = json_of(&mock.run(&["-o", "json", "droplet", "app-2"]).stdout[..]);
+    assert_eq!(d.find("size_slug").unwrap().as_string(), Some("1gb"));
+
+    let one = mock.write("spec.yaml", "name: api\nregion: nyc3\nsize: 512mb\nimage: web-base\n");
+    mock.run(&["droplets", "create", "-f", &one[..]]).expect(OK, &["api"]);
+
+    // Nothing is sent unless the whole spec is valid
+    let invalid = mock.write("spec.json", r#"{"droplets": [
+        {"name": "queue-{n}", "count": 3, "image": "web-base", "colour": "red"},
+        {"name": "bad_name", "region": "nyc3", "size": "1gb", "image": "web-base"},
+        {"name": "solo", "count": 2, "region": "nyc3", "size": "1gb", "image": "web-base"}
+    ]}"#);
+    mock.run(&["droplets", "create", "--file", &invalid[..]])
+        .expect(INVALID, &["droplets[0]: unknown setting 'colour'",
+            "droplets[0]: no region given", "droplets[1]: 'bad_name' isn't a valid droplet name",
+            "droplets[2]: a count of 2 needs {n} in the name"]);
+    mock.run(&["list", "droplets"]).expect(OK, &[]).lacks(&["queue-1", "solo"]);
+    let out = mock.run(&["-o", "json", "droplets", "create", "--file", &invalid[..]]);
+    out.expect(INVALID, &[]);
+    assert_eq!(out.stdout, "");
+    assert!(out.stderr.contains("droplets[1]: 'bad_name' isn't a valid droplet name"));
+    let misspelt = mock.write("misspelt.toml", "regoin = \"nyc3\"\n\n[[droplets]]\nname = \"stray\"
+size = \"1gb\"\nimage = \"web-base\"\n");
+    mock.run(&["droplets", "create", "--file", &misspelt[..]])
+        .expect(INVALID, &["unknown setting 'regoin', settings go in each droplet"]);
+    mock.run(&["list", "droplets"]).expect(OK, &[]).lacks(&["stray"]);
+    mock.run(&["droplets", "create", "extra", "--file", &one[..]]).expect(INVALID, &["belong in the file"]);
+    mock.run(&["droplets", "create", "--file", &cloud_init[..]]).expect(INVALID, &["expected a .toml"]);
+    mock.run(&["droplets", "create", "extra"]).expect(INVALID, &["no image given"]);
+
+    // DigitalOcean may still turn down some of the droplets
+    let partly = mock.write("partly.json", r#"{"droplets": [
+        {"name": "edge-1", "region": "nyc3", "size": "512mb", "image": "web-base"},
+        {"name": "edge-2", "region": "mars1", "size": "512mb", "image": "web-base"}
+    ]}"#);
+    mock.run(&["droplets", "create", "--file", &partly[..]])
+        .expect(PARTIAL, &["Region is not available", "1 of 2 operations failed"]);
+    for f in [cloud_init, many, one, invalid, misspelt, partly].iter() { fs::remove_file(f).ok(); }
+}
+
+#[test]
+fn user_data_files() {
+    let mock = Mock::start();
+    let user_data = |name: &str| {
+        let d = json_of(&mock.run(&["-o", "json", "droplet", name]).stdout[..]);
+        d.find("user_data").and_then(|u| u.as_string()).unwrap_or("").to_owned()
+    };
+    let create = ["droplets", "create", "-r", "nyc3", "-s", "512mb", "-i", "web-base"];
+    let template = mock.write("yml", "#cloud-config\nhostname: {{ droplet.name }}\n\
+        region: {{droplet.region}}\npath: {{ env.PATH }}\n");
+
+    let mut args = create.to_vec();
+    args.extend(["cfg-1", "--user-data-file", &template[..], "--template"].iter().cloned());
+    mock.run(&args[..]).expect(OK, &[]);
+    assert_eq!(user_data("cfg-1"), format!("#cloud-config\nhostname: cfg-1\nregion: nyc3\n\
+        path: {}\n", std::env::var("PATH").unwrap()));
+
+    let mut args = create.to_vec();
+    args.extend(["cfg-2", "--user-data-file", &template[..]].iter().cloned());
+    mock.run(&args[..]).expect(OK, &[]);
+    assert!(user_data("cfg-2").contains("hostname: {{ droplet.name }}"));
+
+    let mut args = create.to_vec();
+    args.extend(["cfg-3", "--user-data-file", "-"].iter().cloned());
+    mock.run_with_input(&args[..], "#!/bin/sh\necho hi\n").expect(OK, &[]);
+    assert_eq!(user_data("cfg-3"), "#!/bin/sh\necho hi\n");
+    // stdin can't hold both the token and the user data
+    let mut args = vec!["--token-file", "-", "--api-url", &mock.url[..]];
+    args.extend(create.iter().cloned());
+    args.extend(["cfg-4", "--user-data-file", "-"].iter().cloned());
+    mock.raw_with_input(&args[..], support::TOKEN).expect(INVALID, &["give --user-data-file a file"]);
+
+    let unknown = mock.write("unknown.yml", "hostname: {{ droplet.colour }}\n");
+    let mut args = create.to_vec();
+    args.extend(["cfg-4", "--user-data-file", &unknown[..], "--template"].iter().cloned());
+    mock.run(&args[..]).expect(INVALID, &["uses 'droplet.colour', which has no value"]);
+    let huge = mock.write("huge.sh", &(0..70000).map(|_| "#").collect::<String>()[..]);
+    let mut args = create.to_vec();
+    args.extend(["cfg-4", "--user-data-file", &huge[..]].iter().cloned());
+    mock.run(&args[..]).expect(INVALID, &["DigitalOcean accepts at most 65536"]);
+    let mut args = create.to_vec();
+    args.extend(["cfg-4", "--user-data-file", &template[..], "-u", "echo"].iter().cloned());
+    mock.run(&args[..]).expect(INVALID, &["not both"]);
+    mock.run(&["list", "droplets"]).expect(OK, &["cfg-3"]).lacks(&["cfg-4"]);
+
+    let spec = mock.write("fleet.toml", &format!("
+        name = \"fleet-{{n}}\"
+        count = 2
+        region = \"sfo1\"
+        size = \"512mb\"
+        image = \"web-base\"
+        user_data_file = \"{}\"
+        user_data_template = true
+    ", template.rsplit('/').next().unwrap())[..]);
+    mock.run(&["droplets", "create", "--file", &spec[..]]).expect(OK, &[]);
+    assert!(user_data("fleet-2").contains("hostname: fleet-2\nregion: sfo1\n"));
+    let piped = mock.write("piped.yaml", "name: piped\nimage: web-base\nuser_data_file: \"-\"\n");
+    mock.run_with_input(&["droplets", "create", "--file", &piped[..]], "echo hi\n")
+        .expect(INVALID, &["user_data_file can't be - in a file"]);
+    for f in [template, unknown, huge, spec, piped].iter() { fs::remove_file(f).ok(); }
+}
+
+#[test]
+fn plan_and_apply() {
+    let mock = Mock::start();
+    let manifest = |worker_size: &str, www: bool| format!("
+        [[ssh_keys]]
+        name = \"deploy\"
+        public_key = \"ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIdeploy deploy@example.com\"
+
+        [[domains]]
+        name = \"example.org\"
+        ip = \"10.0.0.2\"
+
+        [[records]]
+        domain = \"example.org\"
+        type = \"A\"
+        name = \"@\"
+        data = \"10.0.0.2\"
+        {}
+        [[droplets]]
+        name = \"web-1\"
+        region = \"nyc3\"
+        size = \"512mb\"
+        image = \"ubuntu-14-04-x64\"
+
+        [[droplets]]
+        name = \"worker\"
+        region = \"sfo1\"
+        size = \"{}\"
+        image = \"web-base\"
+        ssh_keys = [\"deploy\"]
+    ", if www {
+        "[[records]]\ndomain = \"example.org\"\ntype = \"a\"\nname = \"www\"\ndata = \"10.0.0.2\"\n"
+    } else {
+        ""
+    }, worker_size);
+    let infra = mock.write("infra.toml", &manifest("512mb", true)[..]);
+    let state = mock.config.with_extension("infra.state.json");
+
+    mock.run(&["plan", "-f", &infra[..]])
+        .expect(OK, &["+ ssh_key/deploy", "+ domain/example.org", "+ record/example.org/A/www/10.0.0.2",
+            "+ droplet/worker", "5 to create, 0 to update, 0 to replace, 0 to delete, 1 unchanged"])
+        .lacks(&["droplet/web-1"]);
+    assert!(!state.exists());
+    mock.run_with_input(&["apply", "-f", &infra[..]], "n\n").expect(DECLINED, &["Warning"]);
+    mock.run(&["list", "droplets"]).expect(OK, &[]).lacks(&["worker"]);
+
+    // The records a new domain comes with are taken over instead of being created twice
+    mock.run(&["apply", "-f", &infra[..], "--noconfirm"]).expect(OK, &["+ droplet/worker"]);
+    mock.run(&["dns", "example.org", "records"]).expect(OK, &["www"]);
+    let mut s = String::new();
+    File::open(&state).unwrap().read_to_string(&mut s).unwrap();
+    let ids = json_of(&s[..]);
+    for a in ["ssh_key/deploy", "domain/example.org", "record/example.org/A/@/10.0.0.2",
+              "droplet/web-1", "droplet/worker"].iter() {
+        assert!(ids.find_path(&["resources", *a]).is_some(), "{} isn't in the state", a);
+    }
+    mock.run(&["plan", "-f", &infra[..]]).expect(OK, &["No changes"]);
+
+    // Only what was created or adopted is ever deleted
+    mock.write("infra.toml", &manifest("1gb", false)[..]);
+    let out = mock.run(&["-o", "json", "plan", "-f", &infra[..]]);
+    let changes = json_of(&out.stdout[..]);
+    assert_eq!(changes.as_array().map(|c| c.len()), Some(2));
+    mock.run(&["plan", "-f", &infra[..]])
+        .expect(OK, &["~ droplet/worker", "size: 512mb -> 1gb", "powered off to be resized, and rebooted",
+            "- record/example.org/A/www/10.0.0.2"]);
+    mock.run(&["apply", "-f", &infra[..], "--noconfirm"]).expect(OK, &[]);
+    mock.run(&["plan", "-f", &infra[..]]).expect(OK, &["No changes"]);
+    let worker = json_of(&mock.run(&["-o", "json", "list", "droplets"]).stdout[..]);
+    let worker = worker.as_array().unwrap().iter()
+                       .find(|d| d.find("name").and_then(|n| n.as_string()) == Some("worker")).unwrap();
+    assert_eq!(worker.find("status").and_then(|s| s.as_string()), Some("active"));
+    mock.run(&["droplet", "web-2", "resize", "2gb"]).expect(INVALID, &["powered off"]);
+    mock.run(&["dns", "example.org", "records"]).expect(OK, &[]).lacks(&["www"]);
+    mock.run(&["list", "droplets"]).expect(OK, &["web-2", "db-1"]);
+
+    // A droplet can't change its image in place, the same as its region
+    let rebuilt = manifest("1gb", false).replace("\"web-base\"", "\"ubuntu-14-04-x64\"");
+    mock.write("infra.toml", &rebuilt[..]);
+    mock.run(&["plan", "-f", &infra[..]])
+        .expect(OK, &["-/+ droplet/worker", "image: web-base -> ubuntu-14-04-x64",
+            "0 to create, 0 to update, 1 to replace, 0 to delete"]);
+    mock.run(&["drift", "-f", &infra[..]]).expect(DRIFT, &["droplet/worker"]);
+
+    // The replacement is created first, the old droplet stays when that fails
+    let worker_id = || {
+        let mut s = String::new();
+        File::open(&state).unwrap().read_to_string(&mut s).unwrap();
+        json_of(&s[..]).find_path(&["resources", "droplet/worker"])
+                       .and_then(|i| i.as_string()).unwrap().to_owned()
+    };
+    let old = worker_id();
+    mock.write("infra.toml", &rebuilt.replace("\"sfo1\"", "\"mars1\"")[..]);
+    mock.run(&["apply", "-f", &infra[..], "--noconfirm"]).expect(INVALID, &["Region is not available"]);
+    assert_eq!(worker_id(), old);
+    mock.run(&["droplet", &old[..]]).expect(OK, &["worker"]);
+    mock.write("infra.toml", &rebuilt[..]);
+    mock.run(&["apply", "-f", &infra[..], "--noconfirm"]).expect(OK, &["-/+ droplet/worker"]);
+    assert!(worker_id() != old);
+    mock.run(&["droplet", &old[..]]).expect(NOT_FOUND, &[]);
+    mock.run(&["plan", "-f", &infra[..]]).expect(OK, &["No changes"]);
+
+    let invalid = mock.write("broken.toml", "[[servers]]\nname = \"x\"\n\n[[records]]\ntype = \"SPF\"\n");
+    mock.run(&["plan", "-f", &invalid[..]])
+        .expect(INVALID, &["unknown section 'servers'", "records[0]: no domain given",
+            "records[0]: type SPF isn't one of"]);
+    for f in [infra, invalid].iter() { fs::remove_file(f).ok(); }
+    fs::remove_file(&state).ok();
+}
+
+#[test]
+fn drift() {
+    let mock = Mock::start();
+    let matching = mock.write("matching.yaml", "
+ssh_keys:
+  - name: laptop
+    public_key: ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQDaptop laptop@example.com
+domains:
+  - name: example.com
+    ip: 10.0.0.1
+records:
+  - { domain: example.com, type: A, data: 10.0.0.1 }
+  - { domain: example.com, type: CNAME, name: www, data: '@' }
+  - { domain: example.com, type: MX, data: mail.example.com, priority: 10 }
+droplets:
+  - { name: web-1, region: nyc3, size: 512mb, image: ubuntu-14-04-x64 }
+  - { name: web-2, region: nyc3, size: 1gb, image: '2001' }
+  - { name: db-1, region: sfo1, size: 2gb, image: 14.04 x64 }
+");
+    mock.run(&["drift", "-f", &matching[..]]).expect(OK, &["No drift"]);
+
+    let drifted = mock.write("drifted.yaml", "
+ssh_keys:
+  - name: laptop
+    public_key: ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQDaptop laptop@example.com
+domains:
+  - name: example.com
+    ip: 10.0.0.1
+records:
+  - { domain: example.com, type: A, data: 10.0.0.2 }
+  - { domain: example.com, type: CNAME, name: www, data: '@' }
+droplets:
+  - { name: web-1, region: nyc3, size: 512mb, image: ubuntu-14-04-x64 }
+  - { name: web-2, region: nyc3, size: 2gb, image: web-base }
+  - { name: cache, region: nyc3, size: 512mb, image: web-base }
+");
+    mock.run(&["drift", "-f", &drifted[..]])
+        .expect(DRIFT, &["differs    record/example.com/A/@/10.0.0.2", "data: 10.0.0.1 -> 10.0.0.2",
+            "differs    droplet/web-2 (ID 1002)", "size: 1gb -> 2gb",
+            "image: ubuntu-14-04-x64 -> web-base", "missing    droplet/cache",
+            "unexpected record/example.com/MX/@/mail.example.com", "unexpected droplet/db-1 (ID 1003)",
+            "1 missing, 2 unexpected, 2 different", "drifted from the manifest in 5 place(s)"])
+        .lacks(&["web-1", "NS"]);
+    let out = mock.run(&["-o", "json", "drift", "-f", &drifted[..]]);
+    assert_eq!(out.code, DRIFT);
+    let found = json_of(&out.stdout[..]);
+    assert_eq!(found.as_array().map(|f| f.len()), Some(5));
+    assert!(out.stderr.contains("drifted from the manifest"));
+
+    // Drift only ever reads the account
+    mock.run(&["list", "droplets"]).expect(OK, &["db-1"]).lacks(&["cache"]);
+    for f in [matching, drifted].iter() { fs::remove_file(f).ok(); }
+}
+
+#[test]
+fn zone_files() {
+    let mock = Mock::start();
+    mock.run(&["dns", "example.com", "export", "--format", "bind"])
+        .expect(OK, &["$ORIGIN example.com.\n$TTL 1800\n", "@\tIN\tNS\tns1.digitalocean.com.",
+            "www\tIN\tCNAME\t@", "@\tIN\tMX\t10 mail.example.com."])
+        .lacks(&["::"]);
+    mock.run(&["dns", "example.com", "export", "-n"]).expect(OK, &[]).lacks(&["$ORIGIN"]);
+
+    let zone = mock.write("db", "$ORIGIN example.com.
+$TTL 3600
+@   IN  SOA ns1.digitalocean.com. hostmaster.example.com. (
+            2015010101 ; serial
+            3600 600 604800 1800 )
+@           IN  NS    ns1.digitalocean.com.
+@      3600 IN  A     10.0.0.1
+api         IN  A     10.0.0.5
+            IN  AAAA  2001:db8::5
+mail.example.com. IN A 10.0.0.9  ; the mail server
+@           IN  TXT   \"v=spf1 mx\" \" -all\"
+_sip._tcp   IN  SRV   10 20 5060 sip
+$ORIGIN dev.example.com.
+ci          IN  CNAME build.example.org.
+");
+    let created = ["api\tIN\tA\t10.0.0.5", "api\tIN\tAAAA\t2001:db8::5", "mail\tIN\tA\t10.0.0.9",
+        "@\tIN\tTXT\t\"v=spf1 mx -all\"", "_sip._tcp\tIN\tSRV\t10 20 5060 sip.example.com.",
+        "ci.dev\tIN\tCNAME\tbuild.example.org.", "6 to create, 2 already there"];
+    mock.run(&["dns", "example.com", "import", &zone[..], "--dry-run"]).expect(OK, &created);
+    let out = mock.run(&["-o", "json", "dns", "example.com", "import", &zone[..], "--dry-run"]);
+    assert_eq!(json_of(&out.stdout[..]).as_array().map(|r| r.len()), Some(6));
+    mock.run(&["dns", "example.com", "import", &zone[..], "-n", "--noconfirm"]).expect(OK, &[]);
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &[]).lacks(&["10.0.0.5"]);
+    mock.run_with_input(&["dns", "example.com", "import", &zone[..]], "n\n")
+        .expect(DECLINED, &["Warning"]);
+
+    mock.run(&["dns", "example.com", "import", &zone[..], "--noconfirm"]).expect(OK, &created);
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &["10.0.0.5", "sip.example.com"]);
+    mock.run(&["dns", "example.com", "import", &zone[..]])
+        .expect(OK, &["0 to create, 8 already there"]).lacks(&["Warning"]);
+
+    // What's exported can be imported again as is
+    let exported = mock.write("exported.db",
+        &mock.run(&["dns", "example.com", "export"]).stdout[..]);
+    mock.run(&["dns", "example.com", "import", &exported[..], "--dry-run"])
+        .expect(OK, &["0 to create, 10 already there"]);
+
+    let invalid = mock.write("invalid.db", "www IN A 300.1.1.1
+x IN CAA 0 issue \"letsencrypt.org\"
+other.example.org. IN A 10.0.0.1
+mx IN MX mail
+txt IN TXT \"unterminated
+");
+    mock.run(&["dns", "example.com", "import", &invalid[..]])
+        .expect(INVALID, &["the zone has", "line 1: '300.1.1.1' isn't an IPv4 address",
+            "line 2: type CAA isn't supported", "line 3: other.example.org. is outside of example.com.",
+            "line 4: a MX record takes 2 value(s), not 1", "line 5: a quoted string isn't closed"]);
+    for f in [zone, exported, invalid].iter() { fs::remove_file(f).ok(); }
+}
+
+#[test]
+fn dns_sync() {
+    let mock = Mock::start();
+    let records = mock.write("records.yaml", "records:
+  - { type: A, data: 10.0.0.1 }
+  - { type: CNAME, name: www, data: '@' }
+  - { type: MX, data: mail.example.com, priority: 20 }
+  - { type: A, name: api, data: 10.0.0.5 }
+");
+    mock.run(&["dns", "example.com", "sync", &records[..], "--dry-run"])
+        .expect(OK, &["+ api\tIN\tA\t10.0.0.5", "~ @\tIN\tMX\t20 mail.example.com.", "priority: 10 -> 20",
+            "1 to create, 1 to update, 0 to delete, 2 unchanged"])
+        .lacks(&["ns1.digitalocean.com"]);
+    let out = mock.run(&["-o", "json", "dns", "example.com", "sync", &records[..], "--dry-run"]);
+    assert_eq!(json_of(&out.stdout[..]).as_array().map(|c| c.len()), Some(2));
+    mock.run(&["dns", "example.com", "sync", &records[..], "-n", "--noconfirm"]).expect(OK, &[]);
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &[]).lacks(&["10.0.0.5"]);
+
+    mock.run(&["dns", "example.com", "sync", &records[..], "--noconfirm"]).expect(OK, &["Making"]);
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &["10.0.0.5", "ns1.digitalocean.com"]);
+    mock.run(&["dns", "example.com", "sync", &records[..]]).expect(OK, &["No changes"]);
+
+    let fewer = mock.write("fewer.yaml", "records:
+  - { type: A, data: 10.0.0.1 }
+  - { type: MX, data: mail.example.com, priority: 20 }
+  - { type: A, name: api, data: 10.0.0.5 }
+");
+    mock.run(&["dns", "example.com", "sync", &fewer[..], "--noconfirm"])
+        .expect(OK, &["- www\tIN\tCNAME\t@", "0 to create, 0 to update, 1 to delete, 3 unchanged"]);
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &[]).lacks(&["www"]);
+
+    // A change that fails takes back the ones made before it
+    let failing = mock.write("failing.json", r#"{"records": [
+        {"type": "A", "data": "10.0.0.1"},
+        {"type": "A", "name": "new", "data": "10.0.0.7"},
+        {"type": "TXT", "name": "broken", "data": ""}
+    ]}"#);
+    mock.run(&["dns", "example.com", "sync", &failing[..], "--noconfirm"])
+        .expect(INVALID, &["Data needs to be a valid value", "change(s) already made"]);
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &["10.0.0.5", "mail.example.com"])
+        .lacks(&["10.0.0.7"]);
+
+    let invalid = mock.write("invalid.toml", "[[records]]\ntype = \"SPF\"\ndata = \"x\"\n
+[[records]]\ntype = \"A\"\ndata = \"10.0.0.1\"\n\n[[records]]\ntype = \"A\"\ndata = \"10.0.0.1\"\n");
+    mock.run(&["dns", "example.com", "sync", &invalid[..]])
+        .expect(INVALID, &["records[0]: type SPF isn't one of", "records[2]: @\tIN\tA\t10.0.0.1 is listed more than once"]);
+    for f in [records, fewer, failing, invalid].iter() { fs::remove_file(f).ok(); }
+}
+
+#[test]
+fn ddns() {
+    let mock = Mock::start();
+    let lookup = |address: &str| mock.url.replace("/v2", &format!("/ip?as={}", address)[..]);
+    let v4 = lookup("203.0.113.7");
+    mock.run(&["dns", "example.com", "ddns", "--name", "home", "--lookup-url", &v4[..]])
+        .expect(OK, &["A", "home.example.com", "created 203.0.113.7"]);
+    mock.run(&["dns", "example.com", "ddns", "--name", "home", "--lookup-url", &v4[..]])
+        .expect(OK, &["unchanged 203.0.113.7"]);
+
+    let moved = lookup("203.0.113.9");
+    mock.run(&["dns", "example.com", "ddns", "--name", "home", "--lookup-url", &moved[..], "-n"])
+        .expect(OK, &[]);
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &["203.0.113.7"]).lacks(&["203.0.113.9"]);
+    let out = mock.run(&["-o", "json", "dns", "example.com", "ddns", "--name", "home",
+                         "--lookup-url", &moved[..]]);
+    let check = json_of(&out.stdout[..]);
+    assert_eq!(check.find("status").and_then(|s| s.as_string()), Some("updated"));
+    assert_eq!(check.find("previous").and_then(|s| s.as_string()), Some("203.0.113.7"));
+    mock.run(&["dns", "example.com", "records"]).expect(OK, &["203.0.113.9"]).lacks(&["203.0.113.7"]);
+
+    let v6 = lookup("2001:db8::1");
+    mock.run(&["dns", "example.com", "ddns", "--name", "home", "-t", "AAAA", "--lookup-url", &v6[..]])
+        .expect(OK, &["created 2001:db8::1"]);
+    mock.run(&["dns", "example.com", "ddns", "--name", "home", "--lookup-url", &v6[..]])
+        .expect(NETWORK, &["rather than an IPv4 address"]);
+    let refused = mock.url.replace("/v2", "/ip");
+    mock.run(&["dns", "example.com", "ddns", "--name", "home", "--lookup-url", &refused[..]])
+        .expect(NETWORK, &["answered 401"]);
+    mock.run(&["dns", "example.com", "ddns", "--name", "home", "--lookup-url", &v4[..], "--daemon",
+               "--interval", "0"])
+        .expect(INVALID, &["--interval must be a number of seconds"]);
+
+    // The address comes from one place only
+    let out = mock.run(&["dns", "example.com", "ddns", "--name", "home", "--lookup-url", &v4[..],
+                         "--interface", "lo"]);
+    assert_eq!(out.code, USAGE);
+    assert!(out.stdout.contains("cannot be used with") || out.stderr.contains("cannot be used with"));
+}
@@ -0,0 +1,190 @@
+//! Runs docli against its own `docli-mock` server, each `Mock` is a fresh server with the same
+//! initial state so tests can't affect one another.
+#![allow(dead_code)]
+
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::thread;
+
+use openssl::crypto::hash::Type;
+use openssl::ssl::{SslContext, SslMethod, SslStream};
+use openssl::x509::X509Generator;
+
+pub const TOKEN: &'static str = "dd7d0c1e3c5e4a3a9b3b0c1e3c5e4a3a9b3b0c1e3c5e4a3a9b3b0c1e3c5e4a3a";
+
+static CONFIGS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+// Integration tests are built in target/<profile>/deps, next to the binaries' directory
+fn bin(name: &str) -> PathBuf {
+    let mut p = env::current_exe().unwrap();
+    p.pop();
+    if p.ends_with("deps") { p.pop(); }
+    p.push(format!("{}{}", name, env::consts::EXE_SUFFIX));
+    p
+}
+
+pub struct Mock {
+    child: Child,
+    pub url: String,
+    pub config: PathBuf,
+}
+
+/// The result of running docli once
+pub struct Run {
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Run {
+    fn from(o: Output) -> Run {
+        Run {
+            code: o.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&o.stdout[..]).into_owned(),
+            stderr: String::from_utf8_lossy(&o.stderr[..]).into_owned(),
+        }
+    }
+
+    /// Asserts the exit code and that stdout mentions every one of `wanted`
+    pub fn expect(&self, code: i32, wanted: &[&str]) -> &Run {
+        assert!(self.code == code, "expected exit code {}, got {}\nstdout:\n{}\nstderr:\n{}",
+            code, self.code, self.stdout, self.stderr);
+        for w in wanted.iter() {
+            assert!(self.stdout.contains(w), "expected {:?} in stdout:\n{}\nstderr:\n{}",
+                w, self.stdout, self.stderr);
+        }
+        self
+    }
+
+    pub fn lacks(&self, unwanted: &[&str]) -> &Run {
+        for u in unwanted.iter() {
+            assert!(!self.stdout.contains(u), "didn't expect {:?} in stdout:\n{}", u, self.stdout);
+        }
+        self
+    }
+}
+
+impl Mock {
+    pub fn start() -> Mock {
+        let mut child = Command::new(bin("docli-mock"))
+            .arg("--token").arg(TOKEN)
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok().expect("failed to start docli-mock, run `cargo build` first");
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap()).read_line(&mut line).unwrap();
+        let url = line.trim().to_owned();
+        // The port is unique to this mock, the counter to this test binary
+        let port = url.rsplit(':').next().unwrap_or("").trim_right_matches("/v2").to_owned();
+        let mut config = env::temp_dir();
+        config.push(format!("docli-test-{}-{}.toml", port, CONFIGS.fetch_add(1, Ordering::SeqCst)));
+        Mock {
+            child: child,
+            url: url,
+            config: config,
+        }
+    }
+
+    /// Writes a temporary file for the test, named after its config with the extension `ext`,
+    /// and returns its path. It's left to the test to remove it.
+    pub fn write(&self, ext: &str, contents: &str) -> String {
+        let path = self.config.with_extension(ext);
+        File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut c = Command::new(bin("docli"));
+        c.env("DOCLI_CONFIG", &self.config)
+         .env_remove("DO_AUTH_TOKEN")
+         .env_remove("DOCLI_PROFILE")
+         .env_remove("DOCLI_API_URL")
+         .env_remove("HTTP_PROXY")
+         .env_remove("http_proxy")
+         .env_remove("HTTPS_PROXY")
+         .env_remove("https_proxy")
+         .args(args)
+         .stdin(Stdio::piped())
+         .stdout(Stdio::piped())
+         .stderr(Stdio::piped());
+        c
+    }
+
+    /// Runs `docli --token <token> --api-url <mock> <args>...`
+    pub fn run(&self, args: &[&str]) -> Run {
+        self.run_with_input(args, "")
+    }
+
+    /// Same as `run`, with `input` on stdin (i.e. to answer a confirmation prompt)
+    pub fn run_with_input(&self, args: &[&str], input: &str) -> Run {
+        let mut full = vec!["--token", TOKEN, "--api-url", &self.url[..]];
+        full.extend(args.iter().cloned());
+        self.raw_with_input(&full[..], input)
+    }
+
+    /// Runs docli with exactly `args`, no token or API URL added
+    pub fn raw(&self, args: &[&str]) -> Run {
+        self.raw_with_input(args, "")
+    }
+
+    pub fn raw_with_input(&self, args: &[&str], input: &str) -> Run {
+        let mut child = self.command(args).spawn().unwrap();
+        child.stdin.as_mut().unwrap().write_all(input.as_bytes()).ok();
+        Run::from(child.wait_with_output().unwrap())
+    }
+
+    /// Same as `raw`, with the environment variables `vars` set (i.e. `HTTPS_PROXY`)
+    pub fn raw_with_env(&self, args: &[&str], vars: &[(&str, &str)]) -> Run {
+        let mut c = self.command(args);
+        for &(k, v) in vars { c.env(k, v); }
+        Run::from(c.output().unwrap())
+    }
+}
+
+/// Starts a proxy that accepts every `CONNECT` and then answers TLS itself with a self-signed
+/// certificate for `localhost`. Returns the proxy's URL and the certificate's path (PEM), which
+/// it's left to the test to remove.
+pub fn tls_proxy() -> (String, String) {
+    let (cert, key) = X509Generator::new()
+        .set_bitlength(2048)
+        .set_valid_period(1)
+        .add_name("CN".to_owned(), "localhost".to_owned())
+        .set_sign_hash(Type::SHA256)
+        .generate()
+        .unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let mut path = env::temp_dir();
+    path.push(format!("docli-test-proxy-{}.pem", port));
+    cert.write_pem(&mut File::create(&path).unwrap()).unwrap();
+    let mut ctx = SslContext::new(SslMethod::Sslv23).unwrap();
+    ctx.set_certificate(&cert).unwrap();
+    ctx.set_private_key(&key).unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream { Ok(s) => s, Err(_) => continue };
+            let mut request = vec![];
+            let mut byte = [0u8; 1];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut byte) { Ok(1) => request.push(byte[0]), _ => break }
+            }
+            stream.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").ok();
+            // docli is expected to hang up during the handshake
+            SslStream::accept(&ctx, stream).ok();
+        }
+    });
+    (format!("http://127.0.0.1:{}", port), path.to_str().unwrap().to_owned())
+}
+
+impl Drop for Mock {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+        ::std::fs::remove_file(&self.config).ok();
+    }
+}